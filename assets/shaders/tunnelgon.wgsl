@@ -6,17 +6,34 @@
 @group(2) @binding(0) var prev: texture_2d<f32>;
 @group(2) @binding(1) var prev_sampler: sampler;
 @group(2) @binding(2) var<storage, read> params: TunnelgonParams;
+@group(2) @binding(3) var<storage, read> lasers: array<TunnelgonLaser>;
+@group(2) @binding(4) var<storage, read> rings: array<TunnelgonRing>;
 
 
 struct TunnelgonParams {
-    rings_pos: array<f32, 8>,
-    rings_amp: array<f32, 8>,
-    laser: array<f32, 8>,
     spiral_freq: f32,
     spiral_skew: f32,
     spiral_dir: f32,
     spiral_accum: f32,
     tun_accum: f32,
+    tun_accum_target: f32,
+    primary: vec4<f32>,
+    secondary: vec4<f32>,
+    accent: vec4<f32>,
+}
+
+struct TunnelgonLaser {
+    angle: f32,
+    value: f32,
+    width: f32,
+    color: vec4<f32>,
+}
+
+struct TunnelgonRing {
+    pos: f32,
+    amp: f32,
+    thickness: f32,
+    color: vec4<f32>,
 }
 
 const PI: f32 = 3.14159;
@@ -33,11 +50,18 @@ fn tunnel(uv: vec2<f32>, size: f32) -> vec2<f32>
     return vec2((a+PI) / PI, ((size * (cos(PI/6)/cos(h))) / r));
 }
 
-fn laser(uvt: vec2<f32>, index: f32, intensity: f32) -> f32 {
-    return ((1.-smoothstep(0.005, 0.02, abs((uvt.x - (index+3.5)/3. - 0.5)%2. + 0.5))))*intensity;
+// Distance on a circle with the given circumference
+fn wrapped_distance(a: f32, b: f32, period: f32) -> f32 {
+    let d = abs(a - b) % period;
+    return min(d, period - d);
 }
-fn ring(uvt: vec2<f32>, pos: f32, intensity: f32) -> f32 {
-    return (1-smoothstep(0.005, 0.01, abs(uvt.y-(pos*1.5 + 0.1))))*intensity;
+
+fn laser(uvt: vec2<f32>, l: TunnelgonLaser) -> f32 {
+    let d = wrapped_distance(uvt.x, (l.angle + PI) / PI, 2.);
+    return (1.-smoothstep(l.width*0.25, l.width, d))*l.value;
+}
+fn ring(uvt: vec2<f32>, r: TunnelgonRing) -> f32 {
+    return (1-smoothstep(r.thickness*0.5, r.thickness, abs(uvt.y-(r.pos*1.5 + 0.1))))*r.amp;
 }
 
 fn rot2(a: f32) -> mat2x2<f32> {
@@ -78,30 +102,36 @@ fn fragment(mesh: VertexOutput) -> @location(0) vec4<f32> {
 
     let uvt = tunnel(uvc, 0.1);
 
-    var mask = 1.-smoothstep(0.15, 0.25, abs((uvt.y*12. + globals.time*2. + uvt.x*-3. + params.tun_accum) % 1. - 0.5));
+    var mask = 1.-smoothstep(0.15, 0.25, abs((uvt.y*params.spiral_freq + uvt.x*params.spiral_skew*params.spiral_dir + params.tun_accum) % 1. - 0.5));
     let fog = 1.-smoothstep(0., 1.5, uvt.y);
     let fog_laser = 1.-smoothstep(0.5, 2., uvt.y);
 
-    // Laser
-    let lasermask = laser(uvt, 0., params.laser[0]) + laser(uvt, 1., params.laser[1])
-    + laser(uvt, 2., params.laser[2]) + laser(uvt, 3., params.laser[3])
-    + laser(uvt, 4., params.laser[4]) + laser(uvt, 5., params.laser[5]);
-
-    let ringmask = ring(uvt, params.rings_pos[0], params.rings_amp[0]) +
-    ring(uvt, params.rings_pos[1], params.rings_amp[1]) +
-    ring(uvt, params.rings_pos[2], params.rings_amp[2]) +
-    ring(uvt, params.rings_pos[3], params.rings_amp[3]) +
-    ring(uvt, params.rings_pos[4], params.rings_amp[4]) +
-    ring(uvt, params.rings_pos[5], params.rings_amp[5]) +
-    ring(uvt, params.rings_pos[6], params.rings_amp[6]) +
-    ring(uvt, params.rings_pos[7], params.rings_amp[7]);
+    // Lasers and rings, their colour replaces the palette by its alpha. Secondary and accent
+    // colours of the hexagon replace the built-in palettes the same way.
+    let laser_palette = mix(palette6(uvt.y*0.5-globals.time*4.), params.secondary.rgb, params.secondary.a);
+    var lasermask = 0.;
+    var lasercol = vec3<f32>(0.);
+    for (var i = 0u; i < arrayLength(&lasers); i++) {
+        let m = laser(uvt, lasers[i]);
+        lasermask += m;
+        lasercol += mix(laser_palette, lasers[i].color.rgb, lasers[i].color.a) * m;
+    }
+
+    let ring_palette = mix(palette1(uvt.y*0.5), params.accent.rgb, params.accent.a);
+    var ringmask = 0.;
+    var ringcol = vec3<f32>(0.);
+    for (var i = 0u; i < arrayLength(&rings); i++) {
+        let m = ring(uvt, rings[i]);
+        ringmask += m;
+        ringcol += mix(ring_palette, rings[i].color.rgb, rings[i].color.a) * m;
+    }
 
     var off_samp = textureSample(prev, prev_sampler, mesh.position.xy/vec2<f32>(1920,1080) + samp.xy*0.001*rot2(length(uvc*10.)));
 
-    var out = vec4<f32>(abs(palette4(uvt.y+globals.time*0.2)*(mask-lasermask)*fog*2. + palette6(uvt.y*0.5-globals.time*4.)*lasermask*fog_laser*150. + palette1(uvt.y*0.5)*ringmask*fog*150.), 1);
+    let tunnel_palette = mix(palette4(uvt.y+globals.time*0.2), params.primary.rgb, params.primary.a);
+    var out = vec4<f32>(abs(tunnel_palette*(mask-lasermask)*fog*2. + lasercol*fog_laser*150. + ringcol*fog*150.), 1);
     //out = mix(out + off_samp * 0.8, out, 1.-smoothstep(0.1, 0.2, length(out.rgb)));
     return out;
-    //return vec4<f32>(params.rings_pos[0], params.rings_amp[0]*0, 0, 1);
     //return vec4<f32>(lasermask, mask*fog*0.8745098039215686, mask*fog*0.1843137254901961, 1.)*0.9 ;
     //return vec4<f32>(params.tun_accum % 1., mask*fog*0.8745098039215686, mask*fog*0.1843137254901961, 1.)*0.9 ;
 }
\ No newline at end of file
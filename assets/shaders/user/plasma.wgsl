@@ -0,0 +1,36 @@
+// Example user shader for the Shadergon. Copy it to start a new one, the uniform block and the
+// bindings have to stay the same.
+#import bevy_sprite::mesh2d_vertex_output::VertexOutput
+
+@group(2) @binding(0) var prev: texture_2d<f32>;
+@group(2) @binding(1) var prev_sampler: sampler;
+@group(2) @binding(2) var<uniform> params: ShadergonParams;
+
+struct ShadergonParams {
+    time: f32,
+    beats: f32,
+    beat_count: u32,
+    bpm: f32,
+    hexagon: u32,
+    primary: vec4<f32>,
+    secondary: vec4<f32>,
+}
+
+@fragment
+fn fragment(mesh: VertexOutput) -> @location(0) vec4<f32> {
+    let uv = mesh.uv * 2. - 1.;
+    let phase = fract(params.beats);
+    let offset = f32(params.hexagon) * 0.7;
+
+    let v = sin(uv.x * 6. + params.beats * 0.5 + offset)
+        + sin(uv.y * 5. - params.time * 0.3)
+        + sin(length(uv) * 8. - params.beats * 3.14159);
+    let col = mix(params.primary, params.secondary, 0.5 + 0.5 * sin(v * 2.));
+
+    // Previous frame at the same place on the output, for feedback trails
+    let prev_uv = mesh.position.xy / vec2<f32>(textureDimensions(prev));
+    let fb = textureSample(prev, prev_sampler, prev_uv);
+
+    let pulse = 1. - phase * 0.6;
+    return vec4((col.rgb * pulse + fb.rgb * 0.4), 1.);
+}
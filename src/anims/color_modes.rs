@@ -0,0 +1,154 @@
+//! Generative colour modes for `AnimColors`: the primary and secondary colour are derived from a
+//! base hue with a colour harmony, which drifts, jumps and pulses in musical time.
+
+use std::f32::consts::TAU;
+use bevy::prelude::{Color, Res, ResMut, Resource};
+use strum_macros::EnumIter;
+use crate::anims::AnimColors;
+use crate::beat::beat_clock::BeatClock;
+
+/// Value of `AnimColors::anim` that runs the colour modes
+pub const ANIM_HARMONY: usize = 2;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, EnumIter)]
+pub enum Harmony {
+    #[default]
+    Complementary,
+    Triadic,
+    Analogous,
+    SplitComplementary,
+}
+
+impl Harmony {
+    /// Hue offsets in degrees of the harmony colours
+    pub fn offsets(&self) -> &'static [f32] {
+        match self {
+            Harmony::Complementary => { &[0., 180.] }
+            Harmony::Triadic => { &[0., 120., 240.] }
+            Harmony::Analogous => { &[0., 30., -30.] }
+            Harmony::SplitComplementary => { &[0., 150., 210.] }
+        }
+    }
+}
+
+#[derive(Resource, Copy, Clone, PartialEq, Debug)]
+pub struct ColorModes {
+    pub harmony: Harmony,
+    /// Degrees
+    pub base_hue: f32,
+    /// Harmony colour used as secondary, limited to the colours of the harmony
+    pub secondary_index: usize,
+    /// Degrees per beat, 0 holds the hue
+    pub drift: f32,
+    /// Degrees the hue jumps every `jump_beats`, 0 disables the jumps
+    pub jump: f32,
+    pub jump_beats: f32,
+    pub saturation: f32,
+    pub lightness: f32,
+    /// How far saturation and lightness dip between pulses, 0 to 1
+    pub saturation_pulse: f32,
+    pub brightness_pulse: f32,
+    /// Beats per pulse, the pulse peaks on the beat
+    pub pulse_beats: f32,
+}
+
+impl Default for ColorModes {
+    fn default() -> Self {
+        Self {
+            harmony: Harmony::Complementary,
+            base_hue: 0.,
+            secondary_index: 1,
+            drift: 0.,
+            jump: 0.,
+            jump_beats: 4.,
+            saturation: 1.,
+            lightness: 0.5,
+            saturation_pulse: 0.,
+            brightness_pulse: 0.,
+            pulse_beats: 1.,
+        }
+    }
+}
+
+impl ColorModes {
+    /// Base hue at a position in beats
+    pub fn hue(&self, beats: f64) -> f32 {
+        let drift = (self.drift as f64 * beats) as f32;
+        let jumps = if self.jump != 0. && self.jump_beats > 0. {
+            (beats / self.jump_beats as f64).floor() as f32 * self.jump
+        } else {
+            0.
+        };
+        (self.base_hue + drift + jumps).rem_euclid(360.)
+    }
+
+    /// 1 on the pulse, falling towards 0 in between
+    fn pulse(&self, beats: f64) -> f32 {
+        if self.pulse_beats <= 0. { return 1.; }
+        let phase = (beats / self.pulse_beats as f64).fract() as f32;
+        0.5 + 0.5 * (phase * TAU).cos()
+    }
+
+    /// Primary and secondary colour at a position in beats
+    pub fn colors(&self, beats: f64) -> (Color, Color) {
+        let hue = self.hue(beats);
+        let pulse = self.pulse(beats);
+        let saturation = self.saturation * (1. - self.saturation_pulse * (1. - pulse));
+        let lightness = self.lightness * (1. - self.brightness_pulse * (1. - pulse));
+        let offsets = self.harmony.offsets();
+        let secondary = offsets[self.secondary_index.clamp(1, offsets.len() - 1)];
+        (
+            Color::hsl(hue, saturation, lightness).as_rgba(),
+            Color::hsl((hue + secondary).rem_euclid(360.), saturation, lightness).as_rgba(),
+        )
+    }
+}
+
+pub fn anim_color_modes(
+    mut colors: ResMut<AnimColors>,
+    modes: Res<ColorModes>,
+    clock: Res<BeatClock>,
+) {
+    if colors.anim != ANIM_HARMONY { return; }
+    let (primary, secondary) = modes.colors(clock.position);
+    colors.primary = primary;
+    colors.secondary = secondary;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hue_distance(a: Color, b: Color) -> f32 {
+        let d = (a.h() - b.h()).rem_euclid(360.);
+        d.min(360. - d)
+    }
+
+    #[test]
+    fn secondary_follows_the_harmony() {
+        let modes = ColorModes { harmony: Harmony::Triadic, secondary_index: 2, base_hue: 30., ..Default::default() };
+        let (primary, secondary) = modes.colors(0.);
+        assert!((primary.h() - 30.).abs() < 0.5);
+        assert!((hue_distance(primary, secondary) - 120.).abs() < 0.5);
+    }
+
+    #[test]
+    fn secondary_index_beyond_harmony_is_clamped() {
+        // Third colour of triadic, then switched to complementary
+        let modes = ColorModes { harmony: Harmony::Complementary, secondary_index: 2, ..Default::default() };
+        let (primary, secondary) = modes.colors(0.);
+        assert!((hue_distance(primary, secondary) - 180.).abs() < 0.5);
+
+        let modes = ColorModes { secondary_index: 0, ..modes };
+        let (primary, secondary) = modes.colors(0.);
+        assert!((hue_distance(primary, secondary) - 180.).abs() < 0.5);
+    }
+
+    #[test]
+    fn hue_drifts_and_jumps_in_beats() {
+        let modes = ColorModes { base_hue: 350., drift: 5., jump: 90., jump_beats: 4., ..Default::default() };
+        assert!((modes.hue(0.) - 350.).abs() < 1e-3);
+        assert!((modes.hue(2.) - 0.).abs() < 1e-3);
+        assert!((modes.hue(4.) - 100.).abs() < 1e-3);
+    }
+}
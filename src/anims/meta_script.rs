@@ -0,0 +1,420 @@
+//! Meta animations written in Rhai, loaded from `assets/scripts/*.rhai` and reloaded when a file
+//! changes. Enabled scripts get `on_beat(count)`, `on_bar(bar)` and `on_frame(dt)` called and can
+//! trigger tunnelgon oneshots, physics pushes, tube punches and change the animation colours.
+//! `this` is a map that keeps its values between calls.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use bevy::prelude::*;
+use bevy_defer::reactors::Reactors;
+use rhai::{Array, CallFnOptions, Dynamic, Engine, EvalAltResult, Map, Scope, AST};
+use crate::anims::AnimColors;
+use crate::anims::tubes::{CancelPunch, spawn_punch, tubes};
+use crate::beat::beat_clock::BeatClock;
+use crate::beat::BeatEvent;
+use crate::elements2d::tunnelgon_layers::AnimLayer;
+use crate::elements2d::tunnelgon::{LaserAnimationEvent, RingAnimationEvent, RingBasePosAnim, RingBaseValAnim, TunnelgonBaseAnim, TUNNELGON};
+use crate::elements2d::element::SetHexagonElementEvent;
+use crate::hexagon::HexagonDefinition;
+use crate::beat::BEATS_PER_BAR;
+use crate::physics_hexagon::effectors::center_push::CenterPushEvent;
+use crate::physics_hexagon::effectors::dir_push::DirPushEvent;
+use crate::physics_hexagon::lights::led_layers::LedLayer;
+use crate::physics_hexagon::lights::led_tube::LedTube;
+
+pub const SCRIPT_DIR: &str = "assets/scripts";
+pub const SCRIPT_EXTENSION: &str = "rhai";
+/// Seconds between checks for changed script files
+const RELOAD_INTERVAL: f32 = 0.5;
+/// Keeps a runaway loop in a script from freezing the frame
+const MAX_OPERATIONS: u64 = 200_000;
+
+pub const HOOKS: [&str; 3] = ["on_beat", "on_bar", "on_frame"];
+pub const SCRIPT_FUNCTIONS: [&str; 15] = [
+    "layer(name)",
+    "laser(hexagons, indices)",
+    "laser(hexagons, indices, values)",
+    "ring(hexagons, indices, from, to)",
+    "center_push(hexagons)",
+    "dir_push(dir)",
+    "set_tunnelgon(hexagons)",
+    "set_element(name, hexagons)",
+    "punch(tubes)",
+    "punch(tubes, velocity)",
+    "primary()",
+    "secondary()",
+    "set_primary(r, g, b)",
+    "set_secondary(r, g, b)",
+    "phase()",
+];
+
+enum ScriptCommand {
+    Laser(LaserAnimationEvent),
+    Ring(RingAnimationEvent),
+    CenterPush(CenterPushEvent),
+    DirPush(DirPushEvent),
+    SetElement(SetHexagonElementEvent),
+    Punch { tubes: Vec<u16>, velocity: f32 },
+}
+
+/// State shared between the system and the functions registered in the engine
+#[derive(Default)]
+struct ScriptContext {
+    commands: Vec<ScriptCommand>,
+    primary: [f32; 3],
+    secondary: [f32; 3],
+    colors_changed: bool,
+    phase: f32,
+    /// Layer of the laser and ring oneshots, reset for every script
+    layer: AnimLayer,
+}
+
+type SharedContext = Arc<Mutex<ScriptContext>>;
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+fn hexagon_by_name(name: &str) -> Option<HexagonDefinition> {
+    match name {
+        "Main" => { Some(HexagonDefinition::Main) }
+        "A1" => { Some(HexagonDefinition::A1) }
+        "A2" => { Some(HexagonDefinition::A2) }
+        "A3" => { Some(HexagonDefinition::A3) }
+        "B1" => { Some(HexagonDefinition::B1) }
+        "B2" => { Some(HexagonDefinition::B2) }
+        "B3" => { Some(HexagonDefinition::B3) }
+        _ => { None }
+    }
+}
+
+/// A hexagon name, "all" or an array of names
+fn hexagons(value: Dynamic) -> ScriptResult<Vec<HexagonDefinition>> {
+    let names = if value.is_array() {
+        value.into_array()?.into_iter().map(|name| name.into_string()).collect::<Result<Vec<_>, _>>()?
+    } else {
+        vec![value.into_string()?]
+    };
+    let mut hexagons = vec![];
+    for name in names {
+        if name == "all" {
+            hexagons.extend([HexagonDefinition::A1, HexagonDefinition::A2, HexagonDefinition::A3,
+                HexagonDefinition::B1, HexagonDefinition::B2, HexagonDefinition::B3, HexagonDefinition::Main]);
+        } else {
+            hexagons.push(hexagon_by_name(&name).ok_or_else(|| format!("Unknown hexagon {}", name))?);
+        }
+    }
+    Ok(hexagons)
+}
+
+fn float(value: &Dynamic) -> ScriptResult<f32> {
+    value.as_float().map(|v| v as f32)
+        .or_else(|_| value.as_int().map(|v| v as f32))
+        .map_err(|_| format!("Expected a number, got {}", value.type_name()).into())
+}
+
+fn floats(values: Array) -> ScriptResult<Vec<f32>> {
+    values.iter().map(float).collect()
+}
+
+fn indices(values: Array) -> ScriptResult<Vec<usize>> {
+    values.iter()
+        .map(|value| value.as_int().map(|v| v.max(0) as usize).map_err(|_| "Expected an integer index".into()))
+        .collect()
+}
+
+fn color_array(color: [f32; 3]) -> Array {
+    color.iter().map(|c| Dynamic::from_float(*c as f64)).collect()
+}
+
+fn build_engine(context: &SharedContext) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.on_print(|text| info!("script: {}", text));
+
+    let ctx = context.clone();
+    engine.register_fn("layer", move |name: &str| -> ScriptResult<()> {
+        ctx.lock().unwrap().layer = AnimLayer::parse(name).ok_or_else(|| format!("Unknown layer {}", name))?;
+        Ok(())
+    });
+    let ctx = context.clone();
+    engine.register_fn("laser", move |hex: Dynamic, idx: Array| -> ScriptResult<()> {
+        let indices = indices(idx)?;
+        let mut ctx = ctx.lock().unwrap();
+        let layer = ctx.layer;
+        ctx.commands.push(ScriptCommand::Laser(LaserAnimationEvent {
+            affected_hexagons: hexagons(hex)?,
+            layer,
+            base_anim: TunnelgonBaseAnim::Pulse,
+            values: vec![1.; indices.len()],
+            indices,
+        }));
+        Ok(())
+    });
+    let ctx = context.clone();
+    engine.register_fn("laser", move |hex: Dynamic, idx: Array, values: Array| -> ScriptResult<()> {
+        let mut ctx = ctx.lock().unwrap();
+        let layer = ctx.layer;
+        ctx.commands.push(ScriptCommand::Laser(LaserAnimationEvent {
+            affected_hexagons: hexagons(hex)?,
+            layer,
+            base_anim: TunnelgonBaseAnim::Pulse,
+            indices: indices(idx)?,
+            values: floats(values)?,
+        }));
+        Ok(())
+    });
+    let ctx = context.clone();
+    engine.register_fn("ring", move |hex: Dynamic, idx: Array, from: Array, to: Array| -> ScriptResult<()> {
+        let indices = indices(idx)?;
+        let mut ctx = ctx.lock().unwrap();
+        let layer = ctx.layer;
+        ctx.commands.push(ScriptCommand::Ring(RingAnimationEvent {
+            affected_hexagons: hexagons(hex)?,
+            layer,
+            base_pos_anim: RingBasePosAnim::SlideLinear,
+            base_val_anim: RingBaseValAnim::Pulse,
+            values: vec![1.; indices.len()],
+            indices,
+            positions_from: floats(from)?,
+            positions_to: floats(to)?,
+        }));
+        Ok(())
+    });
+    let ctx = context.clone();
+    engine.register_fn("center_push", move |hex: Dynamic| -> ScriptResult<()> {
+        ctx.lock().unwrap().commands.push(ScriptCommand::CenterPush(CenterPushEvent { affected_hexagons: hexagons(hex)? }));
+        Ok(())
+    });
+    let ctx = context.clone();
+    engine.register_fn("dir_push", move |dir: Dynamic| -> ScriptResult<()> {
+        ctx.lock().unwrap().commands.push(ScriptCommand::DirPush(DirPushEvent { dir: float(&dir)? }));
+        Ok(())
+    });
+    let ctx = context.clone();
+    engine.register_fn("set_tunnelgon", move |hex: Dynamic| -> ScriptResult<()> {
+        ctx.lock().unwrap().commands.push(ScriptCommand::SetElement(SetHexagonElementEvent::new(TUNNELGON, hexagons(hex)?)));
+        Ok(())
+    });
+    let ctx = context.clone();
+    engine.register_fn("set_element", move |name: &str, hex: Dynamic| -> ScriptResult<()> {
+        ctx.lock().unwrap().commands.push(ScriptCommand::SetElement(SetHexagonElementEvent::new(name, hexagons(hex)?)));
+        Ok(())
+    });
+    let ctx = context.clone();
+    engine.register_fn("punch", move |ids: Array, velocity: Dynamic| -> ScriptResult<()> {
+        let tubes = indices(ids)?.into_iter().map(|id| id as u16).collect();
+        ctx.lock().unwrap().commands.push(ScriptCommand::Punch { tubes, velocity: float(&velocity)? });
+        Ok(())
+    });
+    let ctx = context.clone();
+    engine.register_fn("punch", move |ids: Array| -> ScriptResult<()> {
+        let tubes = indices(ids)?.into_iter().map(|id| id as u16).collect();
+        ctx.lock().unwrap().commands.push(ScriptCommand::Punch { tubes, velocity: 1. });
+        Ok(())
+    });
+    let ctx = context.clone();
+    engine.register_fn("primary", move || color_array(ctx.lock().unwrap().primary));
+    let ctx = context.clone();
+    engine.register_fn("secondary", move || color_array(ctx.lock().unwrap().secondary));
+    let ctx = context.clone();
+    engine.register_fn("set_primary", move |r: Dynamic, g: Dynamic, b: Dynamic| -> ScriptResult<()> {
+        let mut ctx = ctx.lock().unwrap();
+        ctx.primary = [float(&r)?, float(&g)?, float(&b)?];
+        ctx.colors_changed = true;
+        Ok(())
+    });
+    let ctx = context.clone();
+    engine.register_fn("set_secondary", move |r: Dynamic, g: Dynamic, b: Dynamic| -> ScriptResult<()> {
+        let mut ctx = ctx.lock().unwrap();
+        ctx.secondary = [float(&r)?, float(&g)?, float(&b)?];
+        ctx.colors_changed = true;
+        Ok(())
+    });
+    let ctx = context.clone();
+    engine.register_fn("phase", move || ctx.lock().unwrap().phase as f64);
+    engine
+}
+
+pub struct MetaScript {
+    /// File name without extension
+    pub name: String,
+    pub enabled: bool,
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    ast: Option<AST>,
+    /// Bound to `this` in the hooks
+    state: Dynamic,
+    /// Compile error of the current file content or the last runtime error
+    pub error: Option<String>,
+}
+
+impl MetaScript {
+    fn load(&mut self, engine: &Engine) {
+        let result = std::fs::read_to_string(&self.path)
+            .map_err(|e| e.to_string())
+            .and_then(|source| engine.compile(source).map_err(|e| e.to_string()));
+        match result {
+            Ok(ast) => {
+                info!("Loaded script {}", self.name);
+                self.ast = Some(ast);
+                self.state = Dynamic::from_map(Map::new());
+                self.error = None;
+            }
+            Err(e) => {
+                error!("Couldn't compile script {}: {}", self.name, e);
+                self.error = Some(e);
+            }
+        }
+    }
+
+    fn call(&mut self, engine: &Engine, hook: &str, arg: Dynamic) {
+        let Some(ast) = self.ast.as_ref() else { return; };
+        if !ast.iter_functions().any(|f| f.name == hook) { return; }
+        let options = CallFnOptions::new().eval_ast(false).bind_this_ptr(&mut self.state);
+        if let Err(e) = engine.call_fn_with_options::<Dynamic>(options, &mut Scope::new(), ast, hook, (arg,)) {
+            let e = format!("{}: {}", hook, e);
+            if self.error.as_ref() != Some(&e) {
+                error!("Script {} failed in {}", self.name, e);
+                self.error = Some(e);
+            }
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct MetaScripts {
+    pub scripts: Vec<MetaScript>,
+    engine: Engine,
+    context: SharedContext,
+    reload_timer: f32,
+}
+
+impl Default for MetaScripts {
+    fn default() -> Self {
+        let context = SharedContext::default();
+        Self {
+            scripts: vec![],
+            engine: build_engine(&context),
+            context,
+            reload_timer: 0.,
+        }
+    }
+}
+
+fn script_files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else { return vec![]; };
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().map(|ext| ext == SCRIPT_EXTENSION).unwrap_or(false))
+        .collect();
+    files.sort();
+    files
+}
+
+/// Pick up new, changed and removed script files
+pub fn reload_meta_scripts(
+    mut scripts: ResMut<MetaScripts>,
+    time: Res<Time<Real>>,
+) {
+    scripts.reload_timer -= time.delta_seconds();
+    if scripts.reload_timer > 0. { return; }
+    scripts.reload_timer = RELOAD_INTERVAL;
+
+    let scripts = &mut *scripts;
+    let files = script_files(Path::new(SCRIPT_DIR));
+    scripts.scripts.retain(|script| files.contains(&script.path));
+    for path in files {
+        let modified = std::fs::metadata(&path).and_then(|metadata| metadata.modified()).ok();
+        let script = match scripts.scripts.iter().position(|script| script.path == path) {
+            Some(i) => { &mut scripts.scripts[i] }
+            None => {
+                let name = path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+                scripts.scripts.push(MetaScript {
+                    name,
+                    enabled: false,
+                    path,
+                    modified: None,
+                    ast: None,
+                    state: Dynamic::from_map(Map::new()),
+                    error: None,
+                });
+                scripts.scripts.last_mut().unwrap()
+            }
+        };
+        if script.modified != modified || script.modified.is_none() {
+            script.modified = modified;
+            script.load(&scripts.engine);
+        }
+    }
+}
+
+/// Call the hooks of the enabled scripts and apply what they requested
+pub fn run_meta_scripts(
+    mut scripts: ResMut<MetaScripts>,
+    mut colors: ResMut<AnimColors>,
+    clock: Res<BeatClock>,
+    time: Res<Time>,
+    mut beat_reader: EventReader<BeatEvent>,
+    mut laser_writer: EventWriter<LaserAnimationEvent>,
+    mut ring_writer: EventWriter<RingAnimationEvent>,
+    mut center_push_writer: EventWriter<CenterPushEvent>,
+    mut dir_push_writer: EventWriter<DirPushEvent>,
+    mut element_writer: EventWriter<SetHexagonElementEvent>,
+    mut commands: Commands,
+    mut reactors: ResMut<Reactors>,
+    tube_query: Query<(&LedTube, &Children)>,
+) {
+    let beats: Vec<u64> = beat_reader.read().map(|beat| beat.count).collect();
+    if !scripts.scripts.iter().any(|script| script.enabled) { return; }
+
+    let scripts = &mut *scripts;
+    {
+        let [pr, pg, pb, _] = colors.primary.as_rgba_f32();
+        let [sr, sg, sb, _] = colors.secondary.as_rgba_f32();
+        let mut context = scripts.context.lock().unwrap();
+        context.primary = [pr, pg, pb];
+        context.secondary = [sr, sg, sb];
+        context.colors_changed = false;
+        context.phase = clock.phase();
+    }
+
+    for script in scripts.scripts.iter_mut().filter(|script| script.enabled) {
+        scripts.context.lock().unwrap().layer = AnimLayer::default();
+        for count in beats.iter() {
+            script.call(&scripts.engine, "on_beat", Dynamic::from_int(*count as i64));
+            if count % BEATS_PER_BAR == 0 {
+                script.call(&scripts.engine, "on_bar", Dynamic::from_int((count / BEATS_PER_BAR) as i64));
+            }
+        }
+        script.call(&scripts.engine, "on_frame", Dynamic::from_float(time.delta_seconds() as f64));
+    }
+
+    let mut context = scripts.context.lock().unwrap();
+    if context.colors_changed {
+        let [pr, pg, pb] = context.primary;
+        let [sr, sg, sb] = context.secondary;
+        colors.primary = Color::rgb(pr, pg, pb);
+        colors.secondary = Color::rgb(sr, sg, sb);
+    }
+
+    let mut punched = false;
+    for command in context.commands.drain(..) {
+        match command {
+            ScriptCommand::Laser(event) => { laser_writer.send(event); }
+            ScriptCommand::Ring(event) => { ring_writer.send(event); }
+            ScriptCommand::CenterPush(event) => { center_push_writer.send(event); }
+            ScriptCommand::DirPush(event) => { dir_push_writer.send(event); }
+            ScriptCommand::SetElement(event) => { element_writer.send(event); }
+            ScriptCommand::Punch { tubes: ids, velocity } => {
+                if !punched {
+                    reactors.get_named::<CancelPunch>("cancel_punch").send(true);
+                    punched = true;
+                }
+                let indices = tubes(&ids);
+                for (led_tube, children) in tube_query.iter() {
+                    if !indices.contains(&led_tube.get_tube_index()) { continue; }
+                    spawn_punch(&mut commands, children.iter().cloned().collect(), LedLayer::Punch, 0.3, colors.primary, colors.secondary, velocity);
+                }
+            }
+        }
+    }
+}
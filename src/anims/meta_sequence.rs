@@ -0,0 +1,417 @@
+//! Meta animations that trigger tunnelgon laser and ring oneshots, described as sequences in
+//! `assets/meta_sequences/*.ron`. An enabled sequence starts on a beat, plays its steps and waits
+//! for the next beat at its end before it starts again.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use bevy::prelude::{error, EventReader, EventWriter, info, Real, Res, ResMut, Resource, Time};
+use serde::{Deserialize, Serialize};
+use crate::beat::beat_clock::BeatClock;
+use crate::beat::BeatEvent;
+use crate::elements2d::tunnelgon_layers::AnimLayer;
+use crate::elements2d::tunnelgon::{LaserAnimationEvent, RingAnimationEvent, RingBasePosAnim, RingBaseValAnim, TunnelgonBaseAnim};
+use crate::hexagon::HexagonDefinition;
+
+pub const SEQUENCE_DIR: &str = "assets/meta_sequences";
+pub const SEQUENCE_EXTENSION: &str = "ron";
+/// Upper bound of steps after expanding loops
+const MAX_INSTRUCTIONS: usize = 10000;
+
+pub const ALL_HEXAGONS: [HexagonDefinition; 7] = [
+    HexagonDefinition::A1,
+    HexagonDefinition::A2,
+    HexagonDefinition::A3,
+    HexagonDefinition::B1,
+    HexagonDefinition::B2,
+    HexagonDefinition::B3,
+    HexagonDefinition::Main,
+];
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum Delay {
+    Frames(u32),
+    Seconds(f32),
+    /// Counted from the last beat, so sequences stay on the beat grid
+    Beats(f32),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum HexagonSet {
+    All,
+    /// Named set of the sequence file
+    Set(String),
+    List(Vec<HexagonDefinition>),
+}
+
+fn pulse() -> TunnelgonBaseAnim { TunnelgonBaseAnim::Pulse }
+fn slide_linear() -> RingBasePosAnim { RingBasePosAnim::SlideLinear }
+fn ring_pulse() -> RingBaseValAnim { RingBaseValAnim::Pulse }
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum SequenceStep {
+    Laser {
+        hexagons: HexagonSet,
+        #[serde(default)]
+        layer: AnimLayer,
+        #[serde(default = "pulse")]
+        anim: TunnelgonBaseAnim,
+        indices: Vec<usize>,
+        /// 1 for every index when empty
+        #[serde(default)]
+        values: Vec<f32>,
+    },
+    Ring {
+        hexagons: HexagonSet,
+        #[serde(default)]
+        layer: AnimLayer,
+        #[serde(default = "slide_linear")]
+        pos_anim: RingBasePosAnim,
+        #[serde(default = "ring_pulse")]
+        val_anim: RingBaseValAnim,
+        indices: Vec<usize>,
+        /// 1 for every index when empty
+        #[serde(default)]
+        values: Vec<f32>,
+        from: Vec<f32>,
+        to: Vec<f32>,
+    },
+    Wait(Delay),
+    Loop {
+        count: usize,
+        steps: Vec<SequenceStep>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SequenceFile {
+    /// Button text, the file name is used when empty
+    #[serde(default)]
+    pub label: String,
+    #[serde(default)]
+    pub sets: HashMap<String, Vec<HexagonDefinition>>,
+    pub steps: Vec<SequenceStep>,
+}
+
+#[derive(Clone)]
+enum Instruction {
+    Laser(LaserAnimationEvent),
+    Ring(RingAnimationEvent),
+    Wait(Delay),
+}
+
+fn values_or_ones(values: &[f32], count: usize) -> Vec<f32> {
+    if values.is_empty() { vec![1.; count] } else { values.to_vec() }
+}
+
+impl SequenceFile {
+    fn hexagons(&self, set: &HexagonSet) -> Result<Vec<HexagonDefinition>, String> {
+        match set {
+            HexagonSet::All => { Ok(ALL_HEXAGONS.to_vec()) }
+            HexagonSet::Set(name) => { self.sets.get(name).cloned().ok_or_else(|| format!("unknown hexagon set {}", name)) }
+            HexagonSet::List(hexagons) => { Ok(hexagons.clone()) }
+        }
+    }
+
+    /// Resolve hexagon sets and expand loops
+    fn compile(&self, steps: &[SequenceStep], out: &mut Vec<Instruction>) -> Result<(), String> {
+        for step in steps {
+            match step {
+                SequenceStep::Laser { hexagons, layer, anim, indices, values } => {
+                    out.push(Instruction::Laser(LaserAnimationEvent {
+                        affected_hexagons: self.hexagons(hexagons)?,
+                        layer: *layer,
+                        base_anim: *anim,
+                        indices: indices.clone(),
+                        values: values_or_ones(values, indices.len()),
+                    }));
+                }
+                SequenceStep::Ring { hexagons, layer, pos_anim, val_anim, indices, values, from, to } => {
+                    out.push(Instruction::Ring(RingAnimationEvent {
+                        affected_hexagons: self.hexagons(hexagons)?,
+                        layer: *layer,
+                        base_pos_anim: *pos_anim,
+                        base_val_anim: *val_anim,
+                        indices: indices.clone(),
+                        values: values_or_ones(values, indices.len()),
+                        positions_from: from.clone(),
+                        positions_to: to.clone(),
+                    }));
+                }
+                SequenceStep::Wait(delay) => { out.push(Instruction::Wait(delay.clone())); }
+                SequenceStep::Loop { count, steps } => {
+                    // Expand the body once, so empty bodies and huge counts are caught before repeating it
+                    let mut body = vec![];
+                    self.compile(steps, &mut body)?;
+                    if *count == 0 || body.is_empty() { continue; }
+                    let total = count.checked_mul(body.len()).and_then(|len| len.checked_add(out.len()));
+                    if total.filter(|total| *total <= MAX_INSTRUCTIONS).is_none() {
+                        return Err(format!("more than {} steps after expanding loops", MAX_INSTRUCTIONS));
+                    }
+                    for _ in 0..*count {
+                        out.extend(body.iter().cloned());
+                    }
+                }
+            }
+            if out.len() > MAX_INSTRUCTIONS {
+                return Err(format!("more than {} steps after expanding loops", MAX_INSTRUCTIONS));
+            }
+        }
+        Ok(())
+    }
+}
+
+enum Waiting {
+    Frames(u32),
+    Seconds(f32),
+    /// Beat events still to come, then a fraction of a beat on the beat clock
+    Beats(u32, f64),
+    /// Beat clock position
+    Until(f64),
+}
+
+impl Waiting {
+    /// Whole beats are counted on beat events, so muted beats or stopped music hold the sequence.
+    /// Only waits shorter than a beat run on the clock.
+    fn start(delay: &Delay, position: f64) -> Option<Self> {
+        match delay {
+            Delay::Frames(frames) => { if *frames > 0 { Some(Waiting::Frames(*frames)) } else { None } }
+            Delay::Seconds(seconds) => { if *seconds > 0. { Some(Waiting::Seconds(*seconds)) } else { None } }
+            Delay::Beats(beats) => {
+                if *beats <= 0. { return None; }
+                let beats = *beats as f64;
+                if beats >= 1. {
+                    return Some(Waiting::Beats(beats.floor() as u32, beats.fract()));
+                }
+                let mut target = position.floor() + beats;
+                while target <= position {
+                    target += beats;
+                }
+                Some(Waiting::Until(target))
+            }
+        }
+    }
+
+    /// Count down, true once the wait is over
+    fn tick(&mut self, dt: f32, position: f64, beats: u32) -> bool {
+        match self {
+            Waiting::Frames(frames) => {
+                *frames -= 1;
+                *frames == 0
+            }
+            Waiting::Seconds(seconds) => {
+                *seconds -= dt;
+                *seconds <= 0.
+            }
+            Waiting::Beats(remaining, fraction) => {
+                *remaining = remaining.saturating_sub(beats);
+                if *remaining > 0 { return false; }
+                if *fraction <= 0. { return true; }
+                let target = position.floor() + *fraction;
+                *self = Waiting::Until(target);
+                position >= target
+            }
+            Waiting::Until(target) => { position >= *target }
+        }
+    }
+}
+
+struct SequenceRun {
+    step: usize,
+    waiting: Option<Waiting>,
+}
+
+pub struct MetaSequence {
+    /// File name without extension
+    pub name: String,
+    pub label: String,
+    pub enabled: bool,
+    instructions: Vec<Instruction>,
+    run: Option<SequenceRun>,
+}
+
+impl MetaSequence {
+    fn load(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let file = ron::from_str::<SequenceFile>(&content).map_err(|e| e.to_string())?;
+        let mut instructions = vec![];
+        file.compile(&file.steps, &mut instructions)?;
+        let name = path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+        Ok(Self {
+            label: if file.label.is_empty() { name.clone() } else { file.label.clone() },
+            name,
+            enabled: false,
+            instructions,
+            run: None,
+        })
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.run.is_some()
+    }
+}
+
+#[derive(Resource)]
+pub struct MetaSequences {
+    pub sequences: Vec<MetaSequence>,
+    /// Files that couldn't be loaded and why
+    pub errors: Vec<String>,
+}
+
+fn sequence_files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else { return vec![]; };
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().map(|ext| ext == SEQUENCE_EXTENSION).unwrap_or(false))
+        .collect();
+    files.sort();
+    files
+}
+
+impl MetaSequences {
+    pub fn load() -> Self {
+        let mut sequences = vec![];
+        let mut errors = vec![];
+        for path in sequence_files(Path::new(SEQUENCE_DIR)) {
+            match MetaSequence::load(&path) {
+                Ok(sequence) => { sequences.push(sequence); }
+                Err(e) => {
+                    error!("Couldn't load meta sequence {}: {}", path.display(), e);
+                    errors.push(format!("{}: {}", path.display(), e));
+                }
+            }
+        }
+        info!("Loaded {} meta sequences from {}", sequences.len(), SEQUENCE_DIR);
+        Self { sequences, errors }
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut MetaSequence> {
+        self.sequences.iter_mut().find(|sequence| sequence.name == name)
+    }
+}
+
+/// Start enabled sequences on the beat and play their steps until the next wait
+pub fn run_meta_sequences(
+    mut sequences: ResMut<MetaSequences>,
+    clock: Res<BeatClock>,
+    time: Res<Time<Real>>,
+    mut beat_reader: EventReader<BeatEvent>,
+    mut laser_writer: EventWriter<LaserAnimationEvent>,
+    mut ring_writer: EventWriter<RingAnimationEvent>,
+) {
+    let beats = beat_reader.read().count() as u32;
+    let is_beat = beats > 0;
+    let dt = time.delta_seconds();
+
+    for sequence in sequences.sequences.iter_mut() {
+        if !sequence.enabled || sequence.instructions.is_empty() {
+            sequence.run = None;
+            continue;
+        }
+        let run = match sequence.run.as_mut() {
+            Some(run) => { run }
+            None => {
+                if !is_beat { continue; }
+                sequence.run.insert(SequenceRun { step: 0, waiting: None })
+            }
+        };
+        if let Some(waiting) = run.waiting.as_mut() {
+            if !waiting.tick(dt, clock.position, beats) { continue; }
+            run.waiting = None;
+        }
+
+        while run.waiting.is_none() {
+            if run.step >= sequence.instructions.len() {
+                run.step = 0;
+                // Restart on the next beat event
+                run.waiting = Some(Waiting::Beats(1, 0.));
+                break;
+            }
+            match &sequence.instructions[run.step] {
+                Instruction::Laser(event) => { laser_writer.send(event.clone()); }
+                Instruction::Ring(event) => { ring_writer.send(event.clone()); }
+                Instruction::Wait(delay) => { run.waiting = Waiting::start(delay, clock.position); }
+            }
+            run.step += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile(source: &str) -> Result<Vec<Instruction>, String> {
+        let file = ron::from_str::<SequenceFile>(source).map_err(|e| e.to_string())?;
+        let mut instructions = vec![];
+        file.compile(&file.steps, &mut instructions)?;
+        Ok(instructions)
+    }
+
+    #[test]
+    fn loops_are_expanded_and_sets_resolved() {
+        let instructions = compile(r#"(
+            sets: { "left": [A1, A2] },
+            steps: [
+                Loop(count: 3, steps: [
+                    Laser(hexagons: Set("left"), indices: [0, 1]),
+                    Wait(Beats(1.0)),
+                ]),
+            ],
+        )"#).unwrap();
+        assert_eq!(instructions.len(), 6);
+        let Instruction::Laser(event) = &instructions[0] else { panic!("expected a laser") };
+        assert_eq!(event.affected_hexagons, vec![HexagonDefinition::A1, HexagonDefinition::A2]);
+        assert_eq!(event.values, vec![1., 1.]);
+        assert!(matches!(instructions[1], Instruction::Wait(Delay::Beats(_))));
+    }
+
+    #[test]
+    fn unknown_set_is_an_error() {
+        let result = compile(r#"(steps: [Laser(hexagons: Set("nope"), indices: [0])])"#);
+        assert!(result.err().unwrap().contains("nope"));
+    }
+
+    #[test]
+    fn runaway_loops_are_rejected() {
+        let result = compile(r#"(steps: [Loop(count: 100000, steps: [Wait(Frames(1))])])"#);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn empty_loops_expand_to_nothing() {
+        let instructions = compile(r#"(steps: [
+            Loop(count: 18446744073709551615, steps: []),
+            Loop(count: 18446744073709551615, steps: [Loop(count: 18446744073709551615, steps: [])]),
+            Loop(count: 0, steps: [Wait(Frames(1))]),
+            Wait(Frames(1)),
+        ])"#).unwrap();
+        assert_eq!(instructions.len(), 1);
+    }
+
+    #[test]
+    fn huge_loop_counts_are_rejected_before_expanding() {
+        let result = compile(r#"(steps: [Loop(count: 18446744073709551615, steps: [Wait(Frames(1)), Wait(Frames(2))])])"#);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn whole_beats_wait_for_beat_events() {
+        let mut waiting = Waiting::start(&Delay::Beats(2.), 0.5).unwrap();
+        // The free running clock alone doesn't end the wait
+        assert!(!waiting.tick(0.1, 10., 0));
+        assert!(!waiting.tick(0.1, 11., 1));
+        assert!(waiting.tick(0.1, 12., 1));
+    }
+
+    #[test]
+    fn beat_fractions_run_on_the_clock() {
+        let mut waiting = Waiting::start(&Delay::Beats(1.5), 0.).unwrap();
+        assert!(!waiting.tick(0.1, 1., 1));
+        assert!(!waiting.tick(0.1, 1.25, 0));
+        assert!(waiting.tick(0.1, 1.5, 0));
+
+        let mut waiting = Waiting::start(&Delay::Beats(0.25), 2.1).unwrap();
+        assert!(!waiting.tick(0.1, 2.2, 0));
+        assert!(waiting.tick(0.1, 2.25, 0));
+    }
+}
@@ -1,5 +1,10 @@
 pub mod tubes;
-pub mod meta_tunnelgon;
+pub mod color_modes;
+pub mod tube_sequencer;
+pub mod noise_field;
+pub mod wave_transition;
+pub mod meta_sequence;
+pub mod meta_script;
 pub mod meta_phys;
 mod bridge;
 
@@ -12,8 +17,13 @@ use bevy::prelude::{Color, Commands, Event, EventReader, IntoSystemSetConfigs, P
 use bevy::tasks::futures_lite::StreamExt;
 use bevy::time::Real;
 use bevy_defer::{AsyncAccess, AsyncCommandsExtension, AsyncFailure, in_async_context, spawn, world};
-use crate::anims::meta_tunnelgon::{tunnelgon_laser_cycle_meta_anim, tunnelgon_laser_figure_eight_meta_anim, tunnelgon_laser_round_the_clock_meta_anim, tunnelgon_laser_sweep_anim, tunnelgon_ring_train_meta_anim, tunnelgon_rings_btf_meta_anim, tunnelgon_rings_ftb_meta_anim, TunnelgonLaserCycleMetaAnim, TunnelgonLaserFigureEightMetaAnim, TunnelgonLaserRoundTheClockMetaAnim, TunnelgonLaserSweepMetaAnim, TunnelgonRingsBTFMetaAnim, TunnelgonRingsFTBMetaAnim, TunnelgonRingsTrainMetaAnim};
+use crate::anims::meta_sequence::{MetaSequences, run_meta_sequences};
+use crate::anims::meta_script::{MetaScripts, reload_meta_scripts, run_meta_scripts};
 use crate::anims::tubes::{TubesWaveAnims, wave_simple, wave_blocky, tube_punch, clear, sweep, tube_punch_2, tube_punch_3, tube_punch_4, wave_noise1, wave_noise2, strobe1, strobe2};
+use crate::anims::color_modes::{anim_color_modes, ColorModes};
+use crate::anims::noise_field::{NoiseField, wave_noise_field};
+use crate::anims::wave_transition::update_wave_transition;
+use crate::anims::tube_sequencer::{tube_sequencer, TubeSequencer};
 use crate::{Clear, GuiUpdate, MetaAnimUpdate};
 use crate::anims::meta_phys::{PhysMetaAnim, push_or_pull_meta_anim, push_pull_meta_anim, sides_meta_anim, up_down, whirl};
 
@@ -27,24 +37,23 @@ impl Plugin for AnimPlugin {
             secondary: Color::BLUE,
             anim: 0,
         });
-        app.init_resource::<TunnelgonLaserCycleMetaAnim>();
-        app.init_resource::<TunnelgonLaserFigureEightMetaAnim>();
-        app.init_resource::<TunnelgonLaserRoundTheClockMetaAnim>();
-        app.init_resource::<TunnelgonLaserSweepMetaAnim>();
-        app.init_resource::<TunnelgonRingsFTBMetaAnim>();
-        app.init_resource::<TunnelgonRingsBTFMetaAnim>();
-        app.init_resource::<TunnelgonRingsTrainMetaAnim>();
+        app.init_resource::<ColorModes>();
+        app.insert_resource(MetaSequences::load());
         app.add_systems(Clear, clear);
-        app.add_systems(MetaAnimUpdate, (
-            tunnelgon_laser_cycle_meta_anim,
-            tunnelgon_laser_figure_eight_meta_anim,
-            tunnelgon_laser_round_the_clock_meta_anim,
-            tunnelgon_laser_sweep_anim,
-            tunnelgon_rings_ftb_meta_anim,
-            tunnelgon_rings_btf_meta_anim,
-            tunnelgon_ring_train_meta_anim,
-        ));
+        app.add_systems(MetaAnimUpdate, run_meta_sequences);
+        app.init_resource::<MetaScripts>();
+        app.add_systems(Update, reload_meta_scripts);
+        app.add_systems(MetaAnimUpdate, run_meta_scripts);
         app.init_resource::<TubesWaveAnims>();
+        app.init_resource::<TubeSequencer>();
+        app.init_resource::<NoiseField>();
+        app.add_systems(MetaAnimUpdate, update_wave_transition
+            .before(wave_simple)
+            .before(wave_blocky)
+            .before(wave_noise1)
+            .before(wave_noise2)
+            .before(wave_noise_field)
+        );
         app.add_systems(MetaAnimUpdate, (
             wave_simple,
             wave_blocky,
@@ -52,10 +61,13 @@ impl Plugin for AnimPlugin {
             tube_punch_2,
             tube_punch_3,
             tube_punch_4,
+            tube_sequencer,
             wave_noise1,
             wave_noise2,
+            wave_noise_field,
             sweep,
             anim_colors,
+            anim_color_modes,
         ).before(strobe1));
         app.init_resource::<PhysMetaAnim>();
         app.add_systems(MetaAnimUpdate, (
@@ -73,6 +85,7 @@ impl Plugin for AnimPlugin {
 pub struct AnimColors {
     pub(crate) primary: Color,
     pub(crate) secondary: Color,
+    /// 0 static, 1 rainbow, 2 harmony from `ColorModes`
     pub anim: usize,
 }
 
@@ -0,0 +1,118 @@
+//! Noise field tube animation. The field drifts over the tubes, evolves over time and gets a
+//! jolt on every beat.
+
+use bevy::math::Vec2;
+use bevy::prelude::{EventReader, GlobalTransform, Query, Res, ResMut, Resource, Time};
+use noise::{Fbm, MultiFractal, NoiseFn, OpenSimplex, Perlin, RidgedMulti, Worley};
+use strum_macros::EnumIter;
+use crate::anims::AnimColors;
+use crate::anims::tubes::{TubesWaveAnims, write_wave};
+use crate::beat::BeatEvent;
+use crate::palette::ActivePalette;
+use crate::physics_hexagon::lights::led_tube::LedTubeLed;
+
+/// Wave mode of `TubesWaveAnims` that shows the noise field
+pub const NOISE_FIELD_WAVE: usize = 7;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, EnumIter)]
+pub enum NoiseType {
+    #[default]
+    Perlin,
+    OpenSimplex,
+    Worley,
+    Fbm,
+    Ridged,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct NoiseFieldSettings {
+    pub noise_type: NoiseType,
+    pub seed: u32,
+    /// Noise units per pixel
+    pub scale: f32,
+    /// Octaves of fBm and ridged noise
+    pub octaves: usize,
+    /// Drift direction in degrees, counter clockwise from the x axis
+    pub drift_angle: f32,
+    /// Pixels per second
+    pub drift_speed: f32,
+    /// Speed of the field evolving through time, noise units per second
+    pub evolution: f32,
+    /// Evolution speed added on every beat
+    pub kick: f32,
+    /// Time constant of the kick decay in seconds
+    pub kick_decay: f32,
+}
+
+impl Default for NoiseFieldSettings {
+    fn default() -> Self {
+        Self {
+            noise_type: NoiseType::Perlin,
+            seed: 1,
+            scale: 0.01,
+            octaves: 4,
+            drift_angle: 0.,
+            drift_speed: 0.,
+            evolution: 0.3,
+            kick: 3.,
+            kick_decay: 0.15,
+        }
+    }
+}
+
+/// Generators are cheap to build, Worley noise can't be kept in a resource as it isn't `Send`
+fn build_generator(settings: &NoiseFieldSettings) -> Box<dyn NoiseFn<f64, 3>> {
+    let octaves = settings.octaves.clamp(1, Fbm::<Perlin>::MAX_OCTAVES);
+    match settings.noise_type {
+        NoiseType::Perlin => { Box::new(Perlin::new(settings.seed)) }
+        NoiseType::OpenSimplex => { Box::new(OpenSimplex::new(settings.seed)) }
+        NoiseType::Worley => { Box::new(Worley::new(settings.seed)) }
+        NoiseType::Fbm => { Box::new(Fbm::<Perlin>::new(settings.seed).set_octaves(octaves)) }
+        NoiseType::Ridged => { Box::new(RidgedMulti::<Perlin>::new(settings.seed).set_octaves(octaves)) }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct NoiseField {
+    pub settings: NoiseFieldSettings,
+    /// Drift offset in pixels
+    offset: Vec2,
+    /// Position in time of the field
+    z: f64,
+    kick: f32,
+}
+
+pub fn wave_noise_field(
+    mut query: Query<(&mut LedTubeLed, &GlobalTransform)>,
+    time: Res<Time>,
+    params: Res<TubesWaveAnims>,
+    mut field: ResMut<NoiseField>,
+    colors: Res<AnimColors>,
+    palette: Res<ActivePalette>,
+    mut beat_reader: EventReader<BeatEvent>,
+) {
+    if !params.transition.is_visible(NOISE_FIELD_WAVE) { return; }
+
+    let dt = time.delta_seconds();
+    for _ in beat_reader.read() {
+        field.kick += field.settings.kick;
+    }
+    field.kick *= (-dt / field.settings.kick_decay.max(0.001)).exp();
+
+    let settings = field.settings;
+    field.offset += Vec2::from_angle(settings.drift_angle.to_radians()) * settings.drift_speed * dt;
+    field.z += ((settings.evolution + field.kick) * dt) as f64;
+
+    let generator = build_generator(&settings);
+    for (mut ltl, gt) in query.iter_mut() {
+        let position = (gt.translation().truncate() - field.offset) * settings.scale;
+        let n = generator.get([position.x as f64, position.y as f64, field.z]) as f32;
+        let val = (0.5 + 0.5 * n).clamp(0., 1.);
+        let color = if palette.enabled {
+            palette.sample(0, val) * (0.2 + val * 1.8)
+        } else {
+            colors.primary * val * 2. + colors.secondary * (1. - val) * 0.2
+        };
+        write_wave(&mut ltl, &params, NOISE_FIELD_WAVE, gt.translation().x, color);
+    }
+}
@@ -0,0 +1,165 @@
+//! Step sequencer for the tubes. Rows are tube groups, columns are subdivisions of the beat.
+//! Every set cell fires the punch envelope on the tubes of its row when the step is reached.
+
+use bevy::hierarchy::Children;
+use bevy::prelude::{Color, Commands, Entity, Query, Res, ResMut, Resource};
+use bevy_defer::reactors::Reactors;
+use strum_macros::EnumIter;
+use crate::anims::AnimColors;
+use crate::anims::tubes::{CancelPunch, spawn_punch, tubes};
+use crate::beat::beat_clock::BeatClock;
+use crate::physics_hexagon::lights::led_layers::LedLayer;
+use crate::physics_hexagon::lights::led_tube::{LedTube, TubeIndex};
+
+/// Step counts the sequencer can be set to
+pub const STEP_COUNTS: [usize; 2] = [16, 32];
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, EnumIter)]
+pub enum ColorSlot {
+    #[default]
+    Primary,
+    Secondary,
+    White,
+}
+
+impl ColorSlot {
+    pub fn color(&self, colors: &AnimColors) -> Color {
+        match self {
+            ColorSlot::Primary => { colors.primary }
+            ColorSlot::Secondary => { colors.secondary }
+            ColorSlot::White => { Color::WHITE }
+        }
+    }
+}
+
+#[derive(Copy, Clone, Default, PartialEq, Debug)]
+pub struct SequencerCell {
+    /// 0 is off
+    pub velocity: f32,
+    pub color: ColorSlot,
+}
+
+impl SequencerCell {
+    pub fn is_set(&self) -> bool {
+        self.velocity > 0.
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct SequencerRow {
+    pub name: String,
+    pub tubes: Vec<TubeIndex>,
+    pub cells: Vec<SequencerCell>,
+}
+
+impl SequencerRow {
+    fn new(name: &str, tubes: Vec<TubeIndex>, steps: usize) -> Self {
+        Self {
+            name: name.to_owned(),
+            tubes,
+            cells: vec![SequencerCell::default(); steps],
+        }
+    }
+
+    fn set(&mut self, steps: &[usize], velocity: f32, color: ColorSlot) {
+        for step in steps {
+            self.cells[*step] = SequencerCell { velocity, color };
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct SequencerPattern {
+    pub steps: usize,
+    /// Beat subdivision of one step, 4 makes the steps 16th notes
+    pub steps_per_beat: usize,
+    pub rows: Vec<SequencerRow>,
+}
+
+impl Default for SequencerPattern {
+    /// Rows follow the punch groups, with a four on the floor on the centre tubes
+    fn default() -> Self {
+        let steps = 16;
+        let mut centre = SequencerRow::new("Centre", tubes(&[5, 6, 7, 16, 17, 18]), steps);
+        centre.set(&[0, 4, 8, 12], 1., ColorSlot::Primary);
+        let mut middle = SequencerRow::new("Middle", tubes(&[3, 4, 8, 9, 14, 15, 19, 20]), steps);
+        middle.set(&[2, 6, 10, 14], 0.6, ColorSlot::Secondary);
+        let mut outer = SequencerRow::new("Outer", tubes(&[1, 2, 10, 11, 12, 13, 21, 22]), steps);
+        outer.set(&[15], 0.8, ColorSlot::White);
+        Self {
+            steps,
+            steps_per_beat: 4,
+            rows: vec![
+                centre,
+                middle,
+                outer,
+                SequencerRow::new("Cross 1", tubes(&[6, 14, 13]), steps),
+                SequencerRow::new("Cross 2", tubes(&[8, 16, 11]), steps),
+                SequencerRow::new("Cross 3", tubes(&[10, 17, 9]), steps),
+                SequencerRow::new("Cross 4", tubes(&[12, 15, 7]), steps),
+            ],
+        }
+    }
+}
+
+impl SequencerPattern {
+    /// Change the step count, new steps repeat the existing pattern
+    pub fn set_steps(&mut self, steps: usize) {
+        let old_steps = self.steps;
+        for row in self.rows.iter_mut() {
+            row.cells = (0..steps).map(|i| row.cells[i % old_steps]).collect();
+        }
+        self.steps = steps;
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct TubeSequencer {
+    pub enabled: bool,
+    pub pattern: SequencerPattern,
+    /// Step that was fired last, for the GUI
+    pub current_step: Option<usize>,
+    /// Steps since start of the last fired step
+    last_step: Option<u64>,
+}
+
+pub fn tube_sequencer(
+    mut sequencer: ResMut<TubeSequencer>,
+    clock: Res<BeatClock>,
+    query: Query<(&LedTube, &Children)>,
+    mut commands: Commands,
+    colors: Res<AnimColors>,
+    mut reactors: ResMut<Reactors>,
+) {
+    if !sequencer.enabled {
+        sequencer.current_step = None;
+        sequencer.last_step = None;
+        return;
+    }
+
+    let absolute_step = (clock.position * sequencer.pattern.steps_per_beat as f64).floor() as u64;
+    if sequencer.last_step == Some(absolute_step) { return; }
+    sequencer.last_step = Some(absolute_step);
+    let step = (absolute_step % sequencer.pattern.steps as u64) as usize;
+    sequencer.current_step = Some(step);
+
+    let hits: Vec<(&SequencerRow, SequencerCell)> = sequencer.pattern.rows.iter()
+        .filter_map(|row| Some((row, *row.cells.get(step)?)))
+        .filter(|(_, cell)| cell.is_set())
+        .collect();
+    if hits.is_empty() { return; }
+
+    let signal = reactors.get_named::<CancelPunch>("cancel_punch");
+    signal.send(true);
+
+    for (row, cell) in hits {
+        let tube_entities: Vec<Vec<Entity>> = query
+            .iter()
+            .filter(|(led_tube, _)| row.tubes.contains(&led_tube.get_tube_index()))
+            .map(|(_, children)| children.iter().cloned().collect())
+            .collect();
+        for tube_entity in tube_entities {
+            spawn_punch(&mut commands, tube_entity, LedLayer::Punch, 0.3, cell.color.color(&colors), colors.secondary, cell.velocity);
+        }
+    }
+}
@@ -14,16 +14,23 @@ use futures::future::join_all;
 use noise::{NoiseFn, OpenSimplex, Perlin};
 use rand::{Rng, thread_rng};
 use crate::anims::AnimColors;
+use crate::anims::wave_transition::WaveTransition;
 use crate::beat::BeatEvent;
-use crate::elements2d::tunnelgon::{CancelAnim, TunnelgonMaterial};
+use crate::elements2d::tunnelgon::TunnelgonMaterial;
 use crate::parameter_animation::{LinearAnim, ParameterAnimation, Pt1Anim};
+use crate::physics_hexagon::lights::led_layers::LedLayer;
+use crate::physics_hexagon::lights::led_symmetry::{LedSymmetry, mirror_tube, tube_positions};
 use crate::physics_hexagon::lights::led_tube::{LedTube, LedTubeLed, TubeIndex};
-use crate::physics_hexagon::lights::led_tube::TubeIndex::{Eight, Eighteen, Eleven, Fifteen, Five, Four, Fourteen, Nine, Nineteen, One, Seven, Seventeen, Six, Sixteen, Ten, Thirteen, Three, Twelve, Twenty, Twentyone, Twentytwo, Two};
+
+/// Wave modes including 0 (off)
+pub const WAVE_COUNT: usize = 8;
 
 #[derive(Resource, Default)]
 pub struct TubesWaveAnims {
     pub(crate) wave: usize,
-    accum: f32,
+    pub transition: WaveTransition,
+    /// Accumulator per wave mode
+    accum: [f32; WAVE_COUNT],
     beat_accum: f32,
     beat_accum_pt1: f32,
     pub punch: bool,
@@ -44,11 +51,20 @@ pub fn clear(
     colors: Res<AnimColors>,
 ) {
     for (mut ltl, gt) in query.iter_mut() {
-        let x = gt.translation().x;
-        ltl.color = colors.secondary.clone() * 0.2;
+        ltl.clear_layers();
+        *ltl.layer_mut(LedLayer::Base) = colors.secondary.clone() * 0.2;
     }
 }
 
+/// Add the output of a wave mode to the wave layer, weighted by the running transition
+pub fn write_wave(ltl: &mut LedTubeLed, params: &TubesWaveAnims, wave: usize, x: f32, color: Color) {
+    let (weight, coverage) = params.transition.weights(wave, x);
+    if coverage <= 0. { return; }
+    let [r, g, b, _] = (color * weight).as_rgba_f32();
+    let layer = ltl.layer_mut(LedLayer::Wave);
+    *layer = *layer + Color::rgba(r, g, b, coverage);
+}
+
 pub fn wave_simple(
     mut query: Query<(&mut LedTubeLed, &GlobalTransform)>,
     time: Res<Time>,
@@ -56,12 +72,17 @@ pub fn wave_simple(
     colors: Res<AnimColors>,
     mut beat_reader: EventReader<BeatEvent>,
 ) {
-    if params.wave == 1 { params.accum += time.delta_seconds(); } else if params.wave == 2 { params.accum -= time.delta_seconds(); } else { return; }
-
-    for (mut ltl, gt) in query.iter_mut() {
-        let x = gt.translation().x;
-        ltl.color = colors.primary * (x * 0.005 - params.accum * 2. * x.signum()).sin().powf(8.)
-            + colors.secondary * (0.2 + (x * 0.005 - params.accum * 2. * x.signum() + PI / 2.).sin().powf(8.) * 0.1);
+    for wave in params.transition.visible_waves() {
+        let direction = match wave { 1 => 1., 2 => -1., _ => continue };
+        params.accum[wave] += time.delta_seconds() * direction;
+        let accum = params.accum[wave];
+
+        for (mut ltl, gt) in query.iter_mut() {
+            let x = gt.translation().x;
+            let color = colors.primary * (x * 0.005 - accum * 2. * x.signum()).sin().powf(8.)
+                + colors.secondary * (0.2 + (x * 0.005 - accum * 2. * x.signum() + PI / 2.).sin().powf(8.) * 0.1);
+            write_wave(&mut ltl, &params, wave, x, color);
+        }
     }
 }
 
@@ -72,12 +93,18 @@ pub fn wave_blocky(
     colors: Res<AnimColors>,
     mut params: ResMut<TubesWaveAnims>,
 ) {
-    if params.wave == 3 { params.accum += time.delta_seconds(); } else if params.wave == 4 { params.accum -= time.delta_seconds(); } else { return; }
-    for (mut ltl, gt_ltl, parent) in query.iter_mut() {
-        let gt = p_query.get(parent.get()).unwrap();
-        let x = (gt.translation().x * 2. + gt_ltl.translation().x) / 3.;
-        ltl.color = colors.primary * (x * 0.005 - params.accum * 2. * x.signum()).sin().powf(8.)
-            + colors.secondary * (0.2 + (x * 0.005 - params.accum * 2. * x.signum() + PI / 2.).sin().powf(8.) * 0.1);
+    for wave in params.transition.visible_waves() {
+        let direction = match wave { 3 => 1., 4 => -1., _ => continue };
+        params.accum[wave] += time.delta_seconds() * direction;
+        let accum = params.accum[wave];
+
+        for (mut ltl, gt_ltl, parent) in query.iter_mut() {
+            let gt = p_query.get(parent.get()).unwrap();
+            let x = (gt.translation().x * 2. + gt_ltl.translation().x) / 3.;
+            let color = colors.primary * (x * 0.005 - accum * 2. * x.signum()).sin().powf(8.)
+                + colors.secondary * (0.2 + (x * 0.005 - accum * 2. * x.signum() + PI / 2.).sin().powf(8.) * 0.1);
+            write_wave(&mut ltl, &params, wave, gt_ltl.translation().x, color);
+        }
     }
 }
 
@@ -85,6 +112,57 @@ signal_ids! {
     pub CancelPunch: bool
 }
 
+/// Tube indices of the punch patterns, these follow the IDs of the default layout
+pub(crate) fn tubes(ids: &[u16]) -> Vec<TubeIndex> {
+    ids.iter().map(|id| TubeIndex(*id)).collect()
+}
+
+/// Punch envelope on one layer of the LEDs of one tube, cancelled by the next punch.
+/// Velocity scales the brightness of the primary colour.
+pub fn spawn_punch(commands: &mut Commands, leds: Vec<Entity>, layer: LedLayer, time_constant: f32, primary_color: Color, secondary_color: Color, velocity: f32) {
+    commands.spawn_task(move || async move {
+        let signal = world().named_signal::<CancelPunch>("cancel_punch");
+        let _ = signal.poll().await; // Discard first message to avoid immediate cancel
+
+        // Spawn PT1 anim
+        let pt1_entity = world().spawn_bundle(
+            Pt1Anim {
+                val: 1.3,
+                target: 0.,
+                time_constant,
+            }
+        ).await.id();
+        let pt1_component = world().entity(pt1_entity).component::<Pt1Anim>();
+
+        let led_tube_entities: Vec<AsyncEntityMut> = leds.iter().map(|child| {
+            world().entity(*child)
+        }).collect();
+
+        loop {
+            let (next_val, finished) = match signal.try_read() {
+                Some(true) => (0., true),
+                Some(false) | None => pt1_component.get(|pt1anim| { (pt1anim.get_val(), pt1anim.target_reached()) }).await.unwrap_or((0., true))
+            };
+
+            let futures: Vec<ChannelOut<AsyncResult<_>>> = led_tube_entities.iter().map(|ent| {
+                ent.component::<LedTubeLed>().set(move |ltl| {
+                    let ind = ltl.get_relative_position() - 0.5;
+                    let lum = next_val * (ind * (1.3 - next_val) * 2.).cos() * velocity;
+                    *ltl.layer_mut(layer) = primary_color.clone() * lum + secondary_color.clone() * (1. - lum.min(1.)) * 0.2;
+                })
+            }).collect();
+            let _ = join_all(futures).await;
+
+            if finished {
+                break;
+            }
+        }
+
+        world().entity(pt1_entity).despawn().await;
+        Ok(())
+    });
+}
+
 pub fn tube_punch(
     mut query: Query<(&LedTube, &Children)>,
     mut params: ResMut<TubesWaveAnims>,
@@ -99,10 +177,10 @@ pub fn tube_punch(
         signal.send(true);
 
         let anim_indices = vec![
-            vec![Five, Six, Seven, Sixteen, Seventeen, Eighteen],
-            vec![Three, Four, Eight, Nine, Fourteen, Fifteen, Nineteen, Twenty],
-            vec![One, Two, Ten, Eleven, Twelve, Thirteen, Twentyone, Twentytwo],
-            vec![Three, Four, Eight, Nine, Fourteen, Fifteen, Nineteen, Twenty],
+            tubes(&[5, 6, 7, 16, 17, 18]),
+            tubes(&[3, 4, 8, 9, 14, 15, 19, 20]),
+            tubes(&[1, 2, 10, 11, 12, 13, 21, 22]),
+            tubes(&[3, 4, 8, 9, 14, 15, 19, 20]),
             //vec![5,6,7,16,17,18],
             //vec![3,4,8,9,14,15,19,20],
             //vec![1,2,10,11,12,13,21,22],
@@ -121,52 +199,8 @@ pub fn tube_punch(
             })
             .collect();
 
-        let primary_color = colors.primary;
-        let secondary_color = colors.secondary;
-
         for tube_entity in tube_entities {
-            commands.spawn_task(move || async move {
-                let signal = world().named_signal::<CancelPunch>("cancel_punch");
-                let _ = signal.poll().await; // Discard first message to avoid immediate cancel
-
-                // Spawn PT1 anim
-                let pt1_entity = world().spawn_bundle(
-                    Pt1Anim {
-                        val: 1.3,
-                        target: 0.,
-                        time_constant: 0.3,
-                        //time_constant: 1.,
-                    }
-                ).await.id();
-                let pt1_component = world().entity(pt1_entity).component::<Pt1Anim>();
-
-                let led_tube_entities: Vec<AsyncEntityMut> = tube_entity.iter().map(|child| {
-                    world().entity(*child)
-                }).collect();
-
-                loop {
-                    let (next_val, finished) = match signal.try_read() {
-                        Some(true) => (0., true),
-                        Some(false) | None => pt1_component.get(|pt1anim| { (pt1anim.get_val(), pt1anim.target_reached()) }).await.unwrap_or((0., true))
-                    };
-
-                    let futures: Vec<ChannelOut<AsyncResult<_>>> = led_tube_entities.iter().map(|ent| {
-                        ent.component::<LedTubeLed>().set(move |ltl| {
-                            let ind = (ltl.get_index() as f32 / 15.) - 0.5;
-                            let lum = next_val * (ind * (1.3 - next_val) * 2.).cos();
-                            ltl.color = primary_color.clone() * lum + secondary_color.clone() * (1. - lum.min(1.)) * 0.2;
-                        })
-                    }).collect();
-                    let _ = join_all(futures).await;
-
-                    if finished {
-                        break;
-                    }
-                }
-
-                world().entity(pt1_entity).despawn().await;
-                Ok(())
-            });
+            spawn_punch(&mut commands, tube_entity, LedLayer::Punch, 0.3, colors.primary, colors.secondary, 1.);
         }
     }
 }
@@ -199,7 +233,7 @@ pub fn sweep(
 
         let lum = lum_r + lum_l;
 
-        ltl.color = colors.primary * lum + colors.secondary * (1. - lum) * 0.2;
+        *ltl.layer_mut(LedLayer::Sweep) = colors.primary * lum + colors.secondary * (1. - lum) * 0.2;
     }
 }
 
@@ -217,10 +251,10 @@ pub fn tube_punch_2(
         signal.send(true);
 
         let anim_indices = vec![
-            vec![Six, Fourteen, Thirteen],
-            vec![Eight, Sixteen, Eleven],
-            vec![Ten, Seventeen, Nine],
-            vec![Twelve, Fifteen ,Seven],
+            tubes(&[6, 14, 13]),
+            tubes(&[8, 16, 11]),
+            tubes(&[10, 17, 9]),
+            tubes(&[12, 15, 7]),
             //vec![5,6,7,16,17,18],
             //vec![3,4,8,9,14,15,19,20],
             //vec![1,2,10,11,12,13,21,22],
@@ -239,52 +273,8 @@ pub fn tube_punch_2(
             })
             .collect();
 
-        let primary_color = colors.primary;
-        let secondary_color = colors.secondary;
-
         for tube_entity in tube_entities {
-            commands.spawn_task(move || async move {
-                let signal = world().named_signal::<CancelPunch>("cancel_punch");
-                let _ = signal.poll().await; // Discard first message to avoid immediate cancel
-
-                // Spawn PT1 anim
-                let pt1_entity = world().spawn_bundle(
-                    Pt1Anim {
-                        val: 1.3,
-                        target: 0.,
-                        time_constant: 0.3,
-                        //time_constant: 1.,
-                    }
-                ).await.id();
-                let pt1_component = world().entity(pt1_entity).component::<Pt1Anim>();
-
-                let led_tube_entities: Vec<AsyncEntityMut> = tube_entity.iter().map(|child| {
-                    world().entity(*child)
-                }).collect();
-
-                loop {
-                    let (next_val, finished) = match signal.try_read() {
-                        Some(true) => (0., true),
-                        Some(false) | None => pt1_component.get(|pt1anim| { (pt1anim.get_val(), pt1anim.target_reached()) }).await.unwrap_or((0., true))
-                    };
-
-                    let futures: Vec<ChannelOut<AsyncResult<_>>> = led_tube_entities.iter().map(|ent| {
-                        ent.component::<LedTubeLed>().set(move |ltl| {
-                            let ind = (ltl.get_index() as f32 / 15.) - 0.5;
-                            let lum = next_val * (ind * (1.3 - next_val) * 2.).cos();
-                            ltl.color = primary_color.clone() * lum + secondary_color.clone() * (1. - lum.min(1.)) * 0.2;
-                        })
-                    }).collect();
-                    let _ = join_all(futures).await;
-
-                    if finished {
-                        break;
-                    }
-                }
-
-                world().entity(pt1_entity).despawn().await;
-                Ok(())
-            });
+            spawn_punch(&mut commands, tube_entity, LedLayer::Punch, 0.3, colors.primary, colors.secondary, 1.);
         }
     }
 }
@@ -303,10 +293,10 @@ pub fn tube_punch_3(
         signal.send(true);
 
         let anim_indices = vec![
-            vec![Six, Eight, Fourteen, Sixteen, Eleven, Thirteen],
-            vec![Ten, Twelve, Seven, Nine, Fifteen, Seventeen],
-            vec![Five, Three, Four, Eighteen, Nineteen, Twenty],
-            vec![One, Two, Twentyone, Twentytwo],
+            tubes(&[6, 8, 14, 16, 11, 13]),
+            tubes(&[10, 12, 7, 9, 15, 17]),
+            tubes(&[5, 3, 4, 18, 19, 20]),
+            tubes(&[1, 2, 21, 22]),
             //vec![5,6,7,16,17,18],
             //vec![3,4,8,9,14,15,19,20],
             //vec![1,2,10,11,12,13,21,22],
@@ -325,52 +315,8 @@ pub fn tube_punch_3(
             })
             .collect();
 
-        let primary_color = colors.primary;
-        let secondary_color = colors.secondary;
-
         for tube_entity in tube_entities {
-            commands.spawn_task(move || async move {
-                let signal = world().named_signal::<CancelPunch>("cancel_punch");
-                let _ = signal.poll().await; // Discard first message to avoid immediate cancel
-
-                // Spawn PT1 anim
-                let pt1_entity = world().spawn_bundle(
-                    Pt1Anim {
-                        val: 1.3,
-                        target: 0.,
-                        time_constant: 0.3,
-                        //time_constant: 1.,
-                    }
-                ).await.id();
-                let pt1_component = world().entity(pt1_entity).component::<Pt1Anim>();
-
-                let led_tube_entities: Vec<AsyncEntityMut> = tube_entity.iter().map(|child| {
-                    world().entity(*child)
-                }).collect();
-
-                loop {
-                    let (next_val, finished) = match signal.try_read() {
-                        Some(true) => (0., true),
-                        Some(false) | None => pt1_component.get(|pt1anim| { (pt1anim.get_val(), pt1anim.target_reached()) }).await.unwrap_or((0., true))
-                    };
-
-                    let futures: Vec<ChannelOut<AsyncResult<_>>> = led_tube_entities.iter().map(|ent| {
-                        ent.component::<LedTubeLed>().set(move |ltl| {
-                            let ind = (ltl.get_index() as f32 / 15.) - 0.5;
-                            let lum = next_val * (ind * (1.3 - next_val) * 2.).cos();
-                            ltl.color = primary_color.clone() * lum + secondary_color.clone() * (1. - lum.min(1.)) * 0.2;
-                        })
-                    }).collect();
-                    let _ = join_all(futures).await;
-
-                    if finished {
-                        break;
-                    }
-                }
-
-                world().entity(pt1_entity).despawn().await;
-                Ok(())
-            });
+            spawn_punch(&mut commands, tube_entity, LedLayer::Punch, 0.3, colors.primary, colors.secondary, 1.);
         }
     }
 }
@@ -389,10 +335,10 @@ pub fn tube_punch_4(
         signal.send(true);
 
         let anim_indices = vec![
-            vec![Three, Five, Six, Sixteen, Eighteen, Nineteen],
-            vec![Four, Five, Seven, Seventeen, Eighteen, Twenty],
-            vec![Eight, Nine, One, Two, Fourteen, Fifteen, Twentyone, Twentytwo],
-            vec![Ten, Twelve, Eleven, Thirteen],
+            tubes(&[3, 5, 6, 16, 18, 19]),
+            tubes(&[4, 5, 7, 17, 18, 20]),
+            tubes(&[8, 9, 1, 2, 14, 15, 21, 22]),
+            tubes(&[10, 12, 11, 13]),
             //vec![5,6,7,16,17,18],
             //vec![3,4,8,9,14,15,19,20],
             //vec![1,2,10,11,12,13,21,22],
@@ -411,52 +357,8 @@ pub fn tube_punch_4(
             })
             .collect();
 
-        let primary_color = colors.primary;
-        let secondary_color = colors.secondary;
-
         for tube_entity in tube_entities {
-            commands.spawn_task(move || async move {
-                let signal = world().named_signal::<CancelPunch>("cancel_punch");
-                let _ = signal.poll().await; // Discard first message to avoid immediate cancel
-
-                // Spawn PT1 anim
-                let pt1_entity = world().spawn_bundle(
-                    Pt1Anim {
-                        val: 1.3,
-                        target: 0.,
-                        time_constant: 0.3,
-                        //time_constant: 1.,
-                    }
-                ).await.id();
-                let pt1_component = world().entity(pt1_entity).component::<Pt1Anim>();
-
-                let led_tube_entities: Vec<AsyncEntityMut> = tube_entity.iter().map(|child| {
-                    world().entity(*child)
-                }).collect();
-
-                loop {
-                    let (next_val, finished) = match signal.try_read() {
-                        Some(true) => (0., true),
-                        Some(false) | None => pt1_component.get(|pt1anim| { (pt1anim.get_val(), pt1anim.target_reached()) }).await.unwrap_or((0., true))
-                    };
-
-                    let futures: Vec<ChannelOut<AsyncResult<_>>> = led_tube_entities.iter().map(|ent| {
-                        ent.component::<LedTubeLed>().set(move |ltl| {
-                            let ind = (ltl.get_index() as f32 / 15.) - 0.5;
-                            let lum = next_val * (ind * (1.3 - next_val) * 2.).cos();
-                            ltl.color = primary_color.clone() * lum + secondary_color.clone() * (1. - lum.min(1.)) * 0.2;
-                        })
-                    }).collect();
-                    let _ = join_all(futures).await;
-
-                    if finished {
-                        break;
-                    }
-                }
-
-                world().entity(pt1_entity).despawn().await;
-                Ok(())
-            });
+            spawn_punch(&mut commands, tube_entity, LedLayer::Punch, 0.3, colors.primary, colors.secondary, 1.);
         }
     }
 }
@@ -473,7 +375,7 @@ pub fn wave_noise1(
     colors: Res<AnimColors>,
     mut beat_reader: EventReader<BeatEvent>,
 ) {
-    if params.wave != 5 { return; }
+    if !params.transition.is_visible(5) { return; }
 
     let perlin = Perlin::new(1);
 
@@ -485,7 +387,7 @@ pub fn wave_noise1(
 
     for (mut ltl, gt) in query.iter_mut() {
         let val = perlin.get([gt.translation().x as f64 * 0.01, gt.translation().y as f64  * 0.01, params.beat_accum_pt1 as f64]) as f32;
-        ltl.color = colors.primary * val * 2. + colors.secondary * (1.-val) * 0.2;
+        write_wave(&mut ltl, &params, 5, gt.translation().x, colors.primary * val * 2. + colors.secondary * (1.-val) * 0.2);
     }
 }
 
@@ -496,20 +398,21 @@ pub fn wave_noise2(
     colors: Res<AnimColors>,
     mut beat_reader: EventReader<BeatEvent>,
 ) {
-    if params.wave != 6 { return; }
+    if !params.transition.is_visible(6) { return; }
 
     let perlin = Perlin::new(1);
 
     for (mut ltl, gt) in query.iter_mut() {
         let val = perlin.get([gt.translation().x as f64 * 0.01, gt.translation().y as f64  * 0.01, time.elapsed_seconds_f64()]) as f32;
-        ltl.color = colors.primary * val * 2. + colors.secondary * (1.-val) * 0.2;
+        write_wave(&mut ltl, &params, 6, gt.translation().x, colors.primary * val * 2. + colors.secondary * (1.-val) * 0.2);
     }
 }
 
 
 
 pub fn strobe1(
-    mut query: Query<(&LedTube, &Children)>,
+    mut query: Query<(&LedTube, &Children, &GlobalTransform)>,
+    symmetry: Res<LedSymmetry>,
     mut commands: Commands,
     colors: Res<AnimColors>,
     mut reactors: ResMut<Reactors>,
@@ -522,46 +425,36 @@ pub fn strobe1(
         let signal = reactors.get_named::<CancelPunch>("cancel_punch");
         signal.send(true);
 
-        let anim_indices = vec![
-            One,
-            Two,
-            Three,
-            Four,
-            Five,
-            Six,
-            Seven,
-            Eight,
-            Nine,
-            Ten,
-            Eleven,
-            Twelve,
-            Thirteen,
-            Fourteen,
-            Fifteen,
-            Sixteen,
-            Seventeen,
-            Eighteen,
-            Nineteen,
-            Twenty,
-            Twentyone,
-            Twentytwo,
-        ];
+        let anim_indices: Vec<TubeIndex> = query.iter().map(|(led_tube, _, _)| led_tube.get_tube_index()).collect();
 
         let mut rng = thread_rng();
 
-        let relevant_indices = vec![
-            anim_indices[rng.gen_range(0..anim_indices.len())],
-            anim_indices[rng.gen_range(0..anim_indices.len())],
-            anim_indices[rng.gen_range(0..anim_indices.len())],
-            anim_indices[rng.gen_range(0..anim_indices.len())],
-        ];
+        if anim_indices.is_empty() { continue; }
+        let relevant_indices = if symmetry.strobe_pairs {
+            // Two random tubes together with their mirrored partners
+            let positions = tube_positions(&query);
+            let mut indices = vec![];
+            for _ in 0..2 {
+                let tube = anim_indices[rng.gen_range(0..anim_indices.len())];
+                indices.push(tube);
+                indices.extend(mirror_tube(&positions, tube));
+            }
+            indices
+        } else {
+            vec![
+                anim_indices[rng.gen_range(0..anim_indices.len())],
+                anim_indices[rng.gen_range(0..anim_indices.len())],
+                anim_indices[rng.gen_range(0..anim_indices.len())],
+                anim_indices[rng.gen_range(0..anim_indices.len())],
+            ]
+        };
 
         let tube_entities: Vec<Vec<Entity>> = query
             .iter()
-            .filter(|(led_tube, children)| {
+            .filter(|(led_tube, children, _)| {
                 relevant_indices.contains(&led_tube.get_tube_index())
             })
-            .map(|(led_tube, children)| {
+            .map(|(led_tube, children, _)| {
                 children.iter().cloned().collect()
             })
             .collect();
@@ -570,48 +463,7 @@ pub fn strobe1(
         let secondary_color = colors.secondary;
 
         for tube_entity in tube_entities {
-            commands.spawn_task(move || async move {
-                let signal = world().named_signal::<CancelPunch>("cancel_punch");
-                let _ = signal.poll().await; // Discard first message to avoid immediate cancel
-
-                // Spawn PT1 anim
-                let pt1_entity = world().spawn_bundle(
-                    Pt1Anim {
-                        val: 1.3,
-                        target: 0.,
-                        time_constant: 0.3,
-                        //time_constant: 1.,
-                    }
-                ).await.id();
-                let pt1_component = world().entity(pt1_entity).component::<Pt1Anim>();
-
-                let led_tube_entities: Vec<AsyncEntityMut> = tube_entity.iter().map(|child| {
-                    world().entity(*child)
-                }).collect();
-
-                loop {
-                    let (next_val, finished) = match signal.try_read() {
-                        Some(true) => (0., true),
-                        Some(false) | None => pt1_component.get(|pt1anim| { (pt1anim.get_val(), pt1anim.target_reached()) }).await.unwrap_or((0., true))
-                    };
-
-                    let futures: Vec<ChannelOut<AsyncResult<_>>> = led_tube_entities.iter().map(|ent| {
-                        ent.component::<LedTubeLed>().set(move |ltl| {
-                            let ind = (ltl.get_index() as f32 / 15.) - 0.5;
-                            let lum = next_val * (ind * (1.3 - next_val) * 2.).cos();
-                            ltl.color = primary_color.clone() * lum + secondary_color.clone() * (1. - lum.min(1.)) * 0.2;
-                        })
-                    }).collect();
-                    let _ = join_all(futures).await;
-
-                    if finished {
-                        break;
-                    }
-                }
-
-                world().entity(pt1_entity).despawn().await;
-                Ok(())
-            });
+            spawn_punch(&mut commands, tube_entity, LedLayer::Strobe, 0.3, primary_color, secondary_color, 1.);
         }
     }
 }
@@ -631,30 +483,7 @@ pub fn strobe2(
         let signal = reactors.get_named::<CancelPunch>("cancel_punch");
         signal.send(true);
 
-        let anim_indices = vec![
-            One,
-            Two,
-            Three,
-            Four,
-            Five,
-            Six,
-            Seven,
-            Eight,
-            Nine,
-            Ten,
-            Eleven,
-            Twelve,
-            Thirteen,
-            Fourteen,
-            Fifteen,
-            Sixteen,
-            Seventeen,
-            Eighteen,
-            Nineteen,
-            Twenty,
-            Twentyone,
-            Twentytwo,
-        ];
+        let anim_indices: Vec<TubeIndex> = query.iter().map(|(led_tube, _)| led_tube.get_tube_index()).collect();
 
         let relevant_indices = anim_indices;
 
@@ -672,48 +501,7 @@ pub fn strobe2(
         let secondary_color = colors.secondary;
 
         for tube_entity in tube_entities {
-            commands.spawn_task(move || async move {
-                let signal = world().named_signal::<CancelPunch>("cancel_punch");
-                let _ = signal.poll().await; // Discard first message to avoid immediate cancel
-
-                // Spawn PT1 anim
-                let pt1_entity = world().spawn_bundle(
-                    Pt1Anim {
-                        val: 1.3,
-                        target: 0.,
-                        time_constant: 0.1,
-                        //time_constant: 1.,
-                    }
-                ).await.id();
-                let pt1_component = world().entity(pt1_entity).component::<Pt1Anim>();
-
-                let led_tube_entities: Vec<AsyncEntityMut> = tube_entity.iter().map(|child| {
-                    world().entity(*child)
-                }).collect();
-
-                loop {
-                    let (next_val, finished) = match signal.try_read() {
-                        Some(true) => (0., true),
-                        Some(false) | None => pt1_component.get(|pt1anim| { (pt1anim.get_val(), pt1anim.target_reached()) }).await.unwrap_or((0., true))
-                    };
-
-                    let futures: Vec<ChannelOut<AsyncResult<_>>> = led_tube_entities.iter().map(|ent| {
-                        ent.component::<LedTubeLed>().set(move |ltl| {
-                            let ind = (ltl.get_index() as f32 / 15.) - 0.5;
-                            let lum = next_val * (ind * (1.3 - next_val) * 2.).cos();
-                            ltl.color = primary_color.clone() * lum + secondary_color.clone() * (1. - lum.min(1.)) * 0.2;
-                        })
-                    }).collect();
-                    let _ = join_all(futures).await;
-
-                    if finished {
-                        break;
-                    }
-                }
-
-                world().entity(pt1_entity).despawn().await;
-                Ok(())
-            });
+            spawn_punch(&mut commands, tube_entity, LedLayer::Strobe, 0.1, primary_color, secondary_color, 1.);
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,125 @@
+//! Crossfades between the tube wave modes. While a transition runs, the previous and the new
+//! mode both render into the wave layer, weighted per LED by the transition style.
+
+use bevy::prelude::{Res, ResMut, Time};
+use strum_macros::EnumIter;
+use crate::anims::tubes::TubesWaveAnims;
+use crate::beat::beat_clock::BeatClock;
+
+/// Width of the soft edge of the wipe, relative to the screen width
+const WIPE_SOFTNESS: f32 = 0.15;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, EnumIter)]
+pub enum TransitionStyle {
+    #[default]
+    Fade,
+    /// The new mode wipes in from the left
+    WipeX,
+    /// The previous mode fades to black, then the new one fades in
+    DissolveThroughBlack,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, EnumIter)]
+pub enum TransitionUnit {
+    Seconds,
+    #[default]
+    Beats,
+}
+
+pub struct WaveTransition {
+    pub style: TransitionStyle,
+    /// 0 switches instantly
+    pub duration: f32,
+    pub unit: TransitionUnit,
+    previous: usize,
+    shown: usize,
+    progress: f32,
+}
+
+impl Default for WaveTransition {
+    fn default() -> Self {
+        Self {
+            style: TransitionStyle::Fade,
+            duration: 2.,
+            unit: TransitionUnit::Beats,
+            previous: 0,
+            shown: 0,
+            progress: 1.,
+        }
+    }
+}
+
+impl WaveTransition {
+    pub fn is_running(&self) -> bool {
+        self.progress < 1.
+    }
+
+    pub fn progress(&self) -> f32 {
+        self.progress
+    }
+
+    /// Wave modes that render this frame
+    pub fn visible_waves(&self) -> Vec<usize> {
+        if self.is_running() && self.previous != self.shown {
+            vec![self.previous, self.shown]
+        } else {
+            vec![self.shown]
+        }
+    }
+
+    pub fn is_visible(&self, wave: usize) -> bool {
+        self.visible_waves().contains(&wave)
+    }
+
+    /// Colour weight and coverage of a wave mode at the x position of an LED
+    pub fn weights(&self, wave: usize, x: f32) -> (f32, f32) {
+        let p = self.progress;
+        let (new, old) = match self.style {
+            TransitionStyle::Fade => { (p, 1. - p) }
+            TransitionStyle::WipeX => {
+                let u = (x / 1920. + 0.5).clamp(0., 1.);
+                let new = ((p * (1. + WIPE_SOFTNESS) - u) / WIPE_SOFTNESS).clamp(0., 1.);
+                (new, 1. - new)
+            }
+            TransitionStyle::DissolveThroughBlack => { ((p * 2. - 1.).max(0.), (1. - p * 2.).max(0.)) }
+        };
+        // Stay opaque through black, so the layers below don't show up in between
+        let (new_coverage, old_coverage) = match self.style {
+            TransitionStyle::DissolveThroughBlack => { if p < 0.5 { (0., 1.) } else { (1., 0.) } }
+            _ => { (new, old) }
+        };
+
+        if wave == self.shown {
+            if self.is_running() { (new, new_coverage) } else { (1., 1.) }
+        } else if wave == self.previous && self.is_running() {
+            (old, old_coverage)
+        } else {
+            (0., 0.)
+        }
+    }
+}
+
+/// Start a transition when the wave mode changed and advance the running one
+pub fn update_wave_transition(
+    mut params: ResMut<TubesWaveAnims>,
+    clock: Res<BeatClock>,
+    time: Res<Time>,
+) {
+    let wave = params.wave;
+    let transition = &mut params.transition;
+    if wave != transition.shown {
+        transition.previous = transition.shown;
+        transition.shown = wave;
+        transition.progress = 0.;
+    }
+
+    let duration = match transition.unit {
+        TransitionUnit::Seconds => { transition.duration }
+        TransitionUnit::Beats => { transition.duration * clock.beat_duration() }
+    };
+    transition.progress = if duration > 0. {
+        (transition.progress + time.delta_seconds() / duration).min(1.)
+    } else {
+        1.
+    };
+}
@@ -0,0 +1,67 @@
+//! Continuous beat position between beat events, for animations that run on subdivisions of the beat
+
+use bevy::prelude::{EventReader, Real, Res, ResMut, Resource, Time};
+use crate::beat::BeatEvent;
+use crate::beat::bpm_guesser::BpmGuesser;
+
+/// BPM used until a plausible tempo was received or guessed
+const FALLBACK_BPM: f32 = 120.;
+
+#[derive(Resource)]
+pub struct BeatClock {
+    /// Beats since start, whole numbers are beat events
+    pub position: f64,
+    pub bpm: f32,
+    /// Position of the last beat event
+    last_beat: f64,
+    /// Seconds since the last beat event
+    since_beat: f32,
+}
+
+impl Default for BeatClock {
+    fn default() -> Self {
+        Self {
+            position: 0.,
+            bpm: FALLBACK_BPM,
+            last_beat: 0.,
+            since_beat: f32::INFINITY,
+        }
+    }
+}
+
+impl BeatClock {
+    /// Position within the current beat, 0 to 1
+    pub fn phase(&self) -> f32 {
+        self.position.fract() as f32
+    }
+
+    pub fn beat_duration(&self) -> f32 {
+        60. / self.bpm
+    }
+}
+
+/// Advance the clock with the tempo and snap it to incoming beats. While beats arrive the clock
+/// waits for the next one instead of running ahead, without beats it keeps running freely.
+pub fn beat_clock_system(
+    mut clock: ResMut<BeatClock>,
+    mut beat_reader: EventReader<BeatEvent>,
+    bpm_guesser: Res<BpmGuesser>,
+    time: Res<Time<Real>>,
+) {
+    let dt = time.delta_seconds();
+    clock.since_beat += dt;
+    clock.position += (dt * clock.bpm / 60.) as f64;
+    if clock.since_beat < clock.beat_duration() * 2. {
+        clock.position = clock.position.min(clock.last_beat + 0.999);
+    }
+
+    for ev in beat_reader.read() {
+        let bpm = ev.bpm.unwrap_or_else(|| bpm_guesser.calculate_bpm());
+        if bpm.is_finite() && (40. ..=240.).contains(&bpm) {
+            clock.bpm = bpm;
+        }
+        clock.last_beat = clock.position.round().max(clock.last_beat + 1.);
+        clock.position = clock.last_beat;
+        clock.since_beat = 0.;
+    }
+}
@@ -5,10 +5,13 @@ use bevy::prelude::*;
 mod osc_receiver;
 mod plugin;
 pub mod bpm_guesser;
+pub mod beat_clock;
 
 pub use osc_receiver::{OscBeatReceiver, osc_beat_receiver_system};
 pub use plugin::OscBeatReceiverPlugin;
 
+pub const BEATS_PER_BAR: u64 = 4;
+
 /// Resource that counts how many beats have been received
 #[derive(Resource, Default)]
 pub struct BeatCounter {
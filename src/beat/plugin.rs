@@ -3,6 +3,8 @@ use crate::beat::osc_receiver::osc_beat_receiver_system;
 use crate::beat::{BeatCounter, BeatEvent, OscBeatReceiver};
 use bevy_rosc::{SingleAddressOscMethod, method_dispatcher_system};
 use crate::beat::bpm_guesser::{bpm_guesser_system, BpmGuesser};
+use crate::beat::beat_clock::{beat_clock_system, BeatClock};
+use crate::traktor_beat::traktor_beat_system;
 
 pub struct OscBeatReceiverPlugin {
     /// Address at which the osc beat signal comes in
@@ -25,6 +27,8 @@ impl Plugin for OscBeatReceiverPlugin {
             .add_systems(PreUpdate, osc_beat_receiver_system.after(method_dispatcher_system::<SingleAddressOscMethod>))
             .insert_resource(BpmGuesser::default())
             .add_systems(PreUpdate, bpm_guesser_system.after(osc_beat_receiver_system))
+            .init_resource::<BeatClock>()
+            .add_systems(PreUpdate, beat_clock_system.after(bpm_guesser_system).after(traktor_beat_system))
         ;
         app.world.spawn((
             OscBeatReceiver {},
@@ -0,0 +1,236 @@
+//! Command line modes that run without opening a window.
+//!
+//! `render-leds <recording> <output.png|output.gif> [--fps N] [--scale N]` renders an LED
+//! recording either to a PNG strip (one row per frame, one column per LED) or to an animated
+//! GIF of the tube layout.
+//!
+//! `extract-palette <image> [output.ron] [--colors N] [--method kmeans|median-cut]` extracts a
+//! palette from a PNG or (animated) GIF and prints it, or writes it as a palette file.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use image::{Delay, Frame, Rgb, RgbImage, Rgba, RgbaImage};
+use image::codecs::gif::{GifEncoder, Repeat};
+use crate::led_output::recorder::{LedRecording, read_recording, RecordedFrame};
+use crate::palette::extract::{extract_palette, ExtractMethod};
+use crate::physics_hexagon::lights::tube_layout::TubeLayout;
+
+const USAGE: &str = "Usage: render-leds <recording> <output.png|output.gif> [--fps N] [--scale N]";
+const EXTRACT_USAGE: &str = "Usage: extract-palette <image> [output.ron] [--colors N] [--method kmeans|median-cut]";
+
+/// Run a CLI mode if one was requested, returns false to start the normal app
+pub fn run_cli() -> bool {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(|arg| arg.as_str()) {
+        Some("render-leds") => {
+            if let Err(e) = render_leds(&args[1..]) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+            true
+        }
+        Some("extract-palette") => {
+            if let Err(e) = extract(&args[1..]) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+            true
+        }
+        _ => { false }
+    }
+}
+
+fn extract(args: &[String]) -> Result<(), String> {
+    let Some(input) = args.first() else { return Err(EXTRACT_USAGE.to_owned()); };
+    let mut output = None;
+    let mut count = 6;
+    let mut method = ExtractMethod::KMeans;
+    let mut rest = args[1..].iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--colors" => {
+                count = rest.next()
+                    .and_then(|value| value.parse::<usize>().ok())
+                    .filter(|value| *value >= 2)
+                    .ok_or_else(|| format!("Missing or invalid value for --colors\n{}", EXTRACT_USAGE))?;
+            }
+            "--method" => {
+                method = rest.next()
+                    .and_then(|value| ExtractMethod::parse(value))
+                    .ok_or_else(|| format!("Missing or invalid value for --method\n{}", EXTRACT_USAGE))?;
+            }
+            _ if output.is_none() && !arg.starts_with("--") => { output = Some(arg); }
+            _ => { return Err(format!("Unknown option {}\n{}", arg, EXTRACT_USAGE)); }
+        }
+    }
+
+    let palette = extract_palette(Path::new(input), count, method)?;
+    let ron = ron::ser::to_string_pretty(&palette, ron::ser::PrettyConfig::default()).map_err(|e| e.to_string())?;
+    match output {
+        Some(output) => {
+            std::fs::write(output, ron).map_err(|e| format!("{}: {}", output, e))?;
+            println!("Wrote {} colours to {}", palette.colors.len(), output);
+        }
+        None => { println!("{}", ron); }
+    }
+    Ok(())
+}
+
+struct RenderOptions {
+    fps: f32,
+    scale: f32,
+}
+
+fn render_leds(args: &[String]) -> Result<(), String> {
+    let [input, output, ..] = args else { return Err(USAGE.to_owned()); };
+    let mut options = RenderOptions { fps: 25., scale: 0.5 };
+    let mut flags = args[2..].iter();
+    while let Some(flag) = flags.next() {
+        let value = flags.next()
+            .and_then(|value| value.parse::<f32>().ok())
+            .filter(|value| *value > 0.)
+            .ok_or_else(|| format!("Missing or invalid value for {}\n{}", flag, USAGE))?;
+        match flag.as_str() {
+            "--fps" => { options.fps = value }
+            "--scale" => { options.scale = value }
+            _ => { return Err(format!("Unknown option {}\n{}", flag, USAGE)); }
+        }
+    }
+
+    let recording = read_recording(input).map_err(|e| format!("{}: {}", input, e))?;
+    let frames = resample(&recording, options.fps);
+    if frames.is_empty() {
+        return Err(format!("{}: recording has no frames", input));
+    }
+
+    if output.to_lowercase().ends_with(".gif") {
+        render_gif(&recording, &frames, &options, output)
+    } else {
+        render_strip(&recording, &frames, output)
+    }?;
+    println!("Rendered {} frames to {}", frames.len(), output);
+    Ok(())
+}
+
+/// Pick the recorded frames closest to a fixed frame rate
+fn resample(recording: &LedRecording, fps: f32) -> Vec<&RecordedFrame> {
+    let Some(last) = recording.frames.last() else { return vec![]; };
+    let count = (last.time * fps).floor() as usize + 1;
+    let mut frames = vec![];
+    let mut source = 0;
+    for i in 0..count {
+        let time = i as f32 / fps;
+        while source + 1 < recording.frames.len() && recording.frames[source + 1].time <= time {
+            source += 1;
+        }
+        frames.push(&recording.frames[source]);
+    }
+    frames
+}
+
+/// One row per frame, tubes side by side with a one pixel gap
+fn render_strip(recording: &LedRecording, frames: &[&RecordedFrame], output: &str) -> Result<(), String> {
+    let width = recording.tubes.iter().map(|(_, led_count)| led_count + 1).sum::<usize>().saturating_sub(1);
+    if width == 0 {
+        return Err("Recording has no LEDs".to_owned());
+    }
+    let mut image = RgbImage::new(width as u32, frames.len() as u32);
+    for (y, frame) in frames.iter().enumerate() {
+        let mut x = 0;
+        for colors in frame.tubes.iter() {
+            for color in colors {
+                image.put_pixel(x as u32, y as u32, Rgb(*color));
+                x += 1;
+            }
+            x += 1;
+        }
+    }
+    image.save(output).map_err(|e| format!("{}: {}", output, e))
+}
+
+/// Animated GIF of the LEDs at their positions in the tube layout
+fn render_gif(recording: &LedRecording, frames: &[&RecordedFrame], options: &RenderOptions, output: &str) -> Result<(), String> {
+    let width = (1920. * options.scale).round() as u32;
+    let height = (1080. * options.scale).round() as u32;
+    let delay = Delay::from_numer_denom_ms(1000, options.fps.round().max(1.) as u32);
+
+    let file = File::create(output).map_err(|e| format!("{}: {}", output, e))?;
+    let mut encoder = GifEncoder::new(BufWriter::new(file));
+    encoder.set_repeat(Repeat::Infinite).map_err(|e| e.to_string())?;
+    let layout = TubeLayout::load();
+
+    for frame in frames {
+        let mut image = RgbaImage::from_pixel(width, height, Rgba([0, 0, 0, 255]));
+        for ((tube, _), colors) in recording.tubes.iter().zip(frame.tubes.iter()) {
+            let Some(definition) = layout.get(*tube) else { continue; };
+            let radius = (definition.length / definition.led_count.max(1) as f32 / 2. * options.scale).max(1.);
+            for (led, color) in colors.iter().enumerate() {
+                let position = definition.led_position(led) * options.scale;
+                // Screen space is y up, images are y down
+                draw_dot(&mut image, position.x, height as f32 - position.y, radius, *color);
+            }
+        }
+        encoder.encode_frame(Frame::from_parts(image, 0, 0, delay)).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn draw_dot(image: &mut RgbaImage, cx: f32, cy: f32, radius: f32, color: [u8; 3]) {
+    let [r, g, b] = color;
+    let x_range = (cx - radius).floor().max(0.) as u32..((cx + radius).ceil() as u32).min(image.width());
+    let y_range = (cy - radius).floor().max(0.) as u32..((cy + radius).ceil() as u32).min(image.height());
+    for y in y_range {
+        for x in x_range.clone() {
+            let dx = x as f32 + 0.5 - cx;
+            let dy = y as f32 + 0.5 - cy;
+            if dx * dx + dy * dy <= radius * radius {
+                image.put_pixel(x, y, Rgba([r, g, b, 255]));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics_hexagon::lights::led_tube::TubeIndex;
+
+    fn make_recording(tubes: Vec<(TubeIndex, usize)>, times: &[f32]) -> LedRecording {
+        let frames = times.iter().map(|time| RecordedFrame {
+            time: *time,
+            tubes: tubes.iter().map(|(_, led_count)| vec![[255, 0, 0]; *led_count]).collect(),
+        }).collect();
+        LedRecording { tubes, frames }
+    }
+
+    #[test]
+    fn resample_picks_latest_frame() {
+        let recording = make_recording(vec![], &[0., 0.03, 0.1, 0.25]);
+        let frames = resample(&recording, 10.);
+        let times: Vec<f32> = frames.iter().map(|frame| frame.time).collect();
+        assert_eq!(times, vec![0., 0.1, 0.1]);
+        assert!(resample(&make_recording(vec![], &[]), 10.).is_empty());
+    }
+
+    #[test]
+    fn strip_without_leds_is_an_error() {
+        let output = std::env::temp_dir().join("vjpyree_cli_empty_strip.png").to_string_lossy().to_string();
+        let empty = make_recording(vec![], &[0.]);
+        let frames: Vec<&RecordedFrame> = empty.frames.iter().collect();
+        assert!(render_strip(&empty, &frames, &output).is_err());
+    }
+
+    #[test]
+    fn strip_has_a_gap_between_tubes() {
+        let output = std::env::temp_dir().join("vjpyree_cli_strip.png").to_string_lossy().to_string();
+        let recording = make_recording(vec![(TubeIndex(0), 2), (TubeIndex(1), 3)], &[0., 0.5]);
+        let frames: Vec<&RecordedFrame> = recording.frames.iter().collect();
+        render_strip(&recording, &frames, &output).unwrap();
+        let image = image::open(&output).unwrap().to_rgb8();
+        assert_eq!(image.dimensions(), (6, 2));
+        assert_eq!(image.get_pixel(1, 1), &Rgb([255, 0, 0]));
+        assert_eq!(image.get_pixel(2, 1), &Rgb([0, 0, 0]));
+        assert_eq!(image.get_pixel(3, 0), &Rgb([255, 0, 0]));
+    }
+}
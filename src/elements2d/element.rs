@@ -0,0 +1,170 @@
+//! 2D elements shown in the hexagons. Every element type registers itself with a name, spawn and
+//! despawn hooks and a parameter UI, and `SetHexagonElementEvent` assigns it to hexagons. A
+//! hexagon shows at most one element, assigning another one despawns the previous.
+
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use bevy::ecs::event::ManualEventReader;
+use bevy::prelude::{App, Assets, DespawnRecursiveExt, Entity, Event, Events, Handle, Local, Mesh, Mut, Quat, RegularPolygon, Resource, Transform, warn, World};
+use bevy::render::view::RenderLayers;
+use bevy::sprite::{Material2d, MaterialMesh2dBundle, Mesh2dHandle};
+use bevy::utils::default;
+use bevy_egui::egui::Ui;
+use crate::hexagon::HexagonDefinition;
+use crate::propagating_render_layers::PropagatingRenderLayers;
+
+/// Element name per hexagon, hexagons without an entry are empty
+pub type ElementAssignment = HashMap<HexagonDefinition, String>;
+
+pub trait HexagonElement: Send + Sync + 'static {
+    /// Shown in the GUI and used in events, presets and scripts
+    fn name(&self) -> &'static str;
+
+    /// Spawn the element into a hexagon, the returned entity is handed to `despawn`
+    fn spawn(&mut self, world: &mut World, hexagon: HexagonDefinition) -> Entity;
+
+    fn despawn(&mut self, world: &mut World, entity: Entity) {
+        if let Some(entity) = world.get_entity_mut(entity) {
+            entity.despawn_recursive();
+        }
+    }
+
+    /// Parameters shown in the Elements window
+    fn ui(&mut self, _ui: &mut Ui, _world: &mut World) {}
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct SetHexagonElementEvent {
+    /// Registered element name
+    pub element: String,
+    /// Hexagons showing the element afterwards, it is removed from all others
+    pub affected_hexagons: Vec<HexagonDefinition>,
+}
+
+impl SetHexagonElementEvent {
+    pub fn new(element: &str, affected_hexagons: Vec<HexagonDefinition>) -> Self {
+        Self { element: element.to_string(), affected_hexagons }
+    }
+
+    pub fn apply(&self, assignment: &mut ElementAssignment) {
+        assignment.retain(|hex, element| *element != self.element || self.affected_hexagons.contains(hex));
+        for hex in self.affected_hexagons.iter() {
+            assignment.insert(*hex, self.element.clone());
+        }
+    }
+}
+
+/// One event per element, so elements that aren't assigned anymore get removed too
+pub fn assignment_events<'a>(names: impl Iterator<Item = &'a str>, assignment: &ElementAssignment) -> Vec<SetHexagonElementEvent> {
+    names.map(|name| {
+        let mut hexagons: Vec<HexagonDefinition> = assignment.iter()
+            .filter(|(_, element)| element.as_str() == name)
+            .map(|(hex, _)| *hex)
+            .collect();
+        hexagons.sort_by_key(|hex| format!("{:?}", hex));
+        SetHexagonElementEvent::new(name, hexagons)
+    }).collect()
+}
+
+/// Hexagon shaped mesh at the place of the hexagon, on the render layer of the 2D elements
+pub fn hexagon_bundle<M: Material2d>(
+    meshes: &mut Assets<Mesh>,
+    hexagon: HexagonDefinition,
+    material: Handle<M>,
+) -> (MaterialMesh2dBundle<M>, PropagatingRenderLayers) {
+    let mesh = Mesh2dHandle(meshes.add(
+        RegularPolygon::new(HexagonDefinition::size(&hexagon).x / 2., 6)
+    ));
+    (
+        MaterialMesh2dBundle {
+            mesh,
+            material,
+            transform: Transform::from_xyz(
+                HexagonDefinition::center(&hexagon).x - 1920. / 2.,
+                HexagonDefinition::center(&hexagon).y - 1080. / 2.,
+                0.0,
+            ).with_rotation(Quat::from_rotation_z(PI / 6.)),
+            ..default()
+        },
+        PropagatingRenderLayers { render_layers: RenderLayers::layer(3) },
+    )
+}
+
+#[derive(Resource, Default)]
+pub struct HexagonElements {
+    elements: Vec<Box<dyn HexagonElement>>,
+    /// Element name and entity per occupied hexagon
+    spawned: HashMap<HexagonDefinition, (String, Entity)>,
+}
+
+impl HexagonElements {
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.elements.iter().map(|element| element.name())
+    }
+
+    pub fn assignment(&self) -> ElementAssignment {
+        self.spawned.iter().map(|(hex, (name, _))| (*hex, name.clone())).collect()
+    }
+
+    fn element_mut(&mut self, name: &str) -> Option<&mut Box<dyn HexagonElement>> {
+        self.elements.iter_mut().find(|element| element.name() == name)
+    }
+
+    fn set(&mut self, world: &mut World, event: &SetHexagonElementEvent) {
+        if self.element_mut(&event.element).is_none() {
+            warn!("Unknown hexagon element {}", event.element);
+            return;
+        }
+        let mut assignment = self.assignment();
+        event.apply(&mut assignment);
+
+        let removed: Vec<(HexagonDefinition, String, Entity)> = self.spawned.iter()
+            .filter(|(hex, (name, _))| assignment.get(*hex) != Some(name))
+            .map(|(hex, (name, entity))| (*hex, name.clone(), *entity))
+            .collect();
+        for (hex, name, entity) in removed {
+            if let Some(element) = self.element_mut(&name) {
+                element.despawn(world, entity);
+            }
+            self.spawned.remove(&hex);
+        }
+
+        for (hex, name) in assignment {
+            if self.spawned.contains_key(&hex) { continue; }
+            if let Some(element) = self.element_mut(&name) {
+                let entity = element.spawn(world, hex);
+                self.spawned.insert(hex, (name, entity));
+            }
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Box<dyn HexagonElement>> {
+        self.elements.iter_mut()
+    }
+}
+
+pub trait HexagonElementAppExt {
+    fn register_hexagon_element(&mut self, element: impl HexagonElement) -> &mut Self;
+}
+
+impl HexagonElementAppExt for App {
+    fn register_hexagon_element(&mut self, element: impl HexagonElement) -> &mut Self {
+        self.init_resource::<HexagonElements>();
+        self.world.resource_mut::<HexagonElements>().elements.push(Box::new(element));
+        self
+    }
+}
+
+/// Spawn and despawn elements for the events of this frame
+pub fn apply_hexagon_elements(
+    world: &mut World,
+    mut reader: Local<ManualEventReader<SetHexagonElementEvent>>,
+) {
+    let events: Vec<SetHexagonElementEvent> = reader.read(world.resource::<Events<SetHexagonElementEvent>>()).cloned().collect();
+    if events.is_empty() { return; }
+    world.resource_scope(|world, mut elements: Mut<HexagonElements>| {
+        for event in events.iter() {
+            elements.set(world, event);
+        }
+    });
+}
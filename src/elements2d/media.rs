@@ -0,0 +1,434 @@
+//! Media library element: GIFs, PNG sequences and stills from `assets/media` shown in hexagons,
+//! plus the Pedro GIF at its old place `assets/pedro.gif`.
+//! Every hexagon picks its own media and playback, GIFs play with their frame delays, sequences
+//! with `SEQUENCE_FPS`. A sequence is a folder of PNGs, played in file name order.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use bevy::asset::{AssetServer, Assets, Handle};
+use bevy::ecs::system::RunSystemOnce;
+use bevy::log::warn;
+use bevy::prelude::{ColorMaterial, Commands, Component, default, Entity, EventReader, Image, In, Local, Mesh, Query, Real, Res, ResMut, Resource, Time, World};
+use bevy_egui::egui;
+use bevy_egui::egui::Ui;
+use rand::{Rng, thread_rng};
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+use vleue_kinetoscope::AnimatedGif;
+use crate::anims::meta_sequence::ALL_HEXAGONS;
+use crate::beat::beat_clock::BeatClock;
+use crate::beat::BeatEvent;
+use crate::elements2d::element::{hexagon_bundle, HexagonElement};
+use crate::hexagon::HexagonDefinition;
+
+pub const MEDIA: &str = "Media";
+pub const MEDIA_DIR: &str = "assets/media";
+const ASSET_DIR: &str = "assets";
+const STILL_EXTENSIONS: [&str; 3] = ["png", "jpg", "jpeg"];
+/// Shown by hexagons without a media of their own
+pub const DEFAULT_MEDIA: &str = "pedro";
+/// Where the Pedro GIF lived before the media library, added to the library unless
+/// `assets/media` has a media of the same name
+const LEGACY_MEDIA: &str = "assets/pedro.gif";
+pub const SEQUENCE_FPS: f32 = 24.;
+/// Used for GIF frames without a delay
+const DEFAULT_FRAME_DELAY: f32 = 0.1;
+/// Seconds between looking for new and removed media
+const RESCAN_INTERVAL: f32 = 2.;
+
+pub enum MediaSource {
+    Gif(Handle<AnimatedGif>),
+    Sequence(Vec<Handle<Image>>),
+    Still(Handle<Image>),
+}
+
+pub struct MediaEntry {
+    /// File or folder name without extension
+    pub name: String,
+    path: PathBuf,
+    /// PNGs of a sequence folder, compared on rescan to pick up added and removed frames
+    frame_paths: Vec<PathBuf>,
+    pub source: MediaSource,
+}
+
+impl MediaEntry {
+    fn kind(&self) -> &'static str {
+        match self.source {
+            MediaSource::Gif(_) => { "GIF" }
+            MediaSource::Sequence(_) => { "Sequence" }
+            MediaSource::Still(_) => { "Still" }
+        }
+    }
+
+    /// 0 while a GIF is still loading
+    fn frame_count(&self, gifs: &Assets<AnimatedGif>) -> usize {
+        match &self.source {
+            MediaSource::Gif(handle) => { gifs.get(handle).map(|gif| gif.frames.len()).unwrap_or(0) }
+            MediaSource::Sequence(frames) => { frames.len() }
+            MediaSource::Still(_) => { 1 }
+        }
+    }
+
+    /// Image and duration in seconds of a frame
+    fn frame(&self, gifs: &Assets<AnimatedGif>, index: usize) -> Option<(Handle<Image>, f32)> {
+        match &self.source {
+            MediaSource::Gif(handle) => {
+                let frame = gifs.get(handle)?.frames.get(index)?;
+                let delay = frame.delay.0 as f32 / frame.delay.1.max(1) as f32 / 1000.;
+                Some((frame.image.clone(), if delay > 0. { delay } else { DEFAULT_FRAME_DELAY }))
+            }
+            MediaSource::Sequence(frames) => { frames.get(index).map(|image| (image.clone(), 1. / SEQUENCE_FPS)) }
+            MediaSource::Still(image) => { Some((image.clone(), f32::INFINITY)) }
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, EnumIter)]
+pub enum PlayMode {
+    /// Frame durations of the file times `speed`
+    #[default]
+    Free,
+    /// One pass over the frames every `beats_per_loop` beats
+    TempoSync,
+    /// `frames_per_beat` frames on every beat
+    BeatStep,
+    /// A random frame on every beat
+    Random,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct MediaPlayback {
+    pub mode: PlayMode,
+    pub speed: f32,
+    pub beats_per_loop: f32,
+    pub frames_per_beat: u32,
+    /// Play forwards and backwards instead of jumping back to the first frame
+    pub ping_pong: bool,
+}
+
+impl Default for MediaPlayback {
+    fn default() -> Self {
+        Self {
+            mode: PlayMode::Free,
+            speed: 1.,
+            beats_per_loop: 4.,
+            frames_per_beat: 1,
+            ping_pong: false,
+        }
+    }
+}
+
+impl MediaPlayback {
+    /// Frame of a linear frame position
+    fn frame(&self, position: f64, count: usize) -> usize {
+        if count <= 1 { return 0; }
+        let position = position.max(0.).floor() as usize;
+        if !self.ping_pong { return position % count; }
+        let period = 2 * (count - 1);
+        let i = position % period;
+        if i < count { i } else { period - i }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct MediaSlot {
+    pub media: String,
+    pub playback: MediaPlayback,
+}
+
+impl Default for MediaSlot {
+    fn default() -> Self {
+        Self { media: DEFAULT_MEDIA.to_string(), playback: MediaPlayback::default() }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct MediaLibrary {
+    pub entries: Vec<MediaEntry>,
+    /// Hexagons without an entry show `DEFAULT_MEDIA` with the default playback
+    pub slots: HashMap<HexagonDefinition, MediaSlot>,
+    rescan_timer: f32,
+}
+
+impl MediaLibrary {
+    pub fn slot(&self, hexagon: &HexagonDefinition) -> MediaSlot {
+        self.slots.get(hexagon).cloned().unwrap_or_default()
+    }
+
+    /// Media of the name, the first one if there is none
+    fn entry(&self, name: &str) -> Option<&MediaEntry> {
+        self.entries.iter().find(|entry| entry.name == name).or_else(|| self.entries.first())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries.iter().any(|entry| entry.name == name)
+    }
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension().map(|ext| extensions.contains(&ext.to_string_lossy().to_lowercase().as_str())).unwrap_or(false)
+}
+
+fn asset_path(path: &Path) -> PathBuf {
+    path.strip_prefix(ASSET_DIR).unwrap_or(path).to_path_buf()
+}
+
+fn sorted_entries(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else { return vec![]; };
+    let mut paths: Vec<PathBuf> = entries.filter_map(|entry| entry.ok().map(|entry| entry.path())).collect();
+    paths.sort();
+    paths
+}
+
+fn sequence_frames(dir: &Path) -> Vec<PathBuf> {
+    sorted_entries(dir).into_iter().filter(|path| has_extension(path, &["png"])).collect()
+}
+
+fn load_entry(path: &Path, asset_server: &AssetServer) -> Option<MediaEntry> {
+    let mut frame_paths = vec![];
+    let source = if path.is_dir() {
+        frame_paths = sequence_frames(path);
+        if frame_paths.is_empty() { return None; }
+        MediaSource::Sequence(frame_paths.iter().map(|path| asset_server.load(asset_path(path))).collect())
+    } else if has_extension(path, &["gif"]) {
+        MediaSource::Gif(asset_server.load(asset_path(path)))
+    } else if has_extension(path, &STILL_EXTENSIONS) {
+        MediaSource::Still(asset_server.load(asset_path(path)))
+    } else {
+        return None;
+    };
+    Some(MediaEntry {
+        name: path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default(),
+        path: path.to_path_buf(),
+        frame_paths,
+        source,
+    })
+}
+
+/// Pick up new and removed media and sequence frames, changed files are reloaded by the asset
+/// server
+pub fn scan_media_library(
+    mut library: ResMut<MediaLibrary>,
+    asset_server: Res<AssetServer>,
+    time: Res<Time<Real>>,
+    mut warned_empty: Local<bool>,
+) {
+    library.rescan_timer -= time.delta_seconds();
+    if library.rescan_timer > 0. { return; }
+    library.rescan_timer = RESCAN_INTERVAL;
+
+    let mut paths = sorted_entries(Path::new(MEDIA_DIR));
+    if paths.is_empty() && !*warned_empty {
+        warn!("{} is missing or empty, the Media element only shows {} if it exists", MEDIA_DIR, LEGACY_MEDIA);
+    }
+    *warned_empty = paths.is_empty();
+    let legacy = Path::new(LEGACY_MEDIA);
+    if legacy.is_file() && !paths.iter().any(|path| path.file_stem() == legacy.file_stem()) {
+        paths.push(legacy.to_path_buf());
+    }
+    library.entries.retain(|entry| paths.contains(&entry.path)
+        && (entry.frame_paths.is_empty() || sequence_frames(&entry.path) == entry.frame_paths));
+    let mut added = false;
+    for path in paths {
+        if library.entries.iter().any(|entry| entry.path == path) { continue; }
+        if let Some(entry) = load_entry(&path, &asset_server) {
+            library.entries.push(entry);
+            added = true;
+        }
+    }
+    if added {
+        library.entries.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+}
+
+#[derive(Component)]
+pub struct MediaPlayer {
+    hexagon_definition: HexagonDefinition,
+    /// Frames played in `Free` mode
+    position: f64,
+    random_frame: usize,
+}
+
+pub fn play_media(
+    mut query: Query<(&mut MediaPlayer, &Handle<ColorMaterial>)>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut beat_reader: EventReader<BeatEvent>,
+    library: Res<MediaLibrary>,
+    gifs: Res<Assets<AnimatedGif>>,
+    clock: Res<BeatClock>,
+    time: Res<Time<Real>>,
+) {
+    let is_beat = beat_reader.read().count() > 0;
+    let dt = time.delta_seconds();
+    let mut rng = thread_rng();
+
+    for (mut player, mat_handle) in query.iter_mut() {
+        let slot = library.slot(&player.hexagon_definition);
+        let Some(entry) = library.entry(&slot.media) else { continue; };
+        let count = entry.frame_count(&gifs);
+        if count == 0 { continue; }
+        let playback = slot.playback;
+
+        let frame = match playback.mode {
+            PlayMode::Free => {
+                let current = playback.frame(player.position, count);
+                let duration = entry.frame(&gifs, current).map(|(_, duration)| duration).unwrap_or(DEFAULT_FRAME_DELAY);
+                player.position += (dt * playback.speed / duration) as f64;
+                playback.frame(player.position, count)
+            }
+            PlayMode::TempoSync => {
+                let loops = clock.position / playback.beats_per_loop.max(0.01) as f64;
+                let frames = if playback.ping_pong { 2 * (count - 1) } else { count };
+                playback.frame(loops * frames as f64, count)
+            }
+            PlayMode::BeatStep => {
+                playback.frame(clock.position.floor() * playback.frames_per_beat as f64, count)
+            }
+            PlayMode::Random => {
+                if is_beat || player.random_frame >= count {
+                    player.random_frame = rng.gen_range(0..count);
+                }
+                player.random_frame
+            }
+        };
+
+        let Some((image, _)) = entry.frame(&gifs, frame) else { continue; };
+        let Some(mat) = materials.get(mat_handle) else { continue; };
+        if mat.texture.as_ref() == Some(&image) { continue; }
+        if let Some(mat) = materials.get_mut(mat_handle) {
+            mat.texture = Some(image);
+        }
+    }
+}
+
+pub fn spawn_media(
+    In(hexagon): In<HexagonDefinition>,
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) -> Entity {
+    let material = materials.add(ColorMaterial {
+        texture: None,
+        ..default()
+    });
+    commands.spawn((
+        hexagon_bundle(&mut meshes, hexagon, material),
+        MediaPlayer { hexagon_definition: hexagon, position: 0., random_frame: 0 },
+    )).id()
+}
+
+#[derive(Default)]
+pub struct MediaElement {
+    /// Index into `ALL_HEXAGONS` of the hexagon whose media is edited
+    edited: usize,
+}
+
+impl HexagonElement for MediaElement {
+    fn name(&self) -> &'static str {
+        MEDIA
+    }
+
+    fn spawn(&mut self, world: &mut World, hexagon: HexagonDefinition) -> Entity {
+        world.run_system_once_with(hexagon, spawn_media)
+    }
+
+    fn ui(&mut self, ui: &mut Ui, world: &mut World) {
+        let mut library = world.resource_mut::<MediaLibrary>();
+        if library.entries.is_empty() {
+            ui.colored_label(egui::Color32::RED, format!("No media in {}", MEDIA_DIR));
+            return;
+        }
+        let entries: Vec<(String, &'static str)> = library.entries.iter().map(|entry| (entry.name.clone(), entry.kind())).collect();
+
+        ui.horizontal_wrapped(|ui| {
+            for (i, hex) in ALL_HEXAGONS.iter().enumerate() {
+                let mut text = egui::RichText::new(format!("{:?}", hex));
+                if !library.contains(&library.slot(hex).media) {
+                    text = text.color(egui::Color32::RED);
+                }
+                ui.selectable_value(&mut self.edited, i, text);
+            }
+        });
+        let hex = ALL_HEXAGONS[self.edited.min(ALL_HEXAGONS.len() - 1)];
+        let mut slot = library.slot(&hex);
+
+        let missing = !library.contains(&slot.media);
+        egui::ComboBox::from_id_source("media_entry")
+            .selected_text(if missing { format!("{} (missing)", slot.media) } else { slot.media.clone() })
+            .show_ui(ui, |ui| {
+                for (name, kind) in entries.iter() {
+                    ui.selectable_value(&mut slot.media, name.clone(), format!("{} ({})", name, kind));
+                }
+            });
+        if missing {
+            ui.colored_label(egui::Color32::RED, format!("{} is not in {}, showing {}", slot.media, MEDIA_DIR, entries[0].0));
+        }
+        ui.horizontal(|ui| {
+            for mode in PlayMode::iter() {
+                ui.selectable_value(&mut slot.playback.mode, mode, format!("{:?}", mode));
+            }
+        });
+        ui.horizontal(|ui| {
+            match slot.playback.mode {
+                PlayMode::Free => {
+                    ui.label("Speed");
+                    ui.add(egui::DragValue::new(&mut slot.playback.speed).speed(0.01).clamp_range(0.0..=10.0));
+                }
+                PlayMode::TempoSync => {
+                    ui.label("Beats per loop");
+                    ui.add(egui::DragValue::new(&mut slot.playback.beats_per_loop).speed(0.25).clamp_range(0.25..=64.0));
+                }
+                PlayMode::BeatStep => {
+                    ui.label("Frames per beat");
+                    ui.add(egui::DragValue::new(&mut slot.playback.frames_per_beat).clamp_range(1..=64));
+                }
+                PlayMode::Random => {}
+            }
+            if slot.playback.mode != PlayMode::Random {
+                ui.checkbox(&mut slot.playback.ping_pong, "Ping-pong");
+            }
+        });
+        if ui.button("Apply to all").clicked() {
+            for hex in ALL_HEXAGONS {
+                library.slots.insert(hex, slot.clone());
+            }
+        }
+
+        if slot != library.slot(&hex) {
+            library.slots.insert(hex, slot);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frames(playback: &MediaPlayback, count: usize) -> Vec<usize> {
+        (0..10).map(|position| playback.frame(position as f64, count)).collect()
+    }
+
+    #[test]
+    fn loops() {
+        let playback = MediaPlayback::default();
+        assert_eq!(frames(&playback, 4), vec![0, 1, 2, 3, 0, 1, 2, 3, 0, 1]);
+        assert_eq!(playback.frame(2.99, 4), 2);
+        assert_eq!(playback.frame(-3., 4), 0);
+    }
+
+    #[test]
+    fn ping_pong_repeats_no_end_frame() {
+        let playback = MediaPlayback { ping_pong: true, ..default() };
+        assert_eq!(frames(&playback, 4), vec![0, 1, 2, 3, 2, 1, 0, 1, 2, 3]);
+        assert_eq!(frames(&playback, 2), vec![0, 1, 0, 1, 0, 1, 0, 1, 0, 1]);
+    }
+
+    #[test]
+    fn single_frame() {
+        for ping_pong in [false, true] {
+            let playback = MediaPlayback { ping_pong, ..default() };
+            assert_eq!(frames(&playback, 1), vec![0; 10]);
+            assert_eq!(frames(&playback, 0), vec![0; 10]);
+        }
+    }
+}
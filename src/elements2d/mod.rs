@@ -1,23 +1,33 @@
+pub mod element;
 pub mod zoomagon;
 pub mod render;
 pub mod tunnelgon;
+pub mod tunnelgon_motion;
+pub mod tunnelgon_layers;
+pub mod tunnelgon_colors;
 pub mod swirlagon;
-pub mod pedrogon;
+pub mod media;
+pub mod shadergon;
 
 use bevy::app::{App, PreUpdate};
 use bevy::core_pipeline::bloom::BloomSettings;
 use bevy::core_pipeline::core_2d::graph::Node2d::Tonemapping;
 use bevy::core_pipeline::tonemapping::Tonemapping::TonyMcMapface;
 use bevy::pbr::MaterialPlugin;
-use bevy::prelude::{Camera, Camera2dBundle, Color, Commands, default, OrthographicProjection, Plugin, Res, Startup, Update};
+use bevy::prelude::{Camera, Camera2dBundle, Color, Commands, default, IntoSystemConfigs, OrthographicProjection, Plugin, Res, Startup, Update};
 use bevy::render::camera::{RenderTarget, ScalingMode};
 use bevy::render::view::RenderLayers;
 use bevy::sprite::Material2dPlugin;
 use bevy_defer::AsyncExtension;
-use crate::elements2d::pedrogon::{SetPedrogonEvent, show_pedrogon, spawn_pedrogon, update_pedrogon};
+use crate::elements2d::element::{apply_hexagon_elements, HexagonElementAppExt, HexagonElements, SetHexagonElementEvent};
+use crate::elements2d::media::{MediaElement, MediaLibrary, play_media, scan_media_library};
 use crate::elements2d::render::Elements2dRendertarget;
-use crate::elements2d::swirlagon::{SetSwirlagonEvent, show_swirlagon_system, spawn_swirlagon, SwirlagonRenderMaterial};
-use crate::elements2d::tunnelgon::{CancelAnim, laser_animation_system, LaserAnimationEvent, ring_animation_system, RingAnimationEvent, SetTunnelgonEvent, spawn_tunnelgon_system, tunnelgon_accum, TunnelgonAccum, TunnelgonMaterial};
+use crate::elements2d::shadergon::{scan_user_shaders, ShadergonElement, ShadergonMaterial, Shadergons, update_shadergons};
+use crate::elements2d::swirlagon::{SwirlagonElement, SwirlagonRenderMaterial};
+use crate::elements2d::tunnelgon::{apply_tunnelgon_layout, laser_animation_system, LaserAnimationEvent, ring_animation_system, RingAnimationEvent, TunnelgonElement, TunnelgonLayout, TunnelgonMaterial};
+use crate::elements2d::tunnelgon_colors::{sync_tunnelgon_colors, TunnelgonColors};
+use crate::elements2d::tunnelgon_layers::{composite_tunnelgon_layers, TunnelgonLayerSettings};
+use crate::elements2d::tunnelgon_motion::{tunnelgon_motion, TunnelgonMotions};
 use crate::elements2d::zoomagon::{spawn_zoomagon_system, SpawnZoomagonEvent, zoomagon_system};
 use crate::propagating_render_layers::PropagatingRenderLayers;
 
@@ -26,21 +36,30 @@ pub struct Elements2DPlugin;
 impl Plugin for Elements2DPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<Elements2dRendertarget>();
-        app.add_systems(Startup, (setup_elements_2d, spawn_swirlagon, spawn_pedrogon));
+        app.add_systems(Startup, setup_elements_2d);
+        app.init_resource::<HexagonElements>();
+        app.add_event::<SetHexagonElementEvent>();
         app.add_event::<SpawnZoomagonEvent>();
         app.add_systems(Update, (spawn_zoomagon_system, zoomagon_system));
-        app.add_event::<SetTunnelgonEvent>();
         app.add_event::<LaserAnimationEvent>();
         app.add_event::<RingAnimationEvent>();
         app.add_plugins(Material2dPlugin::<TunnelgonMaterial>::default());
-        app.add_systems(Update, (spawn_tunnelgon_system, laser_animation_system, ring_animation_system));
-        app.add_event::<SetSwirlagonEvent>();
+        app.init_resource::<TunnelgonLayout>();
+        app.init_resource::<TunnelgonLayerSettings>();
+        app.add_systems(Update, (apply_hexagon_elements, apply_tunnelgon_layout, laser_animation_system, ring_animation_system, composite_tunnelgon_layers).chain());
         app.add_plugins(Material2dPlugin::<SwirlagonRenderMaterial>::default());
-        app.add_systems(Update, (show_swirlagon_system));
-        app.add_event::<SetPedrogonEvent>();
-        app.add_systems(Update, (show_pedrogon, update_pedrogon));
-        app.add_systems(Update, tunnelgon_accum);
-        app.init_resource::<TunnelgonAccum>();
+        app.add_systems(Update, (tunnelgon_motion, sync_tunnelgon_colors));
+        app.init_resource::<TunnelgonMotions>();
+        app.init_resource::<TunnelgonColors>();
+        app.register_hexagon_element(TunnelgonElement);
+        app.register_hexagon_element(SwirlagonElement);
+        app.init_resource::<MediaLibrary>();
+        app.add_systems(Update, (scan_media_library, play_media).chain());
+        app.register_hexagon_element(MediaElement::default());
+        app.add_plugins(Material2dPlugin::<ShadergonMaterial>::default());
+        app.init_resource::<Shadergons>();
+        app.add_systems(Update, (scan_user_shaders, update_shadergons).chain());
+        app.register_hexagon_element(ShadergonElement);
     }
 }
 
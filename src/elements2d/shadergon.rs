@@ -0,0 +1,229 @@
+//! Shadergon: fragment shaders from `assets/shaders/user/*.wgsl` rendered into hexagons. Every
+//! shader gets the same uniform block and the previous frame, so a new visual only needs a shader
+//! file. Changed shaders are hot reloaded by the asset file watcher, new and removed files are
+//! picked up by a rescan.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use bevy::asset::{Asset, AssetServer, Assets, Handle};
+use bevy::ecs::system::RunSystemOnce;
+use bevy::prelude::{Color, Commands, Component, Entity, Image, In, Mesh, Query, Real, Res, ResMut, Resource, Shader, Time, TypePath, World};
+use bevy::render::mesh::MeshVertexBufferLayout;
+use bevy::render::render_resource::{AsBindGroup, RenderPipelineDescriptor, ShaderType, SpecializedMeshPipelineError};
+use bevy::sprite::{Material2d, Material2dKey};
+use bevy_egui::egui;
+use bevy_egui::egui::Ui;
+use crate::anims::AnimColors;
+use crate::anims::meta_sequence::ALL_HEXAGONS;
+use crate::beat::beat_clock::BeatClock;
+use crate::elements2d::element::{hexagon_bundle, HexagonElement};
+use crate::elements2d::render::Elements2dRendertarget;
+use crate::hexagon::HexagonDefinition;
+
+pub const SHADERGON: &str = "Shadergon";
+pub const USER_SHADER_DIR: &str = "assets/shaders/user";
+const SHADER_EXTENSION: &str = "wgsl";
+/// Seconds between looking for new and removed shader files
+const RESCAN_INTERVAL: f32 = 1.;
+
+#[derive(Component)]
+pub struct Shadergon {
+    pub(crate) hexagon_definition: HexagonDefinition,
+}
+
+/// Uniform block of the user shaders, binding 2 of group 2
+#[derive(Clone, Debug, Default, ShaderType)]
+pub struct ShadergonParams {
+    /// Seconds since startup, wraps after an hour
+    pub time: f32,
+    /// Position on the beat clock in beats
+    pub beats: f32,
+    pub beat_count: u32,
+    pub bpm: f32,
+    /// `HexagonDefinition` as index, Main is 0 and A1 to B3 are 1 to 6
+    pub hexagon: u32,
+    pub primary: Color,
+    pub secondary: Color,
+}
+
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
+#[bind_group_data(ShadergonKey)]
+pub struct ShadergonMaterial {
+    #[texture(0)]
+    #[sampler(1)]
+    prev: Handle<Image>,
+    #[uniform(2)]
+    pub(crate) params: ShadergonParams,
+    pub(crate) shader: Handle<Shader>,
+}
+
+/// Pipelines are specialized per user shader
+#[derive(Clone, Eq, PartialEq, Hash)]
+pub struct ShadergonKey {
+    shader: Handle<Shader>,
+}
+
+impl From<&ShadergonMaterial> for ShadergonKey {
+    fn from(material: &ShadergonMaterial) -> Self {
+        Self { shader: material.shader.clone() }
+    }
+}
+
+impl Material2d for ShadergonMaterial {
+    fn specialize(
+        descriptor: &mut RenderPipelineDescriptor,
+        _layout: &MeshVertexBufferLayout,
+        key: Material2dKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        if let Some(fragment) = descriptor.fragment.as_mut() {
+            fragment.shader = key.bind_group_data.shader;
+        }
+        Ok(())
+    }
+}
+
+pub struct UserShader {
+    /// File name without extension
+    pub name: String,
+    path: PathBuf,
+    pub handle: Handle<Shader>,
+}
+
+#[derive(Resource, Default)]
+pub struct Shadergons {
+    pub shaders: Vec<UserShader>,
+    /// Shader name per hexagon, hexagons without an entry show the first shader
+    pub selected: HashMap<HexagonDefinition, String>,
+    rescan_timer: f32,
+}
+
+impl Shadergons {
+    pub fn shader(&self, hexagon: &HexagonDefinition) -> Handle<Shader> {
+        self.selected.get(hexagon)
+            .and_then(|name| self.shaders.iter().find(|shader| shader.name == *name))
+            .or_else(|| self.shaders.first())
+            .map(|shader| shader.handle.clone())
+            .unwrap_or_default()
+    }
+}
+
+fn shader_files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else { return vec![]; };
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().map(|ext| ext == SHADER_EXTENSION).unwrap_or(false))
+        .collect();
+    files.sort();
+    files
+}
+
+/// Pick up new and removed shader files, changes to loaded ones are reloaded by the asset server
+pub fn scan_user_shaders(
+    mut shadergons: ResMut<Shadergons>,
+    asset_server: Res<AssetServer>,
+    time: Res<Time<Real>>,
+) {
+    shadergons.rescan_timer -= time.delta_seconds();
+    if shadergons.rescan_timer > 0. { return; }
+    shadergons.rescan_timer = RESCAN_INTERVAL;
+
+    let files = shader_files(Path::new(USER_SHADER_DIR));
+    if files.len() == shadergons.shaders.len() && shadergons.shaders.iter().all(|shader| files.contains(&shader.path)) {
+        return;
+    }
+    shadergons.shaders.retain(|shader| files.contains(&shader.path));
+    for path in files {
+        if shadergons.shaders.iter().any(|shader| shader.path == path) { continue; }
+        let Some(file_name) = path.file_name().map(|name| name.to_string_lossy().to_string()) else { continue; };
+        shadergons.shaders.push(UserShader {
+            name: path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default(),
+            handle: asset_server.load(format!("shaders/user/{}", file_name)),
+            path,
+        });
+    }
+    shadergons.shaders.sort_by(|a, b| a.name.cmp(&b.name));
+}
+
+pub fn update_shadergons(
+    mut materials: ResMut<Assets<ShadergonMaterial>>,
+    query: Query<(&Shadergon, &Handle<ShadergonMaterial>)>,
+    shadergons: Res<Shadergons>,
+    clock: Res<BeatClock>,
+    colors: Res<AnimColors>,
+    time: Res<Time<Real>>,
+) {
+    for (sg, mat_handle) in query.iter() {
+        let Some(mat) = materials.get_mut(mat_handle) else { continue; };
+        let shader = shadergons.shader(&sg.hexagon_definition);
+        if mat.shader != shader {
+            mat.shader = shader;
+        }
+        mat.params.time = time.elapsed_seconds_wrapped();
+        mat.params.beats = clock.position as f32;
+        mat.params.beat_count = clock.position.max(0.).floor() as u32;
+        mat.params.bpm = clock.bpm;
+        mat.params.primary = colors.primary;
+        mat.params.secondary = colors.secondary;
+    }
+}
+
+pub fn spawn_shadergon(
+    In(hexagon): In<HexagonDefinition>,
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ShadergonMaterial>>,
+    rt: Res<Elements2dRendertarget>,
+    shadergons: Res<Shadergons>,
+) -> Entity {
+    let material = materials.add(ShadergonMaterial {
+        prev: rt.render_target.clone(),
+        params: ShadergonParams {
+            hexagon: hexagon as u32,
+            ..Default::default()
+        },
+        shader: shadergons.shader(&hexagon),
+    });
+    commands.spawn((
+        hexagon_bundle(&mut meshes, hexagon, material),
+        Shadergon { hexagon_definition: hexagon },
+    )).id()
+}
+
+pub struct ShadergonElement;
+
+impl HexagonElement for ShadergonElement {
+    fn name(&self) -> &'static str {
+        SHADERGON
+    }
+
+    fn spawn(&mut self, world: &mut World, hexagon: HexagonDefinition) -> Entity {
+        world.run_system_once_with(hexagon, spawn_shadergon)
+    }
+
+    fn ui(&mut self, ui: &mut Ui, world: &mut World) {
+        let mut shadergons = world.resource_mut::<Shadergons>();
+        if shadergons.shaders.is_empty() {
+            ui.label(format!("No shaders in {}", USER_SHADER_DIR));
+            return;
+        }
+        let names: Vec<String> = shadergons.shaders.iter().map(|shader| shader.name.clone()).collect();
+        ui.label(format!("{} shaders in {}", names.len(), USER_SHADER_DIR));
+        egui::Grid::new("shadergon_shaders").show(ui, |ui| {
+            for hex in ALL_HEXAGONS {
+                ui.label(format!("{:?}", hex));
+                let current = shadergons.selected.get(&hex).filter(|name| names.contains(name))
+                    .unwrap_or(&names[0]).clone();
+                egui::ComboBox::from_id_source(("shadergon", hex))
+                    .selected_text(&current)
+                    .show_ui(ui, |ui| {
+                        for name in names.iter() {
+                            if ui.selectable_label(*name == current, name).clicked() {
+                                shadergons.selected.insert(hex, name.clone());
+                            }
+                        }
+                    });
+                ui.end_row();
+            }
+        });
+    }
+}
@@ -6,6 +6,8 @@ use crate::hexagon::HexagonDefinition;
 use bevy::prelude::*;
 use bevy::render::render_resource::{AsBindGroup, ShaderRef};
 use bevy::render::view::RenderLayers;
+use bevy::ecs::system::RunSystemOnce;
+use crate::elements2d::element::{hexagon_bundle, HexagonElement};
 use crate::elements2d::tunnelgon::{TunnelgonMaterial, TunnelgonParams};
 use crate::propagating_render_layers::PropagatingRenderLayers;
 use crate::swirl::render_target::SwirlRenderTarget;
@@ -15,11 +17,6 @@ pub struct Swirlagon {
     hexagon_definition: HexagonDefinition,
 }
 
-#[derive(Event)]
-pub struct SetSwirlagonEvent {
-    pub affected_hexagons: Vec<HexagonDefinition>,
-}
-
 #[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
 pub struct SwirlagonRenderMaterial {
     #[texture(0)]
@@ -33,49 +30,33 @@ impl Material2d for SwirlagonRenderMaterial {
     }
 }
 
+pub const SWIRLAGON: &str = "Swirlagon";
+
 pub fn spawn_swirlagon(
+    In(hexagon): In<HexagonDefinition>,
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<SwirlagonRenderMaterial>>,
     swirl_rt: Res<SwirlRenderTarget>,
-) {
-    for hex in vec![HexagonDefinition::A1, HexagonDefinition::A2, HexagonDefinition::A3,
-                    HexagonDefinition::B1, HexagonDefinition::B2, HexagonDefinition::B3, HexagonDefinition::Main] {
-        let mesh = Mesh2dHandle(meshes.add(
-            RegularPolygon::new(HexagonDefinition::size(&hex).x / 2., 6)
-        ));
-        commands.spawn((
-            MaterialMesh2dBundle {
-                mesh,
-                material: materials.add(SwirlagonRenderMaterial {
-                    tex: swirl_rt.render_target.clone(),
-                }),
-                transform: Transform::from_xyz(
-                    // Distribute shapes from -X_EXTENT to +X_EXTENT.
-                    HexagonDefinition::center(&hex).x - 1920. / 2.,
-                    HexagonDefinition::center(&hex).y - 1080. / 2.,
-                    0.0,
-                ).with_rotation(Quat::from_rotation_z(PI / 6.)),
-                visibility: Visibility::Hidden,
-                ..default()
-            },
-            PropagatingRenderLayers { render_layers: RenderLayers::layer(3) },
-            Swirlagon { hexagon_definition: hex },
-        ));
-    }
+) -> Entity {
+    let material = materials.add(SwirlagonRenderMaterial {
+        tex: swirl_rt.render_target.clone(),
+    });
+    commands.spawn((
+        hexagon_bundle(&mut meshes, hexagon, material),
+        Swirlagon { hexagon_definition: hexagon },
+    )).id()
 }
 
-pub fn show_swirlagon_system(
-    mut query: Query<(&Swirlagon, &mut Visibility)>,
-    mut ev_reader: EventReader<SetSwirlagonEvent>,
-) {
-    for ev in ev_reader.read() {
-        for (sw, mut vis) in query.iter_mut() {
-            if ev.affected_hexagons.contains(&sw.hexagon_definition) {
-                *vis = Visibility::Visible;
-            } else {
-                *vis = Visibility::Hidden;
-            }
-        }
+/// Shows the swirl render target
+pub struct SwirlagonElement;
+
+impl HexagonElement for SwirlagonElement {
+    fn name(&self) -> &'static str {
+        SWIRLAGON
+    }
+
+    fn spawn(&mut self, world: &mut World, hexagon: HexagonDefinition) -> Entity {
+        world.run_system_once_with(hexagon, spawn_swirlagon)
     }
-}
\ No newline at end of file
+}
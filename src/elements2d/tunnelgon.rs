@@ -7,16 +7,23 @@ use std::ptr::read;
 use bevy::asset::Assets;
 use bevy::log::warn;
 use bevy::math::Quat;
-use bevy::prelude::{Asset, Color, ColorMaterial, Commands, Component, default, DespawnRecursiveExt, Entity, Event, EventReader, Handle, Image, Mesh, Query, Real, RegularPolygon, Res, ResMut, Resource, Time, Transform, TypePath, With};
+use bevy::ecs::system::RunSystemOnce;
+use bevy::prelude::{Asset, Color, ColorMaterial, Commands, Component, default, DespawnRecursiveExt, Entity, Event, EventReader, Handle, Image, In, Mesh, Query, Real, RegularPolygon, Res, ResMut, Resource, Time, Transform, TypePath, With, World};
 use bevy::render::render_resource::{AsBindGroup, ShaderRef, ShaderType};
 use bevy::render::view::RenderLayers;
 use bevy::sprite::{Material2d, MaterialMesh2dBundle, Mesh2dHandle};
 use bevy_defer::{async_system, AsyncAccess, AsyncCommandsExtension, signal_ids, world};
 use bevy_defer::reactors::Reactors;
 use bevy_defer::signals::{Receiver, Sender, Signal, Signals, SignalSender};
+use bevy_egui::egui;
+use bevy_egui::egui::Ui;
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
 use crate::beat::BeatEvent;
 use crate::parameter_animation::{LinearAnim, ParameterAnimation, Pt1Anim};
+use crate::elements2d::element::{hexagon_bundle, HexagonElement};
 use crate::elements2d::render::Elements2dRendertarget;
+use crate::elements2d::tunnelgon_layers::{AnimLayer, LayerBlend, RingParam, TunnelgonAnimLayers, TunnelgonLayerSettings};
 use crate::elements2d::zoomagon::Zoomagon;
 use crate::hexagon::HexagonDefinition;
 use crate::propagating_render_layers::PropagatingRenderLayers;
@@ -24,45 +31,128 @@ use crate::propagating_render_layers::PropagatingRenderLayers;
 #[derive(Component)]
 pub struct Tunnelgon {
     pub params: TunnelgonParams,
-    hexagon_definition: HexagonDefinition,
+    pub(crate) hexagon_definition: HexagonDefinition,
 }
 
 #[derive(Clone, Debug, ShaderType)]
 pub struct TunnelgonParams {
-    pub rings_pos: [f32; 8],
-    pub rings_amp: [f32; 8],
-    pub laser: [f32; 8],
     pub spiral_freq: f32,
     pub spiral_skew: f32,
     pub spiral_dir: f32,
     pub spiral_accum: f32,
     pub tun_accum: f32,
     pub tun_accum_target: f32,
+    /// Tunnel, laser and ring colours, alpha blends from the shader palettes to the colour
+    pub primary: Color,
+    pub secondary: Color,
+    pub accent: Color,
 }
 
 impl Default for TunnelgonParams {
     fn default() -> Self {
         Self {
-            rings_pos: [0., 0., 0., 0., 0., 0., 0., 0.],
-            rings_amp: [0., 0., 0., 0., 0., 0., 0., 0.],
-            laser: [0., 0., 0., 0., 0., 0., 0., 0.],
-            spiral_freq: 10.,
-            spiral_skew: 6.,
+            spiral_freq: 12.,
+            spiral_skew: -3.,
             spiral_dir: 1.,
             spiral_accum: 1.,
             tun_accum: 0.,
             tun_accum_target: 0.,
+            primary: Color::rgba(0., 0., 0., 0.),
+            secondary: Color::rgba(0., 0., 0., 0.),
+            accent: Color::rgba(0., 0., 0., 0.),
         }
     }
 }
 
+/// Laser shooting from the centre of the tunnel outwards
+#[derive(Copy, Clone, PartialEq, Debug, ShaderType)]
+pub struct TunnelgonLaser {
+    /// Radians, the hexagon corners sit at (i + 0.5) * PI / 3
+    pub angle: f32,
+    pub value: f32,
+    /// Angular width, a full turn is 2
+    pub width: f32,
+    /// Replaces the shader palette by its alpha
+    pub color: Color,
+}
+
+impl Default for TunnelgonLaser {
+    fn default() -> Self {
+        Self {
+            angle: PI / 6.,
+            value: 0.,
+            width: 0.02,
+            color: Color::rgba(1., 1., 1., 0.),
+        }
+    }
+}
+
+/// Hexagonal ring travelling through the tunnel
+#[derive(Copy, Clone, PartialEq, Debug, ShaderType)]
+pub struct TunnelgonRing {
+    /// 0 at the front, 1 in the depth of the tunnel
+    pub pos: f32,
+    pub amp: f32,
+    pub thickness: f32,
+    /// Replaces the shader palette by its alpha
+    pub color: Color,
+}
+
+impl Default for TunnelgonRing {
+    fn default() -> Self {
+        Self {
+            pos: 0.,
+            amp: 0.,
+            thickness: 0.01,
+            color: Color::rgba(1., 1., 1., 0.),
+        }
+    }
+}
+
+/// Lasers and rings every tunnelgon is spawned with. There is always at least one of each, the
+/// shader can't bind empty storage buffers.
+#[derive(Resource, Clone, PartialEq, Debug)]
+pub struct TunnelgonLayout {
+    pub lasers: Vec<TunnelgonLaser>,
+    pub rings: Vec<TunnelgonRing>,
+}
+
+impl Default for TunnelgonLayout {
+    fn default() -> Self {
+        let mut layout = Self { lasers: vec![], rings: vec![] };
+        layout.set_laser_count(6);
+        layout.set_ring_count(8);
+        layout
+    }
+}
+
+impl TunnelgonLayout {
+    /// Spread `count` lasers evenly, starting at the first hexagon corner. Widths and colours of
+    /// existing lasers are kept.
+    pub fn set_laser_count(&mut self, count: usize) {
+        let count = count.max(1);
+        self.lasers.resize(count, TunnelgonLaser::default());
+        for (i, laser) in self.lasers.iter_mut().enumerate() {
+            laser.angle = PI / 6. + i as f32 * 2. * PI / count as f32;
+        }
+    }
+
+    pub fn set_ring_count(&mut self, count: usize) {
+        self.rings.resize(count.max(1), TunnelgonRing::default());
+    }
+}
+
 #[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
 pub struct TunnelgonMaterial {
     #[texture(0)]
     #[sampler(1)]
     prev: Handle<Image>,
     #[storage(2, read_only)]
-    params: TunnelgonParams,
+    pub(crate) params: TunnelgonParams,
+    #[storage(3, read_only)]
+    pub(crate) lasers: Vec<TunnelgonLaser>,
+    #[storage(4, read_only)]
+    pub(crate) rings: Vec<TunnelgonRing>,
 }
 
 impl Material2d for TunnelgonMaterial {
@@ -71,53 +161,108 @@ impl Material2d for TunnelgonMaterial {
     }
 }
 
-#[derive(Event)]
-pub struct SetTunnelgonEvent {
-    pub affected_hexagons: Vec<HexagonDefinition>,
-}
+pub const TUNNELGON: &str = "Tunnelgon";
 
-pub fn spawn_tunnelgon_system(
+pub fn spawn_tunnelgon(
+    In(hexagon): In<HexagonDefinition>,
     mut commands: Commands,
-    mut event_reader: EventReader<SetTunnelgonEvent>,
-    mut query: Query<Entity, With<Tunnelgon>>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<TunnelgonMaterial>>,
     rt: Res<Elements2dRendertarget>,
-    mut reactors: ResMut<Reactors>,
-) {
-    for event in event_reader.read() {
-        for entity in query.iter() {
-            commands.entity(entity).despawn_recursive();
+    layout: Res<TunnelgonLayout>,
+) -> Entity {
+    let material = materials.add(TunnelgonMaterial {
+        prev: rt.render_target.clone(),
+        params: TunnelgonParams::default(),
+        lasers: layout.lasers.clone(),
+        rings: layout.rings.clone(),
+    });
+    commands.spawn((
+        hexagon_bundle(&mut meshes, hexagon, material),
+        Tunnelgon {
+            hexagon_definition: hexagon,
+            params: TunnelgonParams::default(),
+        },
+        TunnelgonAnimLayers::default(),
+    )).id()
+}
+
+pub struct TunnelgonElement;
+
+impl HexagonElement for TunnelgonElement {
+    fn name(&self) -> &'static str {
+        TUNNELGON
+    }
+
+    fn spawn(&mut self, world: &mut World, hexagon: HexagonDefinition) -> Entity {
+        world.run_system_once_with(hexagon, spawn_tunnelgon)
+    }
+
+    fn ui(&mut self, ui: &mut Ui, world: &mut World) {
+        egui::CollapsingHeader::new("Layers").show(ui, |ui| {
+            let mut layer_settings = world.resource_mut::<TunnelgonLayerSettings>();
+            for layer in AnimLayer::iter() {
+                let settings = &mut layer_settings.layers[layer.index()];
+                ui.horizontal(|ui| {
+                    ui.add_sized([80., 20.], egui::Label::new(format!("{:?}", layer)));
+                    for blend in LayerBlend::iter() {
+                        ui.selectable_value(&mut settings.blend, blend, format!("{:?}", blend));
+                    }
+                    ui.add(egui::DragValue::new(&mut settings.opacity).speed(0.01).clamp_range(0.0..=1.0));
+                });
+            }
+        });
+        egui::CollapsingHeader::new("Layout").show(ui, |ui| {
+            // Edit a copy so the layout only counts as changed on actual edits
+            let mut edited = world.resource::<TunnelgonLayout>().clone();
+            tunnelgon_layout_gui(ui, &mut edited);
+            if edited != *world.resource::<TunnelgonLayout>() {
+                *world.resource_mut::<TunnelgonLayout>() = edited;
+            }
+        });
+    }
+}
+
+fn tunnelgon_layout_gui(ui: &mut Ui, layout: &mut TunnelgonLayout) {
+    ui.horizontal(|ui| {
+        let mut lasers = layout.lasers.len();
+        ui.label("Lasers");
+        if ui.add(egui::DragValue::new(&mut lasers).clamp_range(1..=64)).changed() {
+            layout.set_laser_count(lasers);
         }
-        for hexagon_definition in &event.affected_hexagons {
-            let mesh = Mesh2dHandle(meshes.add(
-                RegularPolygon::new(HexagonDefinition::size(hexagon_definition).x / 2., 6)
-            ));
-            commands.spawn((
-                MaterialMesh2dBundle {
-                    mesh,
-                    material: materials.add(TunnelgonMaterial {
-                        prev: rt.render_target.clone(),
-                        params: TunnelgonParams::default(),
-                    }),
-                    transform: Transform::from_xyz(
-                        // Distribute shapes from -X_EXTENT to +X_EXTENT.
-                        HexagonDefinition::center(hexagon_definition).x - 1920. / 2.,
-                        HexagonDefinition::center(hexagon_definition).y - 1080. / 2.,
-                        0.0,
-                    ).with_rotation(Quat::from_rotation_z(PI / 6.)),
-                    ..default()
-                },
-                Tunnelgon {
-                    hexagon_definition: hexagon_definition.clone(),
-                    params: TunnelgonParams::default(),
-                },
-                Signals::new()
-                    .with_sender::<CancelAnim>(reactors.get_named::<CancelAnim>("cancel_tunnelgon_laser_anim")),
-                PropagatingRenderLayers { render_layers: RenderLayers::layer(3) }
-            ));
+        let mut rings = layout.rings.len();
+        ui.label("Rings");
+        if ui.add(egui::DragValue::new(&mut rings).clamp_range(1..=64)).changed() {
+            layout.set_ring_count(rings);
         }
-    }
+        if ui.button("Reset").clicked() {
+            *layout = TunnelgonLayout::default();
+        }
+    });
+    egui::Grid::new("tunnelgon_lasers").show(ui, |ui| {
+        for (i, laser) in layout.lasers.iter_mut().enumerate() {
+            ui.label(format!("Laser {}", i));
+            let mut degrees = laser.angle.to_degrees();
+            if ui.add(egui::DragValue::new(&mut degrees).speed(1.).suffix("°")).changed() {
+                laser.angle = degrees.to_radians();
+            }
+            ui.add(egui::DragValue::new(&mut laser.width).speed(0.001).clamp_range(0.001..=1.0));
+            let mut col_vals = laser.color.as_rgba_f32();
+            ui.color_edit_button_rgba_unmultiplied(&mut col_vals);
+            laser.color = Color::rgba_from_array(col_vals);
+            ui.end_row();
+        }
+    });
+    egui::Grid::new("tunnelgon_rings").show(ui, |ui| {
+        for (i, ring) in layout.rings.iter_mut().enumerate() {
+            ui.label(format!("Ring {}", i));
+            ui.add(egui::DragValue::new(&mut ring.thickness).speed(0.001).clamp_range(0.001..=0.5));
+            let mut col_vals = ring.color.as_rgba_f32();
+            ui.color_edit_button_rgba_unmultiplied(&mut col_vals);
+            ring.color = Color::rgba_from_array(col_vals);
+            ui.end_row();
+        }
+    });
 }
 
 // Animations (base)
@@ -133,8 +278,38 @@ pub fn spawn_tunnelgon_system(
 //    pulse
 
 
+/// Drive a slot of the anim layers with a parameter animation until it finishes or another
+/// animation claims the slot. Released slots leave the index to the layers below.
+fn spawn_slot_anim<A: Component + ParameterAnimation>(
+    commands: &mut Commands,
+    entity: Entity,
+    anim: A,
+    release: bool,
+    write: impl Fn(&mut TunnelgonAnimLayers, Option<f32>) -> bool + Clone + Send + Sync + 'static,
+) {
+    commands.spawn_task(move || async move {
+        let anim_entity = world().spawn_bundle(anim).await.id();
+        let anim_component = world().entity(anim_entity).component::<A>();
+        let layers = world().entity(entity).component::<TunnelgonAnimLayers>();
+
+        loop {
+            let (next_val, finished) = anim_component.get(|anim| { (anim.get_val(), anim.target_reached()) }).await.unwrap_or((0., true));
+            let value = if finished && release { None } else { Some(next_val) };
+            let write = write.clone();
+            let owned = layers.set(move |layers| write(layers, value)).await.unwrap_or(false);
+            if finished || !owned {
+                break;
+            }
+        }
+
+        world().entity(anim_entity).despawn().await;
+
+        Ok(())
+    });
+}
+
 // LASER
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub enum TunnelgonBaseAnim {
     Pulse,
     SetToVal,
@@ -143,114 +318,52 @@ pub enum TunnelgonBaseAnim {
 #[derive(Event, Clone)]
 pub struct LaserAnimationEvent {
     pub affected_hexagons: Vec<HexagonDefinition>,
+    /// Only animations on the same layer and index cancel each other
+    pub layer: AnimLayer,
     pub base_anim: TunnelgonBaseAnim,
     pub indices: Vec<usize>,
     pub values: Vec<f32>,
 }
 
-signal_ids! {
-    pub CancelAnim: CancelAnimData
-}
-
-#[derive(Clone, Default)]
-pub struct CancelAnimData {
-    pub indices: HashMap<HexagonDefinition, Vec<usize>>,
+/// Values of 0 release the slot, so layers below show through
+fn set_value(value: f32) -> Option<f32> {
+    if value == 0. { None } else { Some(value) }
 }
 
 pub fn laser_animation_system(
     mut commands: Commands,
-    query: Query<(Entity, &Tunnelgon, &Handle<TunnelgonMaterial>)>,
+    mut query: Query<(Entity, &Tunnelgon, &mut TunnelgonAnimLayers, &Handle<TunnelgonMaterial>)>,
     mut event_reader: EventReader<LaserAnimationEvent>,
-    mut reactors: ResMut<Reactors>,
-    mut materials: ResMut<Assets<TunnelgonMaterial>>,
+    materials: Res<Assets<TunnelgonMaterial>>,
 ) {
-    // Accumulate all events for this frame into single dataset
-    let anim_data: Vec<(HexagonDefinition, TunnelgonBaseAnim, usize, f32)> = event_reader.read().flat_map(
-        |ev| {
-            let zipped_i_v: Vec<(usize, f32)> = ev.indices.iter().enumerate().map(|(i, index)| {
-                (*index, *(ev.values.get(i).unwrap_or(&0f32)))
-            }).collect();
-
-            let mut out = vec![];
-            for hex in ev.affected_hexagons.clone() {
-                for (i, v) in zipped_i_v.clone() {
-                    out.push((hex, ev.base_anim, i, v));
-                }
-            }
-            out
-        }
-    ).collect();
-
-    // Populate hashmap of hexagons -> indices to cancel and send it
-    let signal = reactors.get_named::<CancelAnim>("cancel_tunnelgon_laser_anim");
-    let mut cancel_anim_data = CancelAnimData::default();
-    for (hex, _, i, _) in anim_data.iter() {
-        let indices_for_hex = cancel_anim_data.indices.entry(*hex).or_insert(vec![*i]);
-        indices_for_hex.push(*i);
-    }
-    signal.send(cancel_anim_data);
-
-    for (hex, base_anim, laser_index, laser_value) in anim_data.iter().cloned() {
-        for (entity, tg, tgm) in query.iter() {
-            if hex != tg.hexagon_definition {
+    for ev in event_reader.read() {
+        for (entity, tg, mut layers, tgm) in query.iter_mut() {
+            if !ev.affected_hexagons.contains(&tg.hexagon_definition) {
                 continue;
             }
-            let tgm_material = materials.get_mut(tgm).unwrap();
-            if laser_index >= 8 { warn!("Got index out of range: {}", laser_index) }
-            let laser_index = laser_index % 8;
-            let entity_cloned = entity.clone();
-
-            match base_anim {
-                TunnelgonBaseAnim::SetToVal => {
-                    tgm_material.params.laser[laser_index] = laser_value;
+            let laser_count = materials.get(tgm).map(|mat| mat.lasers.len()).unwrap_or(0);
+
+            for (i, laser_index) in ev.indices.iter().copied().enumerate() {
+                if laser_index >= laser_count {
+                    warn!("Got laser index out of range: {}", laser_index);
+                    continue;
                 }
-                TunnelgonBaseAnim::Pulse => {
-                    commands.spawn_task(move || async move {
-                        let signal = world().named_signal::<CancelAnim>("cancel_tunnelgon_laser_anim");
-                        let _ = signal.poll().await;
-
-                        let tunnelgon_entity = world().entity(entity_cloned);
-                        let materials = world().resource::<Assets<TunnelgonMaterial>>();
-
-                        // Spawn PT1 anim
-                        let pt1_entity = world().spawn_bundle(
-                            Pt1Anim {
-                                val: laser_value.clone(),
-                                target: 0.,
-                                time_constant: 0.03,
-                            }
-                        ).await.id();
-                        let pt1_component = world().entity(pt1_entity).component::<Pt1Anim>();
-
-                        let mat_handle = tunnelgon_entity.component::<Handle<TunnelgonMaterial>>()
-                            .get(|mat_handle| mat_handle.clone()).await.unwrap();
-
-                        // While the PT1 is still going, update the material
-                        loop {
-                            // Check if animation is meant to cancel
-                            if let Some(cancel_data) = signal.try_read() {
-                                if let Some(indices) = cancel_data.indices.get(&hex) {
-                                    if indices.contains(&laser_index) {
-                                        break;
-                                    }
-                                }
-                            }
-
-                            let (next_val, finished) = pt1_component.get(|pt1anim| { (pt1anim.get_val(), pt1anim.target_reached()) }).await.unwrap_or((0., true));
-                            let mat_handle_cloned = mat_handle.clone();
-                            let _ = materials.set(move |mut materials| {
-                                let mut mat = materials.get_mut(mat_handle_cloned).unwrap();
-                                mat.params.laser[laser_index] = next_val
-                            }).await.unwrap();
-                            if finished {
-                                break;
-                            }
-                        }
-
-                        world().entity(pt1_entity).despawn().await;
-
-                        Ok(())
-                    });
+                let laser_value = *ev.values.get(i).unwrap_or(&0.);
+                let layer = ev.layer;
+                let token = layers.claim_laser(layer, laser_index);
+
+                match ev.base_anim {
+                    TunnelgonBaseAnim::SetToVal => {
+                        layers.write_laser(layer, laser_index, token, set_value(laser_value));
+                    }
+                    TunnelgonBaseAnim::Pulse => {
+                        layers.write_laser(layer, laser_index, token, Some(laser_value));
+                        spawn_slot_anim(&mut commands, entity, Pt1Anim {
+                            val: laser_value,
+                            target: 0.,
+                            time_constant: 0.03,
+                        }, true, move |layers, value| layers.write_laser(layer, laser_index, token, value));
+                    }
                 }
             }
         }
@@ -258,13 +371,13 @@ pub fn laser_animation_system(
 }
 
 // RINGS
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub enum RingBasePosAnim {
     SetToPosition,
     SlideLinear,
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub enum RingBaseValAnim {
     SetToVal,
     Pulse,
@@ -273,6 +386,8 @@ pub enum RingBaseValAnim {
 #[derive(Event, Clone)]
 pub struct RingAnimationEvent {
     pub affected_hexagons: Vec<HexagonDefinition>,
+    /// Only animations on the same layer and index cancel each other
+    pub layer: AnimLayer,
     pub base_pos_anim: RingBasePosAnim,
     pub base_val_anim: RingBaseValAnim,
     pub indices: Vec<usize>,
@@ -283,181 +398,83 @@ pub struct RingAnimationEvent {
 
 pub fn ring_animation_system(
     mut commands: Commands,
-    query: Query<(Entity, &Tunnelgon, &Handle<TunnelgonMaterial>)>,
+    mut query: Query<(Entity, &Tunnelgon, &mut TunnelgonAnimLayers, &Handle<TunnelgonMaterial>)>,
     mut event_reader: EventReader<RingAnimationEvent>,
-    mut reactors: ResMut<Reactors>,
-    mut materials: ResMut<Assets<TunnelgonMaterial>>,
+    materials: Res<Assets<TunnelgonMaterial>>,
 ) {
-    // Accumulate all events for this frame into single dataset
-    let anim_data: Vec<(HexagonDefinition, RingBasePosAnim, RingBaseValAnim, usize, f32, f32, f32)> = event_reader.read().flat_map(
-        |ev| {
-            let zipped_i_v: Vec<(usize, f32, f32, f32)> = ev.indices.iter().enumerate().map(|(i, index)| {
-                (*index, *(ev.values.get(i).unwrap_or(&0f32)), *(ev.positions_from.get(i).unwrap_or(&0f32)), *(ev.positions_to.get(i).unwrap_or(&0f32)))
-            }).collect();
-
-            let mut out = vec![];
-            for hex in ev.affected_hexagons.clone() {
-                for (i, v, from, to) in zipped_i_v.clone() {
-                    out.push((hex, ev.base_pos_anim, ev.base_val_anim, i, v, from, to));
-                }
-            }
-            out
-        }
-    ).collect();
-
-    // Populate hashmap of hexagons -> indices to cancel and send it
-    let signal = reactors.get_named::<CancelAnim>("cancel_tunnelgon_ring_anim");
-    let mut cancel_anim_data = CancelAnimData::default();
-    for (hex, _, _, i, _, _, _) in anim_data.iter() {
-        let indices_for_hex = cancel_anim_data.indices.entry(*hex).or_insert(vec![*i]);
-        indices_for_hex.push(*i);
-    }
-    signal.send(cancel_anim_data);
-
-    for (hex, base_pos_anim, base_val_anim, ring_index, ring_value, ring_pos_from, ring_pos_to) in anim_data.iter().cloned() {
-        for (entity, tg, tgm) in query.iter() {
-            if hex != tg.hexagon_definition {
+    for ev in event_reader.read() {
+        for (entity, tg, mut layers, tgm) in query.iter_mut() {
+            if !ev.affected_hexagons.contains(&tg.hexagon_definition) {
                 continue;
             }
-            let tgm_material = materials.get_mut(tgm).unwrap();
-
-            if ring_index.clone() >= 8 { warn!("Got index out of range: {}", ring_index) }
-            let ring_index = ring_index.clone() % 8;
-            let entity_cloned = entity.clone();
-
-            match base_pos_anim {
-                RingBasePosAnim::SetToPosition => { tgm_material.params.rings_pos[ring_index] = ring_pos_to; }
-                RingBasePosAnim::SlideLinear => {
-                    commands.spawn_task(move || async move {
-                        let signal = world().named_signal::<CancelAnim>("cancel_tunnelgon_ring_anim");
-                        let _ = signal.poll().await;
-
-                        let tunnelgon_entity = world().entity(entity_cloned);
-                        let materials = world().resource::<Assets<TunnelgonMaterial>>();
-
-                        // Spawn Linear anim
-                        let anim_entity = world().spawn_bundle(
-                            LinearAnim {
-                                val: ring_pos_from,
-                                target: ring_pos_to,
-                                speed: 1.,
-                            }
-                        ).await.id();
-                        let anim_component = world().entity(anim_entity).component::<LinearAnim>();
-
-                        let mat_handle = tunnelgon_entity.component::<Handle<TunnelgonMaterial>>()
-                            .get(|mat_handle| mat_handle.clone()).await.unwrap();
-
-                        // While the Anim is still going, update the material
-                        loop {
-                            // Check if animation is meant to cancel
-                            if let Some(cancel_data) = signal.try_read() {
-                                if let Some(indices) = cancel_data.indices.get(&hex) {
-                                    if indices.contains(&ring_index) {
-                                        break;
-                                    }
-                                }
-                            }
-
-                            let (next_val, finished) = anim_component.get(|linear_anim| { (linear_anim.get_val(), linear_anim.target_reached()) }).await.unwrap_or((0., true));
-                            let mat_handle_cloned = mat_handle.clone();
-                            let _ = materials.set(move |mut materials| {
-                                let mut mat = materials.get_mut(mat_handle_cloned).unwrap();
-                                mat.params.rings_pos[ring_index] = next_val
-                            }).await.unwrap();
-                            if finished {
-                                break;
-                            }
-                        }
-
-                        world().entity(anim_entity).despawn().await;
-
-                        Ok(())
-                    });
+            let ring_count = materials.get(tgm).map(|mat| mat.rings.len()).unwrap_or(0);
+
+            for (i, ring_index) in ev.indices.iter().copied().enumerate() {
+                if ring_index >= ring_count {
+                    warn!("Got ring index out of range: {}", ring_index);
+                    continue;
+                }
+                let ring_value = *ev.values.get(i).unwrap_or(&0.);
+                let ring_pos_from = *ev.positions_from.get(i).unwrap_or(&0.);
+                let ring_pos_to = *ev.positions_to.get(i).unwrap_or(&0.);
+                let layer = ev.layer;
+
+                let token = layers.claim_ring(layer, ring_index, RingParam::Pos);
+                match ev.base_pos_anim {
+                    RingBasePosAnim::SetToPosition => {
+                        layers.write_ring(layer, ring_index, RingParam::Pos, token, Some(ring_pos_to));
+                    }
+                    RingBasePosAnim::SlideLinear => {
+                        layers.write_ring(layer, ring_index, RingParam::Pos, token, Some(ring_pos_from));
+                        spawn_slot_anim(&mut commands, entity, LinearAnim {
+                            val: ring_pos_from,
+                            target: ring_pos_to,
+                            speed: 1.,
+                        }, false, move |layers, value| layers.write_ring(layer, ring_index, RingParam::Pos, token, value));
+                    }
                 }
-            }
 
-            match base_val_anim {
-                RingBaseValAnim::SetToVal => { tgm_material.params.rings_amp[ring_index] = ring_value; }
-                RingBaseValAnim::Pulse => {
-                    commands.spawn_task(move || async move {
-                        let signal = world().named_signal::<CancelAnim>("cancel_tunnelgon_ring_anim");
-                        let _ = signal.poll().await;
-
-                        let tunnelgon_entity = world().entity(entity_cloned);
-                        let materials = world().resource::<Assets<TunnelgonMaterial>>();
-
-                        // Spawn Linear anim
-                        let anim_entity = world().spawn_bundle(
-                            Pt1Anim {
-                                val: ring_value,
-                                target: 0.,
-                                ..default()
-                            }
-                        ).await.id();
-                        let anim_component = world().entity(anim_entity).component::<Pt1Anim>();
-
-                        let mat_handle = tunnelgon_entity.component::<Handle<TunnelgonMaterial>>()
-                            .get(|mat_handle| mat_handle.clone()).await.unwrap();
-
-                        // While the Anim is still going, update the material
-                        loop {
-                            // Check if animation is meant to cancel
-                            if let Some(cancel_data) = signal.try_read() {
-                                if let Some(indices) = cancel_data.indices.get(&hex) {
-                                    if indices.contains(&ring_index) {
-                                        break;
-                                    }
-                                }
-                            }
-
-                            let (next_val, finished) = anim_component.get(|pt1anim| { (pt1anim.get_val(), pt1anim.target_reached()) }).await.unwrap_or((0., true));
-                            let mat_handle_cloned = mat_handle.clone();
-                            let _ = materials.set(move |mut materials| {
-                                let mut mat = materials.get_mut(mat_handle_cloned).unwrap();
-                                mat.params.rings_amp[ring_index] = next_val
-                            }).await.unwrap();
-                            if finished {
-                                break;
-                            }
-                        }
-
-                        world().entity(anim_entity).despawn().await;
-
-                        Ok(())
-                    });
+                let token = layers.claim_ring(layer, ring_index, RingParam::Amp);
+                match ev.base_val_anim {
+                    RingBaseValAnim::SetToVal => {
+                        layers.write_ring(layer, ring_index, RingParam::Amp, token, set_value(ring_value));
+                    }
+                    RingBaseValAnim::Pulse => {
+                        layers.write_ring(layer, ring_index, RingParam::Amp, token, Some(ring_value));
+                        spawn_slot_anim(&mut commands, entity, Pt1Anim {
+                            val: ring_value,
+                            target: 0.,
+                            ..default()
+                        }, true, move |layers, value| layers.write_ring(layer, ring_index, RingParam::Amp, token, value));
+                    }
                 }
             }
         }
     }
 }
 
-#[derive(Resource, Default)]
-pub struct TunnelgonAccum {
-    pub(crate) enabled: bool,
-}
-
-pub fn tunnelgon_accum(
+/// Resize and restyle the lasers and rings of spawned tunnelgons when the layout changes, running
+/// animations keep their values
+pub fn apply_tunnelgon_layout(
+    layout: Res<TunnelgonLayout>,
+    query: Query<&Handle<TunnelgonMaterial>, With<Tunnelgon>>,
     mut materials: ResMut<Assets<TunnelgonMaterial>>,
-    mut beat_reader: EventReader<BeatEvent>,
-    mut query: Query<&Handle<TunnelgonMaterial>>,
-    time: Res<Time<Real>>,
-    settings: Res<TunnelgonAccum>,
 ) {
-    for _ in beat_reader.read() {
-        if settings.enabled {
-            for mat_handle in query.iter() {
-                if let Some(mut mat) = materials.get_mut(mat_handle) {
-                    mat.params.tun_accum_target = mat.params.tun_accum_target + 1.;
-                }
-            }
-        }
-    }
-
+    if !layout.is_changed() { return; }
     for mat_handle in query.iter() {
-        if let Some(mut mat) = materials.get_mut(mat_handle) {
-            mat.params.tun_accum = mat.params.tun_accum + (mat.params.tun_accum_target - mat.params.tun_accum) * (time.delta_seconds() / (time.delta_seconds() + 0.1));
-            println!("{} {}", mat.params.tun_accum, mat.params.tun_accum_target);
+        let Some(mat) = materials.get(mat_handle) else { continue; };
+        let lasers: Vec<TunnelgonLaser> = layout.lasers.iter().enumerate().map(|(i, laser)| TunnelgonLaser {
+            value: mat.lasers.get(i).map(|old| old.value).unwrap_or(0.),
+            ..*laser
+        }).collect();
+        let rings: Vec<TunnelgonRing> = layout.rings.iter().enumerate().map(|(i, ring)| {
+            let (pos, amp) = mat.rings.get(i).map(|old| (old.pos, old.amp)).unwrap_or((0., 0.));
+            TunnelgonRing { pos, amp, ..*ring }
+        }).collect();
+        if lasers == mat.lasers && rings == mat.rings { continue; }
+        if let Some(mat) = materials.get_mut(mat_handle) {
+            mat.lasers = lasers;
+            mat.rings = rings;
         }
     }
-}
\ No newline at end of file
+}
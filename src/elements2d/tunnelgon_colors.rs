@@ -0,0 +1,100 @@
+//! Tunnelgon colours following the animation colours and the active palette: primary tints the
+//! tunnel, secondary the lasers and accent the rings. Hexagons can override single colours and
+//! the satellites can be spread around the hue circle.
+
+use std::collections::HashMap;
+use bevy::prelude::{Assets, Color, Handle, Query, Res, ResMut, Resource};
+use crate::anims::AnimColors;
+use crate::elements2d::tunnelgon::{Tunnelgon, TunnelgonMaterial};
+use crate::hexagon::HexagonDefinition;
+use crate::palette::ActivePalette;
+
+/// Order of the hue spread over the satellite hexagons
+pub const SATELLITES: [HexagonDefinition; 6] = [
+    HexagonDefinition::A1,
+    HexagonDefinition::A2,
+    HexagonDefinition::A3,
+    HexagonDefinition::B1,
+    HexagonDefinition::B2,
+    HexagonDefinition::B3,
+];
+
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct TunnelgonColorOverride {
+    pub primary: Option<Color>,
+    pub secondary: Option<Color>,
+    pub accent: Option<Color>,
+}
+
+#[derive(Resource)]
+pub struct TunnelgonColors {
+    /// Off leaves the shader with its built-in palettes
+    pub enabled: bool,
+    /// How far the colours replace the built-in palettes, 0 to 1
+    pub amount: f32,
+    /// Degrees of hue added per satellite, Main keeps the source hue
+    pub hue_spread: f32,
+    pub overrides: HashMap<HexagonDefinition, TunnelgonColorOverride>,
+}
+
+impl Default for TunnelgonColors {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            amount: 1.,
+            hue_spread: 0.,
+            overrides: HashMap::new(),
+        }
+    }
+}
+
+fn rotate_hue(color: Color, degrees: f32) -> Color {
+    if degrees == 0. { return color; }
+    color.with_h((color.h() + degrees).rem_euclid(360.)).as_rgba()
+}
+
+impl TunnelgonColors {
+    fn hue_offset(&self, hexagon: &HexagonDefinition) -> f32 {
+        SATELLITES.iter().position(|satellite| satellite == hexagon)
+            .map(|i| self.hue_spread * i as f32)
+            .unwrap_or(0.)
+    }
+
+    /// Primary, secondary and accent of a hexagon, alpha is the amount the shader mixes in
+    pub fn colors(&self, hexagon: &HexagonDefinition, primary: Color, secondary: Color, accent: Color) -> [Color; 3] {
+        if !self.enabled {
+            return [Color::rgba(0., 0., 0., 0.); 3];
+        }
+        let offset = self.hue_offset(hexagon);
+        let overrides = self.overrides.get(hexagon).copied().unwrap_or_default();
+        [
+            overrides.primary.unwrap_or_else(|| rotate_hue(primary, offset)),
+            overrides.secondary.unwrap_or_else(|| rotate_hue(secondary, offset)),
+            overrides.accent.unwrap_or_else(|| rotate_hue(accent, offset)),
+        ].map(|color| color.with_a(self.amount))
+    }
+}
+
+/// Write the animation colours into the tunnelgon materials, the accent is the palette colour
+/// after the secondary slot
+pub fn sync_tunnelgon_colors(
+    mut materials: ResMut<Assets<TunnelgonMaterial>>,
+    query: Query<(&Tunnelgon, &Handle<TunnelgonMaterial>)>,
+    settings: Res<TunnelgonColors>,
+    colors: Res<AnimColors>,
+    palette: Res<ActivePalette>,
+) {
+    let accent = palette.color(palette.secondary_slot + 1);
+    for (tg, mat_handle) in query.iter() {
+        let [primary, secondary, accent] = settings.colors(&tg.hexagon_definition, colors.primary, colors.secondary, accent);
+        let Some(mat) = materials.get(mat_handle) else { continue; };
+        if mat.params.primary == primary && mat.params.secondary == secondary && mat.params.accent == accent {
+            continue;
+        }
+        if let Some(mat) = materials.get_mut(mat_handle) {
+            mat.params.primary = primary;
+            mat.params.secondary = secondary;
+            mat.params.accent = accent;
+        }
+    }
+}
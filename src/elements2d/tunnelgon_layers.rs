@@ -0,0 +1,268 @@
+//! Priority layers of the tunnelgon laser and ring animations. An animation claims an index on
+//! its layer and only a newer animation on the same layer and index cancels it, so background
+//! patterns and accent hits can run at the same time. The layers are combined into the material
+//! from the lowest to the highest priority.
+
+use bevy::prelude::{Assets, Component, Handle, Query, Res, ResMut, Resource};
+use serde::{Deserialize, Serialize};
+use strum_macros::EnumIter;
+use crate::elements2d::tunnelgon::TunnelgonMaterial;
+
+pub const ANIM_LAYER_COUNT: usize = 3;
+
+/// Lowest priority first
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default, Serialize, Deserialize, EnumIter)]
+pub enum AnimLayer {
+    Background,
+    #[default]
+    Pattern,
+    Accent,
+}
+
+impl AnimLayer {
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "background" => { Some(AnimLayer::Background) }
+            "pattern" => { Some(AnimLayer::Pattern) }
+            "accent" => { Some(AnimLayer::Accent) }
+            _ => { None }
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, EnumIter)]
+pub enum LayerBlend {
+    /// Mixes over the layers below by its opacity where it owns an index
+    #[default]
+    Blend,
+    /// Keeps the brighter of the layer and the layers below
+    Max,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct LayerSettings {
+    pub blend: LayerBlend,
+    pub opacity: f32,
+}
+
+impl Default for LayerSettings {
+    fn default() -> Self {
+        Self { blend: LayerBlend::Blend, opacity: 1. }
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct TunnelgonLayerSettings {
+    pub layers: [LayerSettings; ANIM_LAYER_COUNT],
+}
+
+impl Default for TunnelgonLayerSettings {
+    fn default() -> Self {
+        Self {
+            layers: [
+                LayerSettings::default(),
+                LayerSettings::default(),
+                LayerSettings { blend: LayerBlend::Max, opacity: 1. },
+            ],
+        }
+    }
+}
+
+impl TunnelgonLayerSettings {
+    fn combine(&self, layer: usize, below: f32, value: f32) -> f32 {
+        let settings = self.layers[layer];
+        match settings.blend {
+            LayerBlend::Blend => { below + (value - below) * settings.opacity }
+            LayerBlend::Max => { below.max(value * settings.opacity) }
+        }
+    }
+}
+
+/// Value of one index on one layer. `owner` counts the animations that claimed the slot, an
+/// animation stops once it isn't the owner anymore.
+#[derive(Copy, Clone, Default, Debug)]
+pub struct Slot {
+    pub value: Option<f32>,
+    owner: u64,
+}
+
+impl Slot {
+    fn claim(&mut self) -> u64 {
+        self.owner += 1;
+        self.owner
+    }
+
+    /// Write the value if `token` still owns the slot
+    fn write(&mut self, token: u64, value: Option<f32>) -> bool {
+        if self.owner != token { return false; }
+        self.value = value;
+        true
+    }
+}
+
+#[derive(Copy, Clone, Default, Debug)]
+pub struct RingSlot {
+    pub pos: Slot,
+    pub amp: Slot,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum RingParam {
+    Pos,
+    Amp,
+}
+
+/// Laser and ring values of a tunnelgon per layer, grown on demand
+#[derive(Component, Default, Debug)]
+pub struct TunnelgonAnimLayers {
+    lasers: [Vec<Slot>; ANIM_LAYER_COUNT],
+    rings: [Vec<RingSlot>; ANIM_LAYER_COUNT],
+}
+
+impl TunnelgonAnimLayers {
+    fn laser_slot(&mut self, layer: AnimLayer, index: usize) -> &mut Slot {
+        let slots = &mut self.lasers[layer.index()];
+        if slots.len() <= index {
+            slots.resize(index + 1, Slot::default());
+        }
+        &mut slots[index]
+    }
+
+    fn ring_slot(&mut self, layer: AnimLayer, index: usize, param: RingParam) -> &mut Slot {
+        let slots = &mut self.rings[layer.index()];
+        if slots.len() <= index {
+            slots.resize(index + 1, RingSlot::default());
+        }
+        match param {
+            RingParam::Pos => { &mut slots[index].pos }
+            RingParam::Amp => { &mut slots[index].amp }
+        }
+    }
+
+    /// Take over a laser on a layer, cancelling the animation running on it
+    pub fn claim_laser(&mut self, layer: AnimLayer, index: usize) -> u64 {
+        self.laser_slot(layer, index).claim()
+    }
+
+    /// False once another animation claimed the laser
+    pub fn write_laser(&mut self, layer: AnimLayer, index: usize, token: u64, value: Option<f32>) -> bool {
+        self.laser_slot(layer, index).write(token, value)
+    }
+
+    pub fn claim_ring(&mut self, layer: AnimLayer, index: usize, param: RingParam) -> u64 {
+        self.ring_slot(layer, index, param).claim()
+    }
+
+    pub fn write_ring(&mut self, layer: AnimLayer, index: usize, param: RingParam, token: u64, value: Option<f32>) -> bool {
+        self.ring_slot(layer, index, param).write(token, value)
+    }
+
+    /// Combined laser value, 0 where no layer owns the laser
+    fn laser(&self, settings: &TunnelgonLayerSettings, index: usize) -> f32 {
+        let mut out = 0.;
+        for (layer, slots) in self.lasers.iter().enumerate() {
+            if let Some(value) = slots.get(index).and_then(|slot| slot.value) {
+                out = settings.combine(layer, out, value);
+            }
+        }
+        out
+    }
+
+    /// Combined ring amplitude and the position of the highest layer with an active amplitude.
+    /// Positions stay set after a slide, so layers whose ring is released don't take part.
+    fn ring(&self, settings: &TunnelgonLayerSettings, index: usize) -> (Option<f32>, f32) {
+        let mut pos = None;
+        let mut amp = 0.;
+        for (layer, slots) in self.rings.iter().enumerate() {
+            let Some(slot) = slots.get(index) else { continue; };
+            let Some(value) = slot.amp.value else { continue; };
+            amp = settings.combine(layer, amp, value);
+            if slot.pos.value.is_some() {
+                pos = slot.pos.value;
+            }
+        }
+        (pos, amp)
+    }
+}
+
+/// Write the combined layers into the tunnelgon materials
+pub fn composite_tunnelgon_layers(
+    query: Query<(&TunnelgonAnimLayers, &Handle<TunnelgonMaterial>)>,
+    mut materials: ResMut<Assets<TunnelgonMaterial>>,
+    settings: Res<TunnelgonLayerSettings>,
+) {
+    for (layers, mat_handle) in query.iter() {
+        let Some(mat) = materials.get(mat_handle) else { continue; };
+        let lasers: Vec<f32> = (0..mat.lasers.len()).map(|i| layers.laser(&settings, i)).collect();
+        let rings: Vec<(Option<f32>, f32)> = (0..mat.rings.len()).map(|i| layers.ring(&settings, i)).collect();
+        let changed = mat.lasers.iter().zip(lasers.iter()).any(|(laser, value)| laser.value != *value)
+            || mat.rings.iter().zip(rings.iter()).any(|(ring, (pos, amp))| ring.amp != *amp || pos.map(|pos| pos != ring.pos).unwrap_or(false));
+        if !changed { continue; }
+
+        let Some(mat) = materials.get_mut(mat_handle) else { continue; };
+        for (laser, value) in mat.lasers.iter_mut().zip(lasers) {
+            laser.value = value;
+        }
+        for (ring, (pos, amp)) in mat.rings.iter_mut().zip(rings) {
+            ring.amp = amp;
+            if let Some(pos) = pos {
+                ring.pos = pos;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn newer_claim_cancels_older_animation() {
+        let mut layers = TunnelgonAnimLayers::default();
+        let first = layers.claim_laser(AnimLayer::Pattern, 2);
+        let second = layers.claim_laser(AnimLayer::Pattern, 2);
+        assert!(!layers.write_laser(AnimLayer::Pattern, 2, first, Some(1.)));
+        assert!(layers.write_laser(AnimLayer::Pattern, 2, second, Some(0.5)));
+        // Other layers keep their own owner
+        let accent = layers.claim_laser(AnimLayer::Accent, 2);
+        assert!(layers.write_laser(AnimLayer::Pattern, 2, second, Some(0.5)));
+        assert!(layers.write_laser(AnimLayer::Accent, 2, accent, Some(1.)));
+    }
+
+    #[test]
+    fn lasers_combine_from_background_to_accent() {
+        let settings = TunnelgonLayerSettings::default();
+        let mut layers = TunnelgonAnimLayers::default();
+        let token = layers.claim_laser(AnimLayer::Background, 0);
+        layers.write_laser(AnimLayer::Background, 0, token, Some(0.8));
+        let token = layers.claim_laser(AnimLayer::Accent, 0);
+        layers.write_laser(AnimLayer::Accent, 0, token, Some(0.3));
+        // Accent keeps the max by default
+        assert_eq!(layers.laser(&settings, 0), 0.8);
+        assert_eq!(layers.laser(&settings, 1), 0.);
+    }
+
+    #[test]
+    fn released_ring_doesnt_keep_its_position() {
+        let settings = TunnelgonLayerSettings::default();
+        let mut layers = TunnelgonAnimLayers::default();
+
+        let pos = layers.claim_ring(AnimLayer::Accent, 0, RingParam::Pos);
+        let amp = layers.claim_ring(AnimLayer::Accent, 0, RingParam::Amp);
+        layers.write_ring(AnimLayer::Accent, 0, RingParam::Pos, pos, Some(0.9));
+        layers.write_ring(AnimLayer::Accent, 0, RingParam::Amp, amp, Some(1.));
+        assert_eq!(layers.ring(&settings, 0), (Some(0.9), 1.));
+
+        // The accent ring ends, a slide on the pattern layer shows at its own position
+        layers.write_ring(AnimLayer::Accent, 0, RingParam::Amp, amp, None);
+        let pos = layers.claim_ring(AnimLayer::Pattern, 0, RingParam::Pos);
+        let amp = layers.claim_ring(AnimLayer::Pattern, 0, RingParam::Amp);
+        layers.write_ring(AnimLayer::Pattern, 0, RingParam::Pos, pos, Some(0.2));
+        layers.write_ring(AnimLayer::Pattern, 0, RingParam::Amp, amp, Some(0.5));
+        assert_eq!(layers.ring(&settings, 0), (Some(0.2), 0.5));
+    }
+}
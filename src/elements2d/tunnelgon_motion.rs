@@ -0,0 +1,114 @@
+//! Tunnel travel and spiral automation of the tunnelgons, set per hexagon. Bars and phrases are
+//! counted on the beat clock, so reversals and flips stay on the grid.
+
+use std::collections::HashMap;
+use std::f32::consts::TAU;
+use bevy::prelude::{Assets, EventReader, Handle, Query, Real, Res, ResMut, Resource, Time};
+use strum_macros::EnumIter;
+use crate::beat::beat_clock::BeatClock;
+use crate::beat::BeatEvent;
+use crate::elements2d::tunnelgon::{Tunnelgon, TunnelgonMaterial};
+use crate::hexagon::HexagonDefinition;
+use crate::beat::BEATS_PER_BAR;
+
+pub const BARS_PER_PHRASE: u64 = 4;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, EnumIter)]
+pub enum TunnelTravel {
+    Off,
+    /// Moves smoothly with `speed` segments per beat
+    #[default]
+    Constant,
+    /// Jumps `speed` segments on every beat
+    BeatStep,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct TunnelgonMotion {
+    pub travel: TunnelTravel,
+    /// Tunnel segments per beat, negative travels outwards
+    pub speed: f32,
+    /// Travel backwards on every other bar
+    pub reverse_on_bar: bool,
+    pub spiral_freq: f32,
+    pub spiral_skew: f32,
+    /// Skew swings by this much around `spiral_skew`, 0 disables the sweep
+    pub skew_sweep: f32,
+    pub skew_sweep_beats: f32,
+    /// Turn the spiral the other way on every other phrase
+    pub flip_on_phrase: bool,
+}
+
+impl Default for TunnelgonMotion {
+    fn default() -> Self {
+        Self {
+            travel: TunnelTravel::Constant,
+            speed: 1.,
+            reverse_on_bar: false,
+            spiral_freq: 12.,
+            spiral_skew: -3.,
+            skew_sweep: 0.,
+            skew_sweep_beats: 8.,
+            flip_on_phrase: false,
+        }
+    }
+}
+
+impl TunnelgonMotion {
+    /// Travel direction at a position in beats
+    fn direction(&self, beats: f64) -> f32 {
+        let bar = beats.floor() as u64 / BEATS_PER_BAR;
+        if self.reverse_on_bar && bar % 2 == 1 { -1. } else { 1. }
+    }
+
+    fn spiral_dir(&self, beats: f64) -> f32 {
+        let phrase = beats.floor() as u64 / (BEATS_PER_BAR * BARS_PER_PHRASE);
+        if self.flip_on_phrase && phrase % 2 == 1 { -1. } else { 1. }
+    }
+
+    fn spiral_skew(&self, beats: f64) -> f32 {
+        if self.skew_sweep == 0. || self.skew_sweep_beats <= 0. { return self.spiral_skew; }
+        let phase = (beats / self.skew_sweep_beats as f64).fract() as f32;
+        self.spiral_skew + self.skew_sweep * (phase * TAU).sin()
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct TunnelgonMotions {
+    /// Hexagons without an entry use the default motion
+    pub motions: HashMap<HexagonDefinition, TunnelgonMotion>,
+}
+
+impl TunnelgonMotions {
+    pub fn get(&self, hexagon: &HexagonDefinition) -> TunnelgonMotion {
+        self.motions.get(hexagon).copied().unwrap_or_default()
+    }
+}
+
+pub fn tunnelgon_motion(
+    mut materials: ResMut<Assets<TunnelgonMaterial>>,
+    mut beat_reader: EventReader<BeatEvent>,
+    query: Query<(&Tunnelgon, &Handle<TunnelgonMaterial>)>,
+    motions: Res<TunnelgonMotions>,
+    clock: Res<BeatClock>,
+    time: Res<Time<Real>>,
+) {
+    let beats = beat_reader.read().count() as f32;
+    let dt = time.delta_seconds();
+    let beat_delta = dt / clock.beat_duration();
+
+    for (tg, mat_handle) in query.iter() {
+        let Some(mat) = materials.get_mut(mat_handle) else { continue; };
+        let motion = motions.get(&tg.hexagon_definition);
+        let step = motion.speed * motion.direction(clock.position);
+        match motion.travel {
+            TunnelTravel::Off => {}
+            TunnelTravel::Constant => { mat.params.tun_accum_target += step * beat_delta; }
+            TunnelTravel::BeatStep => { mat.params.tun_accum_target += step * beats; }
+        }
+        mat.params.tun_accum = mat.params.tun_accum + (mat.params.tun_accum_target - mat.params.tun_accum) * (dt / (dt + 0.1));
+        mat.params.spiral_freq = motion.spiral_freq;
+        mat.params.spiral_skew = motion.spiral_skew(clock.position);
+        mat.params.spiral_dir = motion.spiral_dir(clock.position);
+    }
+}
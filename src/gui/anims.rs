@@ -1,58 +1,75 @@
+use std::collections::HashMap;
 use bevy::ecs::event::ManualEventReader;
 use bevy::ecs::system::SystemParam;
-use bevy::prelude::{EventReader, Events, EventWriter, Local, ResMut};
+use bevy::prelude::{EventReader, Events, EventWriter, Local, Res, ResMut};
 use bevy::utils::default;
 use bevy_egui::{egui, EguiContexts};
 use bevy_egui::egui::{Color32, RichText, Ui, WidgetText};
+use strum::IntoEnumIterator;
 use crate::anims::meta_phys::{PhysAnimMode, PhysMetaAnim};
-use crate::anims::meta_tunnelgon::{TunnelgonLaserCycleMetaAnim, TunnelgonLaserFigureEightMetaAnim, TunnelgonLaserRoundTheClockMetaAnim, TunnelgonLaserSweepMetaAnim, TunnelgonRingsBTFMetaAnim, TunnelgonRingsFTBMetaAnim, TunnelgonRingsTrainMetaAnim};
+use crate::anims::meta_script::{HOOKS, MetaScripts, SCRIPT_DIR, SCRIPT_FUNCTIONS};
+use crate::anims::meta_sequence::{ALL_HEXAGONS, MetaSequences, SEQUENCE_DIR};
+use crate::anims::noise_field::{NOISE_FIELD_WAVE, NoiseField, NoiseFieldSettings, NoiseType};
+use crate::anims::wave_transition::{TransitionStyle, TransitionUnit, WaveTransition};
+use crate::anims::tube_sequencer::{SequencerPattern, TubeSequencer};
 use crate::anims::tubes::TubesWaveAnims;
 use crate::beat::BeatEvent;
-use crate::elements2d::pedrogon::SetPedrogonEvent;
-use crate::elements2d::swirlagon::SetSwirlagonEvent;
-use crate::elements2d::tunnelgon::SetTunnelgonEvent;
+use crate::elements2d::element::{assignment_events, ElementAssignment, HexagonElements, SetHexagonElementEvent};
+use crate::elements2d::media::MEDIA;
+use crate::elements2d::swirlagon::SWIRLAGON;
+use crate::elements2d::tunnelgon::TUNNELGON;
+use crate::elements2d::tunnelgon_motion::{TunnelgonMotion, TunnelgonMotions, TunnelTravel};
 use crate::hexagon::HexagonDefinition;
 use crate::hexagon::HexagonDefinition::{A1, A2, A3, B1, B2, B3};
 use crate::physics_hexagon::effectors::{EyesMode, PhysHexSettings};
+use crate::physics_hexagon::lights::led_layers::{LedBlendMode, LedLayer, LedLayerSettings};
+use crate::physics_hexagon::lights::led_symmetry::{LedSymmetry, SymmetryMode};
 
 
 #[derive(SystemParam)]
 pub struct TgMetaAnim<'w> {
-    laser_cycle: ResMut<'w, TunnelgonLaserCycleMetaAnim>,
-    laser_figure_eight: ResMut<'w, TunnelgonLaserFigureEightMetaAnim>,
-    laser_round_the_clock: ResMut<'w, TunnelgonLaserRoundTheClockMetaAnim>,
-    laser_sweep: ResMut<'w, TunnelgonLaserSweepMetaAnim>,
-    ring_ftb: ResMut<'w, TunnelgonRingsFTBMetaAnim>,
-    ring_btf: ResMut<'w, TunnelgonRingsBTFMetaAnim>,
-    ring_train: ResMut<'w, TunnelgonRingsTrainMetaAnim>,
+    sequences: ResMut<'w, MetaSequences>,
+    motions: ResMut<'w, TunnelgonMotions>,
 }
 
 impl TgMetaAnim<'_> {
-    pub fn load_storage(&mut self, storage: TgMetaAnimStorage) {
-        self.laser_cycle.enabled = storage.laser_cycle;
-        self.laser_figure_eight.enabled = storage.laser_figure_eight;
-        self.laser_round_the_clock.enabled = storage.laser_round_the_clock;
-        self.laser_sweep.enabled = storage.laser_sweep;
-        self.ring_ftb.enabled = storage.ring_ftb;
-        self.ring_btf.enabled = storage.ring_btf;
-        self.ring_train.enabled = storage.ring_train;
+    pub fn load_storage(&mut self, storage: &TgMetaAnimStorage) {
+        for sequence in self.sequences.sequences.iter_mut() {
+            sequence.enabled = storage.sequences.contains(&sequence.name);
+        }
+        if self.motions.motions != storage.motions {
+            self.motions.motions = storage.motions.clone();
+        }
     }
 }
 
-#[derive(Default, Copy, Clone)]
+#[derive(Default, Clone)]
 pub struct TgMetaAnimStorage {
-    laser_cycle: bool,
-    laser_figure_eight: bool,
-    laser_round_the_clock: bool,
-    laser_sweep: bool,
-    ring_ftb: bool,
-    ring_btf: bool,
-    ring_train: bool,
+    /// File names of the enabled meta sequences
+    sequences: Vec<String>,
+    /// Tunnel travel and spiral per hexagon, missing hexagons use the default motion
+    motions: HashMap<HexagonDefinition, TunnelgonMotion>,
+}
+
+impl TgMetaAnimStorage {
+    fn new(sequences: &[&str]) -> Self {
+        Self { sequences: sequences.iter().map(|name| name.to_string()).collect(), ..default() }
+    }
+
+    /// Same motion on every hexagon
+    fn with_motion(mut self, motion: TunnelgonMotion) -> Self {
+        self.motions = ALL_HEXAGONS.iter().map(|hex| (*hex, motion)).collect();
+        self
+    }
 }
 
 #[derive(SystemParam)]
 pub struct TubesAnim<'w> {
     wave: ResMut<'w, TubesWaveAnims>,
+    layers: ResMut<'w, LedLayerSettings>,
+    symmetry: ResMut<'w, LedSymmetry>,
+    sequencer: ResMut<'w, TubeSequencer>,
+    noise_field: ResMut<'w, NoiseField>,
 }
 
 impl TubesAnim<'_> {
@@ -64,6 +81,9 @@ impl TubesAnim<'_> {
         self.wave.punch4 = storage.punch4;
         self.wave.sweep_out = storage.sweep_out;
         self.wave.sweep_in = storage.sweep_in;
+        self.symmetry.mode = storage.symmetry;
+        self.sequencer.enabled = storage.sequencer;
+        self.noise_field.settings = storage.noise_field;
     }
 }
 
@@ -76,6 +96,9 @@ pub struct TubesAnimStorage {
     punch4: bool,
     sweep_out: bool,
     sweep_in: bool,
+    symmetry: SymmetryMode,
+    sequencer: bool,
+    noise_field: NoiseFieldSettings,
 }
 
 #[derive(SystemParam)]
@@ -104,9 +127,12 @@ pub struct MetaAnimStorage {
     tg: TgMetaAnimStorage,
     tubes: TubesAnimStorage,
     phys: PhysAnimStorage,
-    tg_next: Vec<HexagonDefinition>,
-    sg_next: Vec<HexagonDefinition>,
-    pg_next: Vec<HexagonDefinition>,
+    /// Element per hexagon
+    elements: ElementAssignment,
+    /// File names of the enabled scripts
+    scripts: Vec<String>,
+    /// Step sequencer pattern, None keeps the current pattern
+    sequencer_pattern: Option<SequencerPattern>,
 }
 
 #[derive(Default)]
@@ -126,12 +152,12 @@ pub fn anim_gui(
     mut phys: PhysAnim,
     mut memory: Local<MetaAnimMemory>,
     mut beat_reader: EventReader<BeatEvent>,
-    mut tg_writer: ResMut<Events<SetTunnelgonEvent>>,
-    mut sg_writer: ResMut<Events<SetSwirlagonEvent>>,
-    mut pg_writer: ResMut<Events<SetPedrogonEvent>>,
-    mut tg_reader: Local<ManualEventReader<SetTunnelgonEvent>>,
-    mut sg_reader: Local<ManualEventReader<SetSwirlagonEvent>>,
-    mut pg_reader: Local<ManualEventReader<SetPedrogonEvent>>,
+    mut element_writer: ResMut<Events<SetHexagonElementEvent>>,
+    mut element_reader: Local<ManualEventReader<SetHexagonElementEvent>>,
+    hexagon_elements: Res<HexagonElements>,
+    mut scripts: ResMut<MetaScripts>,
+    // Index into `ALL_HEXAGONS` of the hexagon whose motion is edited
+    mut motion_hexagon: Local<usize>,
 ) {
     let ctx = contexts.ctx_mut();
 
@@ -148,38 +174,53 @@ pub fn anim_gui(
                 Some(_) => { memory.next.as_mut().unwrap() }
             };
 
-            for ev in tg_reader.read(&tg_writer) {
-                settings.tg_next = ev.affected_hexagons.clone();
-            }
-            for ev in sg_reader.read(&sg_writer) {
-                settings.sg_next = ev.affected_hexagons.clone();
-            }
-            for ev in pg_reader.read(&pg_writer) {
-                settings.pg_next = ev.affected_hexagons.clone();
+            for ev in element_reader.read(&element_writer) {
+                ev.apply(&mut settings.elements);
             }
 
             ui.separator();
-            ui.label("Laser");
-
             ui.horizontal(|ui| {
-                anim_button(ui, button_width, button_height, &mut settings.tg.laser_cycle, "Laser Cycle");
-                anim_button(ui, button_width, button_height, &mut settings.tg.laser_figure_eight, "Laser Fig8");
-            });
-            ui.horizontal(|ui| {
-                anim_button(ui, button_width, button_height, &mut settings.tg.laser_round_the_clock, "Laser Clock");
-                anim_button(ui, button_width, button_height, &mut settings.tg.laser_sweep, "Laser Sweep");
+                ui.label("Laser & Rings");
+                if ui.small_button("Reload").on_hover_text(SEQUENCE_DIR).clicked() {
+                    *tg.sequences = MetaSequences::load();
+                }
             });
 
-            ui.separator();
-            ui.label("Rings");
-
-            ui.horizontal(|ui| {
-                anim_button(ui, button_width, button_height, &mut settings.tg.ring_ftb, "FTB");
-                anim_button(ui, button_width, button_height, &mut settings.tg.ring_btf, "BTF");
+            for pair in tg.sequences.sequences.chunks(2) {
+                ui.horizontal(|ui| {
+                    for sequence in pair {
+                        sequence_button(ui, button_width, button_height, &mut settings.tg.sequences, &sequence.name, &sequence.label);
+                    }
+                });
+            }
+            for error in tg.sequences.errors.iter() {
+                ui.label(RichText::new(error).color(Color32::RED));
+            }
+            egui::CollapsingHeader::new("Tunnel motion").show(ui, |ui| {
+                tunnelgon_motion_gui(ui, &mut settings.tg.motions, &mut *motion_hexagon);
             });
 
-            ui.horizontal(|ui| {
-                anim_button(ui, button_width, button_height, &mut settings.tg.ring_train, "Train");
+            ui.separator();
+            ui.label("Scripts");
+            if scripts.scripts.is_empty() {
+                ui.label(format!("No scripts found in {}", SCRIPT_DIR));
+            }
+            for pair in scripts.scripts.chunks(2) {
+                ui.horizontal(|ui| {
+                    for script in pair {
+                        sequence_button(ui, button_width, button_height, &mut settings.scripts, &script.name, &script.name);
+                    }
+                });
+            }
+            for script in scripts.scripts.iter() {
+                if let Some(error) = script.error.as_ref() {
+                    ui.label(RichText::new(format!("{}: {}", script.name, error)).color(Color32::RED));
+                }
+            }
+            egui::CollapsingHeader::new("Script reference").show(ui, |ui| {
+                ui.label(format!("Hooks: {}", HOOKS.join(", ")));
+                ui.label(format!("Functions: {}", SCRIPT_FUNCTIONS.join(", ")));
+                ui.label("Hexagons are names like \"A1\", \"Main\", \"all\" or arrays of them, `this` keeps state between calls");
             });
 
             ui.separator();
@@ -208,6 +249,27 @@ pub fn anim_gui(
                 anim_button(ui, button_width, button_height, &mut settings.tubes.punch3, "Punch3");
                 anim_button(ui, button_width, button_height, &mut settings.tubes.punch4, "Punch4");
             });
+            ui.horizontal(|ui| {
+                anim_button(ui, button_width, button_height, &mut settings.tubes.sequencer, "Sequencer");
+                tubes_button(ui, button_width, button_height, &mut settings.tubes.wave, NOISE_FIELD_WAVE, "Noise field");
+            });
+            egui::CollapsingHeader::new("Transition").show(ui, |ui| {
+                wave_transition_gui(ui, &mut tubes.wave.transition);
+            });
+            egui::CollapsingHeader::new("Noise field").show(ui, |ui| {
+                noise_field_gui(ui, &mut settings.tubes.noise_field);
+            });
+            ui.horizontal_wrapped(|ui| {
+                for mode in SymmetryMode::iter() {
+                    ui.selectable_value(&mut settings.tubes.symmetry, mode, format!("{:?}", mode));
+                }
+            });
+            egui::CollapsingHeader::new("Layers").show(ui, |ui| {
+                led_layers_gui(ui, &mut tubes.layers);
+            });
+            egui::CollapsingHeader::new("Symmetry").show(ui, |ui| {
+                led_symmetry_gui(ui, &mut tubes.symmetry);
+            });
 
             ui.separator();
             ui.heading("Eyes");
@@ -242,7 +304,8 @@ pub fn anim_gui(
 
 
             // SETTINGS DONE
-            let settings_clone = settings.clone();
+            let mut settings_clone = settings.clone();
+            settings_clone.sequencer_pattern = Some(tubes.sequencer.pattern.clone());
 
             ui.separator();
             ui.label("Presets");
@@ -332,18 +395,69 @@ pub fn anim_gui(
             }
 
             // Load current settings
-            tg.load_storage(memory.current.tg);
+            tg.load_storage(&memory.current.tg);
+            for script in scripts.scripts.iter_mut() {
+                script.enabled = memory.current.scripts.contains(&script.name);
+            }
             tubes.load_storage(memory.current.tubes);
             phys.load_storage(memory.current.phys);
             if !memory.gons_written {
-                tg_writer.send(SetTunnelgonEvent { affected_hexagons: memory.current.tg_next.clone() });
-                sg_writer.send(SetSwirlagonEvent { affected_hexagons: memory.current.sg_next.clone() });
-                pg_writer.send(SetPedrogonEvent { affected_hexagons: memory.current.pg_next.clone() });
+                if let Some(pattern) = &memory.current.sequencer_pattern {
+                    tubes.sequencer.pattern = pattern.clone();
+                }
+                for ev in assignment_events(hexagon_elements.names(), &memory.current.elements) {
+                    element_writer.send(ev);
+                }
                 memory.gons_written = true;
             };
         });
 }
 
+fn sequence_button(ui: &mut Ui, width: f32, height: f32, enabled: &mut Vec<String>, name: &str, text: &str) {
+    let is_enabled = enabled.iter().any(|enabled| enabled == name);
+    if ui.add_sized([width, height], egui::SelectableLabel::new(is_enabled, text))
+        .clicked() {
+        if is_enabled {
+            enabled.retain(|enabled| enabled != name);
+        } else {
+            enabled.push(name.to_string());
+        }
+    };
+}
+
+fn tunnelgon_motion_gui(ui: &mut Ui, motions: &mut HashMap<HexagonDefinition, TunnelgonMotion>, selected: &mut usize) {
+    ui.horizontal_wrapped(|ui| {
+        for (i, hex) in ALL_HEXAGONS.iter().enumerate() {
+            ui.selectable_value(selected, i, format!("{:?}", hex));
+        }
+    });
+    let hex = ALL_HEXAGONS[*selected % ALL_HEXAGONS.len()];
+    let motion = motions.entry(hex).or_default();
+    ui.horizontal(|ui| {
+        for travel in TunnelTravel::iter() {
+            ui.selectable_value(&mut motion.travel, travel, format!("{:?}", travel));
+        }
+    });
+    ui.add(egui::Slider::new(&mut motion.speed, -4.0..=4.0).text("Segments per beat"));
+    ui.checkbox(&mut motion.reverse_on_bar, "Reverse on bar");
+    ui.add(egui::Slider::new(&mut motion.spiral_freq, 1.0..=40.0).text("Spiral freq"));
+    ui.add(egui::Slider::new(&mut motion.spiral_skew, -12.0..=12.0).text("Spiral skew"));
+    ui.add(egui::Slider::new(&mut motion.skew_sweep, 0.0..=12.0).text("Skew sweep"));
+    ui.add(egui::Slider::new(&mut motion.skew_sweep_beats, 1.0..=64.0).text("Sweep beats"));
+    ui.checkbox(&mut motion.flip_on_phrase, "Flip spiral on phrase");
+    let motion = *motion;
+    ui.horizontal(|ui| {
+        if ui.button("Apply to all").clicked() {
+            for hex in ALL_HEXAGONS {
+                motions.insert(hex, motion);
+            }
+        }
+        if ui.button("Reset").clicked() {
+            motions.insert(hex, TunnelgonMotion::default());
+        }
+    });
+}
+
 fn anim_button(ui: &mut Ui, width: f32, height: f32, toggle: &mut bool, text: impl Into<WidgetText>) {
     if ui.add_sized([width, height], egui::SelectableLabel::new(*toggle, text))
         .clicked() {
@@ -351,6 +465,67 @@ fn anim_button(ui: &mut Ui, width: f32, height: f32, toggle: &mut bool, text: im
     };
 }
 
+fn led_layers_gui(ui: &mut Ui, layers: &mut LedLayerSettings) {
+    for layer in LedLayer::iter() {
+        let setting = layers.get_mut(layer);
+        ui.horizontal(|ui| {
+            ui.add_sized([50., 20.], egui::Label::new(format!("{:?}", layer)));
+            ui.add(egui::DragValue::new(&mut setting.opacity).speed(0.01).clamp_range(0.0..=1.0));
+            egui::ComboBox::from_id_source(format!("led_layer_blend_{:?}", layer))
+                .selected_text(format!("{:?}", setting.blend_mode))
+                .show_ui(ui, |ui| {
+                    for blend_mode in LedBlendMode::iter() {
+                        ui.selectable_value(&mut setting.blend_mode, blend_mode, format!("{:?}", blend_mode));
+                    }
+                });
+        });
+    }
+}
+
+fn wave_transition_gui(ui: &mut Ui, transition: &mut WaveTransition) {
+    ui.horizontal_wrapped(|ui| {
+        for style in TransitionStyle::iter() {
+            ui.selectable_value(&mut transition.style, style, format!("{:?}", style));
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.add(egui::DragValue::new(&mut transition.duration).speed(0.1).clamp_range(0.0..=32.0));
+        for unit in TransitionUnit::iter() {
+            ui.selectable_value(&mut transition.unit, unit, format!("{:?}", unit));
+        }
+    });
+    if transition.is_running() {
+        ui.add(egui::ProgressBar::new(transition.progress()));
+    }
+}
+
+fn noise_field_gui(ui: &mut Ui, settings: &mut NoiseFieldSettings) {
+    ui.horizontal_wrapped(|ui| {
+        for noise_type in NoiseType::iter() {
+            ui.selectable_value(&mut settings.noise_type, noise_type, format!("{:?}", noise_type));
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.label("Seed");
+        ui.add(egui::DragValue::new(&mut settings.seed));
+        ui.label("Octaves");
+        ui.add(egui::DragValue::new(&mut settings.octaves).clamp_range(1..=8));
+    });
+    ui.add(egui::Slider::new(&mut settings.scale, 0.001..=0.05).logarithmic(true).text("Scale"));
+    ui.add(egui::Slider::new(&mut settings.drift_angle, -180.0..=180.0).text("Drift angle"));
+    ui.add(egui::Slider::new(&mut settings.drift_speed, 0.0..=500.0).text("Drift speed"));
+    ui.add(egui::Slider::new(&mut settings.evolution, 0.0..=3.0).text("Evolution"));
+    ui.add(egui::Slider::new(&mut settings.kick, 0.0..=20.0).text("Beat kick"));
+    ui.add(egui::Slider::new(&mut settings.kick_decay, 0.01..=1.0).text("Kick decay"));
+}
+
+fn led_symmetry_gui(ui: &mut Ui, symmetry: &mut LedSymmetry) {
+    ui.checkbox(&mut symmetry.strobe_pairs, "Strobe mirrored pairs");
+    for layer in LedLayer::iter() {
+        ui.checkbox(&mut symmetry.layers[layer.index()], format!("{:?}", layer));
+    }
+}
+
 fn tubes_button(ui: &mut Ui, width: f32, height: f32, wave: &mut usize, wave_set: usize, text: impl Into<WidgetText>) {
     if ui.add_sized([width, height], egui::SelectableLabel::new(*wave == wave_set, text))
         .clicked() {
@@ -387,14 +562,16 @@ fn memory_storage_buttons(ui: &mut Ui, index: usize) -> MemStorageButtonAction {
     ret
 }
 
+fn elements(assignment: &[(&str, &[HexagonDefinition])]) -> ElementAssignment {
+    assignment.iter()
+        .flat_map(|(element, hexagons)| hexagons.iter().map(|hex| (*hex, element.to_string())))
+        .collect()
+}
+
 fn preset1() -> MetaAnimStorage {
     MetaAnimStorage {
-        tg: TgMetaAnimStorage {
-            laser_round_the_clock: true,
-            ring_ftb: true,
-            ring_btf: true,
-            ..default()
-        },
+        tg: TgMetaAnimStorage::new(&["laser_round_the_clock", "ring_ftb", "ring_btf"])
+            .with_motion(TunnelgonMotion { travel: TunnelTravel::BeatStep, ..default() }),
         tubes: TubesAnimStorage {
             wave: 1,
             ..default()
@@ -403,52 +580,40 @@ fn preset1() -> MetaAnimStorage {
             eye_count: 19,
             ..default()
         },
-        tg_next: vec![A1, A2, A3, B1, B2, B3],
+        elements: elements(&[(TUNNELGON, &[A1, A2, A3, B1, B2, B3])]),
         ..default()
     }
 }
 
 fn preset2() -> MetaAnimStorage {
     MetaAnimStorage {
-        tg: TgMetaAnimStorage {
-            laser_cycle: true,
-            ring_ftb: true,
-            ring_btf: true,
-            ..default()
-        },
+        tg: TgMetaAnimStorage::new(&["laser_cycle", "ring_ftb", "ring_btf"]),
         tubes: TubesAnimStorage {
             punch3: true,
             ..default()
         },
-        tg_next: vec![A1, A3, B1, B3],
-        sg_next: vec![A2, B2],
         phys: PhysAnimStorage {
           eye_count: 19,
             ..default()
         },
+        elements: elements(&[(TUNNELGON, &[A1, A3, B1, B3]), (SWIRLAGON, &[A2, B2])]),
         ..default()
     }
 }
 
 fn preset3() -> MetaAnimStorage {
     MetaAnimStorage {
-        tg: TgMetaAnimStorage {
-            laser_cycle: true,
-            laser_round_the_clock: true,
-            ring_ftb: true,
-            ring_btf: true,
-            ..default()
-        },
+        tg: TgMetaAnimStorage::new(&["laser_cycle", "laser_round_the_clock", "ring_ftb", "ring_btf"])
+            .with_motion(TunnelgonMotion { reverse_on_bar: true, skew_sweep: 4., flip_on_phrase: true, ..default() }),
         tubes: TubesAnimStorage {
             wave: 6,
             ..default()
         },
-        tg_next: vec![A1, A3, B1, B3],
-        sg_next: vec![A2, B2],
         phys: PhysAnimStorage {
             eye_count: 19,
             ..default()
         },
+        elements: elements(&[(TUNNELGON, &[A1, A3, B1, B3]), (SWIRLAGON, &[A2, B2])]),
         ..default()
     }
 }
@@ -459,11 +624,11 @@ fn preset4() -> MetaAnimStorage {
             wave: 3,
             ..default()
         },
-        sg_next: vec![A1, A3, A2, B2, B1, B3],
         phys: PhysAnimStorage {
             eye_count: 19,
             ..default()
         },
+        elements: elements(&[(SWIRLAGON, &[A1, A3, A2, B2, B1, B3])]),
         ..default()
     }
 }
@@ -474,12 +639,11 @@ fn preset5() -> MetaAnimStorage {
             wave: 5,
             ..default()
         },
-        sg_next: vec![A1, A3, B1, B3],
-        tg_next: vec![A2, B2],
         phys: PhysAnimStorage {
             eye_count: 19,
             ..default()
         },
+        elements: elements(&[(SWIRLAGON, &[A1, A3, B1, B3]), (TUNNELGON, &[A2, B2])]),
         ..default()
     }
 }
@@ -490,11 +654,11 @@ fn preset6() -> MetaAnimStorage {
             wave: 5,
             ..default()
         },
-        pg_next: vec![A1, A3, A2, B2, B1, B3],
         phys: PhysAnimStorage {
             eye_count: 19,
             ..default()
         },
+        elements: elements(&[(MEDIA, &[A1, A3, A2, B2, B1, B3])]),
         ..default()
     }
 }
\ No newline at end of file
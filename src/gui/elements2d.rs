@@ -1,17 +1,21 @@
-use bevy::prelude::{Color, EventWriter};
+use bevy::prelude::{Color, EventWriter, Res};
 use bevy_egui::{egui, EguiContexts};
-use crate::elements2d::tunnelgon::{LaserAnimationEvent, RingAnimationEvent, RingBasePosAnim, RingBaseValAnim, SetTunnelgonEvent, TunnelgonBaseAnim};
+use crate::elements2d::element::SetHexagonElementEvent;
+use crate::elements2d::tunnelgon_layers::AnimLayer;
+use crate::elements2d::tunnelgon::{LaserAnimationEvent, RingAnimationEvent, RingBasePosAnim, RingBaseValAnim, TunnelgonBaseAnim, TunnelgonLayout, TUNNELGON};
 use crate::elements2d::zoomagon::SpawnZoomagonEvent;
 use crate::hexagon::HexagonDefinition;
 
 pub fn elements_2d_gui(
     mut contexts: EguiContexts,
     mut spawn_zoomagon_event_writer: EventWriter<SpawnZoomagonEvent>,
-    mut set_tunnelgon_event_writer: EventWriter<SetTunnelgonEvent>,
+    mut set_element_event_writer: EventWriter<SetHexagonElementEvent>,
     mut laser_animation_event_writer: EventWriter<LaserAnimationEvent>,
     mut ring_animation_event_writer: EventWriter<RingAnimationEvent>,
+    layout: Res<TunnelgonLayout>,
 ) {
     egui::Window::new("Elements 2D").show(contexts.ctx_mut(), |ui| {
+        let laser_count = layout.lasers.len();
         if ui.button("Zoomagon").clicked() {
             spawn_zoomagon_event_writer.send(
                 SpawnZoomagonEvent {
@@ -28,25 +32,20 @@ pub fn elements_2d_gui(
             );
         };
         if ui.button("Tunnelgon All").clicked() {
-            set_tunnelgon_event_writer.send(
-                SetTunnelgonEvent {
-                    affected_hexagons: vec![
-                        HexagonDefinition::A1,
-                        HexagonDefinition::A2,
-                        HexagonDefinition::A3,
-                        HexagonDefinition::B1,
-                        HexagonDefinition::B2,
-                        HexagonDefinition::B3,
-                    ]
-                }
+            set_element_event_writer.send(
+                SetHexagonElementEvent::new(TUNNELGON, vec![
+                    HexagonDefinition::A1,
+                    HexagonDefinition::A2,
+                    HexagonDefinition::A3,
+                    HexagonDefinition::B1,
+                    HexagonDefinition::B2,
+                    HexagonDefinition::B3,
+                ])
             );
         };
         if ui.button("Tunnelgon Off").clicked() {
-            set_tunnelgon_event_writer.send(
-                SetTunnelgonEvent {
-                    affected_hexagons: vec![
-                    ]
-                }
+            set_element_event_writer.send(
+                SetHexagonElementEvent::new(TUNNELGON, vec![])
             );
         };
         if ui.button("Laser Pulse").clicked() {
@@ -60,9 +59,10 @@ pub fn elements_2d_gui(
                         HexagonDefinition::B2,
                         HexagonDefinition::B3,
                     ],
+                    layer: AnimLayer::Accent,
                     base_anim: TunnelgonBaseAnim::Pulse,
-                    indices: vec![0, 1, 2, 3, 4, 5, 6, 7],
-                    values: vec![1.; 8],
+                    indices: (0..laser_count).collect(),
+                    values: vec![1.; laser_count],
                 }
             );
         };
@@ -77,9 +77,10 @@ pub fn elements_2d_gui(
                         HexagonDefinition::B2,
                         HexagonDefinition::B3,
                     ],
+                    layer: AnimLayer::Background,
                     base_anim: TunnelgonBaseAnim::SetToVal,
-                    indices: vec![0, 1, 2, 3, 4, 5, 6, 7],
-                    values: vec![1.; 8],
+                    indices: (0..laser_count).collect(),
+                    values: vec![1.; laser_count],
                 }
             );
         };
@@ -94,9 +95,10 @@ pub fn elements_2d_gui(
                         HexagonDefinition::B2,
                         HexagonDefinition::B3,
                     ],
+                    layer: AnimLayer::Background,
                     base_anim: TunnelgonBaseAnim::SetToVal,
-                    indices: vec![0, 1, 2, 3, 4, 5, 6, 7],
-                    values: vec![0.; 8],
+                    indices: (0..laser_count).collect(),
+                    values: vec![0.; laser_count],
                 }
             );
         };
@@ -111,6 +113,7 @@ pub fn elements_2d_gui(
                         HexagonDefinition::B2,
                         HexagonDefinition::B3,
                     ],
+                    layer: AnimLayer::Accent,
                     base_pos_anim: RingBasePosAnim::SlideLinear,
                     base_val_anim: RingBaseValAnim::Pulse,
                     indices: vec![0, 1, 2, 3, 4,],
@@ -121,4 +124,4 @@ pub fn elements_2d_gui(
             );
         };
     });
-}
\ No newline at end of file
+}
@@ -0,0 +1,20 @@
+use bevy::prelude::{Mut, With, World};
+use bevy::window::PrimaryWindow;
+use bevy_egui::{egui, EguiContext};
+use crate::elements2d::element::HexagonElements;
+
+/// Parameters of every registered element. Exclusive, the element UIs get the whole world.
+pub fn hexagon_elements_gui(world: &mut World) {
+    let Ok(mut context) = world.query_filtered::<&mut EguiContext, With<PrimaryWindow>>().get_single_mut(world) else { return; };
+    let ctx = context.get_mut().clone();
+
+    world.resource_scope(|world, mut elements: Mut<HexagonElements>| {
+        egui::Window::new("Elements").show(&ctx, |ui| {
+            for element in elements.iter_mut() {
+                egui::CollapsingHeader::new(element.name()).show(ui, |ui| {
+                    element.ui(ui, world);
+                });
+            }
+        });
+    });
+}
@@ -0,0 +1,218 @@
+use bevy::prelude::{Local, Res, ResMut};
+use bevy_egui::{egui, EguiContexts};
+use strum::IntoEnumIterator;
+use crate::led_output::{ColorOrder, LedController, LedOutputConfig, LedOutputState, LedProtocol, OUTPUT_CONFIG_PATH};
+use crate::led_output::calibration::LedCalibration;
+use crate::led_output::loopback::LedLoopback;
+use crate::led_output::recorder::{LedRecorder, RecordingFormat};
+use crate::physics_hexagon::lights::tube_layout::TubeLayout;
+
+pub fn led_output_gui(
+    mut contexts: EguiContexts,
+    mut config: ResMut<LedOutputConfig>,
+    state: Res<LedOutputState>,
+    mut loopback: ResMut<LedLoopback>,
+    mut calibration: ResMut<LedCalibration>,
+    mut recorder: ResMut<LedRecorder>,
+    layout: Res<TubeLayout>,
+    mut file_status: Local<Option<String>>,
+) {
+    egui::Window::new("LED Output").default_open(false).show(contexts.ctx_mut(), |ui| {
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut config.enabled, "Enabled");
+            ui.checkbox(&mut config.sync, "Sync");
+            ui.label("FPS");
+            ui.add(egui::DragValue::new(&mut config.frame_rate).speed(1.).clamp_range(1.0..=200.0));
+        });
+        ui.horizontal(|ui| {
+            ui.label("sACN sync universe");
+            ui.add(egui::DragValue::new(&mut config.sacn_sync_universe).speed(1).clamp_range(1..=63999));
+        });
+        ui.label(format!("Packets sent: {}", state.packets_sent));
+        if let Some(last_error) = &state.last_error {
+            ui.colored_label(egui::Color32::RED, last_error);
+        }
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.heading("Controllers");
+            if ui.button("Add").clicked() {
+                config.controllers.push(LedController::default());
+            }
+            if ui.button("Save").on_hover_text(OUTPUT_CONFIG_PATH).clicked() {
+                *file_status = Some(match config.save() {
+                    Ok(()) => format!("Saved {}", OUTPUT_CONFIG_PATH),
+                    Err(e) => format!("Couldn't save {}: {}", OUTPUT_CONFIG_PATH, e),
+                });
+            }
+            if ui.button("Reload").on_hover_text(OUTPUT_CONFIG_PATH).clicked() {
+                *file_status = Some(match config.load(&layout) {
+                    Ok(()) => format!("Loaded {}", OUTPUT_CONFIG_PATH),
+                    Err(e) => format!("Couldn't load {}: {}", OUTPUT_CONFIG_PATH, e),
+                });
+            }
+        });
+        if let Some(file_status) = &*file_status {
+            ui.label(file_status);
+        }
+        let removable = config.controllers.len() > 1;
+        let mut remove = None;
+        for (i, controller) in config.controllers.iter_mut().enumerate() {
+            ui.push_id(i, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{:0>2}", i));
+                    ui.text_edit_singleline(&mut controller.name);
+                    if removable && ui.button("Remove").clicked() {
+                        remove = Some(i);
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("IP");
+                    ui.text_edit_singleline(&mut controller.address);
+                });
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_id_source("protocol")
+                        .selected_text(format!("{:?}", controller.protocol))
+                        .show_ui(ui, |ui| {
+                            for protocol in LedProtocol::iter() {
+                                ui.selectable_value(&mut controller.protocol, protocol, format!("{:?}", protocol));
+                            }
+                        });
+                    egui::ComboBox::from_id_source("color_order")
+                        .selected_text(format!("{:?}", controller.color_order))
+                        .show_ui(ui, |ui| {
+                            for color_order in ColorOrder::iter() {
+                                ui.selectable_value(&mut controller.color_order, color_order, format!("{:?}", color_order));
+                            }
+                        });
+                });
+                ui.horizontal(|ui| {
+                    if controller.protocol != LedProtocol::Ddp {
+                        ui.label("Universe");
+                        ui.add(egui::DragValue::new(&mut controller.start_universe).speed(1).clamp_range(controller.protocol.universes()));
+                        ui.label("Px/Uni");
+                        let max_pixels = controller.max_pixels_per_universe();
+                        ui.add(egui::DragValue::new(&mut controller.pixels_per_universe).speed(1).clamp_range(1..=max_pixels));
+                    }
+                    ui.label("Gamma");
+                    ui.add(egui::DragValue::new(&mut controller.gamma).speed(0.01).clamp_range(1.0..=3.0));
+                    ui.label("Max FPS");
+                    ui.add(egui::DragValue::new(&mut controller.max_frame_rate).speed(1.).clamp_range(0.0..=200.0));
+                });
+            });
+            ui.separator();
+        }
+        if let Some(i) = remove {
+            config.remove_controller(i);
+        }
+
+        let controller_count = config.controllers.len();
+        let config = &mut *config;
+        egui::CollapsingHeader::new("Tube mapping").show(ui, |ui| {
+            for mapping in config.tubes.iter_mut() {
+                ui.horizontal(|ui| {
+                    ui.add_sized([70., 20.], egui::Label::new(format!("{}", mapping.tube)));
+                    ui.label("Ctrl");
+                    ui.add(egui::DragValue::new(&mut mapping.controller).speed(1).clamp_range(0..=controller_count.saturating_sub(1)));
+                    ui.label("Px");
+                    ui.add(egui::DragValue::new(&mut mapping.pixel_offset).speed(1));
+                    ui.checkbox(&mut mapping.reversed, "Rev");
+                    if let Some(controller) = config.controllers.get(mapping.controller) {
+                        if controller.protocol == LedProtocol::Ddp {
+                            ui.label(format!("Byte {}", mapping.pixel_offset * controller.color_order.channels()));
+                        } else {
+                            let (universe, channel) = controller.universe_and_channel(mapping.pixel_offset);
+                            ui.label(format!("U{} Ch{}", universe, channel));
+                        }
+                    }
+                });
+            }
+        });
+
+        egui::CollapsingHeader::new("Calibration").show(ui, |ui| {
+            ui.add(egui::Slider::new(&mut calibration.brightness, 0.0..=1.0).text("Brightness"));
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut calibration.limiter_enabled, "Current limiter");
+                ui.label("Budget mA");
+                ui.add(egui::DragValue::new(&mut calibration.current_budget).speed(10.).clamp_range(0.0..=100000.0));
+            });
+            ui.horizontal(|ui| {
+                ui.label("mA/channel");
+                ui.add(egui::DragValue::new(&mut calibration.channel_current).speed(0.1).clamp_range(0.0..=100.0));
+                ui.label("mA idle");
+                ui.add(egui::DragValue::new(&mut calibration.idle_current).speed(0.1).clamp_range(0.0..=10.0));
+            });
+            ui.horizontal(|ui| {
+                ui.label("Attack s");
+                ui.add(egui::DragValue::new(&mut calibration.limiter_attack).speed(0.01).clamp_range(0.0..=5.0));
+                ui.label("Release s");
+                ui.add(egui::DragValue::new(&mut calibration.limiter_release).speed(0.01).clamp_range(0.0..=10.0));
+            });
+            let over_budget = calibration.estimated_current > calibration.current_budget;
+            ui.colored_label(
+                if over_budget { egui::Color32::YELLOW } else { ui.visuals().text_color() },
+                format!("Estimated: {:.0} mA  Limiter: {:.0}%", calibration.estimated_current, calibration.limiter_scale * 100.),
+            );
+
+            ui.label("White balance");
+            for tube in layout.indices() {
+                let mut trim = calibration.white_balance(tube);
+                ui.horizontal(|ui| {
+                    ui.add_sized([70., 20.], egui::Label::new(format!("{}", tube)));
+                    for (channel, label) in trim.iter_mut().zip(["R", "G", "B"]) {
+                        ui.label(label);
+                        ui.add(egui::DragValue::new(channel).speed(0.01).clamp_range(0.0..=1.0));
+                    }
+                });
+                if trim == [1.; 3] {
+                    calibration.white_balance.remove(&tube);
+                } else {
+                    calibration.white_balance.insert(tube, trim);
+                }
+            }
+        });
+
+        ui.separator();
+        ui.heading("Recorder");
+        ui.add_enabled_ui(!recorder.is_recording(), |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Path");
+                ui.text_edit_singleline(&mut recorder.path);
+                egui::ComboBox::from_id_source("recording_format")
+                    .selected_text(format!("{:?}", recorder.format))
+                    .show_ui(ui, |ui| {
+                        for format in RecordingFormat::iter() {
+                            ui.selectable_value(&mut recorder.format, format, format!("{:?}", format));
+                        }
+                    });
+            });
+        });
+        ui.horizontal(|ui| {
+            if recorder.is_recording() {
+                if ui.button("Stop").clicked() {
+                    recorder.stop();
+                }
+            } else if ui.button("Record").clicked() {
+                recorder.start();
+            }
+            if let Some(path) = &recorder.recording_path {
+                ui.label(format!("{} ({} frames)", path, recorder.frames));
+            }
+        });
+        if let Some(last_error) = &recorder.last_error {
+            ui.colored_label(egui::Color32::RED, last_error);
+        }
+
+        ui.separator();
+        ui.heading("Loopback test");
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut loopback.enabled, "Enabled");
+            if ui.button("Reset").clicked() {
+                loopback.reset_stats();
+            }
+        });
+        let stats = loopback.stats;
+        ui.label(format!("Received: {}  Verified: {}  Syncs: {}", stats.received, stats.verified, stats.syncs));
+        ui.label(format!("Mismatched: {}  Unexpected: {}", stats.mismatched, stats.unexpected));
+    });
+}
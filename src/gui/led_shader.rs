@@ -0,0 +1,38 @@
+use bevy::prelude::ResMut;
+use bevy_egui::{egui, EguiContexts};
+use bevy_egui::egui::{Color32, RichText};
+use crate::led_shader::{LedShaders, SHADER_DIR, SHADER_EXTENSION};
+use crate::led_shader::expr::{FUNCTIONS, VARIABLES};
+
+pub fn led_shader_gui(
+    mut contexts: EguiContexts,
+    mut shaders: ResMut<LedShaders>,
+) {
+    egui::Window::new("LED Shaders").default_open(false).show(contexts.ctx_mut(), |ui| {
+        let shaders = &mut *shaders;
+        ui.checkbox(&mut shaders.enabled, "Enabled");
+        if shaders.shaders.is_empty() {
+            ui.label(format!("No shaders found in {}/*.{}", SHADER_DIR, SHADER_EXTENSION));
+        }
+        for shader in shaders.shaders.iter() {
+            let selected = shaders.selected.as_ref() == Some(&shader.name);
+            let text = if shader.error.is_some() {
+                RichText::new(&shader.name).color(Color32::RED)
+            } else {
+                RichText::new(&shader.name)
+            };
+            if ui.selectable_label(selected, text).clicked() {
+                shaders.selected = Some(shader.name.clone());
+            }
+        }
+        if let Some(error) = shaders.selected().and_then(|shader| shader.error.as_ref()) {
+            ui.label(RichText::new(error).color(Color32::RED));
+        }
+        egui::CollapsingHeader::new("Reference").show(ui, |ui| {
+            ui.label(format!("Variables: {}", VARIABLES.join(", ")));
+            ui.label(format!("Functions: {}", FUNCTIONS.join(", ")));
+            ui.label("Operators: + - * / % ^ < > <= >=");
+            ui.label("x and y are screen positions scaled to -1..1 vertically, pos runs from 0 to 1 along the tube");
+        });
+    });
+}
@@ -10,12 +10,12 @@ use bevy_egui::{egui, EguiContexts};
 use bevy_egui::egui::{Ui, WidgetText};
 use egui_plot::{Line, Plot, PlotBounds, PlotPoints};
 use rand::{Rng, thread_rng};
+use strum::IntoEnumIterator;
 use crate::anims::AnimColors;
+use crate::anims::color_modes::{ANIM_HARMONY, ColorModes, Harmony};
 use crate::beat::BeatEvent;
 use crate::beat::bpm_guesser::BpmGuesser;
-use crate::elements2d::pedrogon::SetPedrogonEvent;
-use crate::elements2d::swirlagon::SetSwirlagonEvent;
-use crate::elements2d::tunnelgon::{SetTunnelgonEvent, TunnelgonAccum};
+use crate::elements2d::element::{assignment_events, ElementAssignment, HexagonElements, SetHexagonElementEvent};
 use crate::hexagon::HexagonDefinition;
 use crate::hexagon::HexagonDefinition::Main;
 use crate::propagating_render_layers::PropagatingRenderLayers;
@@ -53,9 +53,7 @@ pub struct NextSettings {
     swirl_next_beat: bool,
     swirl_preset: usize,
     gons_next_beat: bool,
-    tunnelgon: Vec<HexagonDefinition>,
-    swirlgon: Vec<HexagonDefinition>,
-    pedrogon: Vec<HexagonDefinition>,
+    elements: ElementAssignment,
     colors: AnimColors,
     col_rand_beat: bool,
 }
@@ -66,14 +64,11 @@ pub fn left_panel(
     mut beat_controls_params: BeatControlsParams,
     mut swirl: ResMut<SwirlAutomation>,
     mut next_settings: Local<NextSettings>,
-    mut tunnelgon_reader: Local<ManualEventReader<SetTunnelgonEvent>>,
-    mut tunnelgon_events: ResMut<Events<SetTunnelgonEvent>>,
-    mut swirlgon_reader: Local<ManualEventReader<SetSwirlagonEvent>>,
-    mut swirlgon_events: ResMut<Events<SetSwirlagonEvent>>,
-    mut pedrogon_reader: Local<ManualEventReader<SetPedrogonEvent>>,
-    mut pedrogon_events: ResMut<Events<SetPedrogonEvent>>,
+    mut element_reader: Local<ManualEventReader<SetHexagonElementEvent>>,
+    mut element_events: ResMut<Events<SetHexagonElementEvent>>,
+    hexagon_elements: Res<HexagonElements>,
     mut colors: ResMut<AnimColors>,
-    mut tunnelgon_accum_settings: ResMut<TunnelgonAccum>,
+    mut color_modes: ResMut<ColorModes>,
 ) {
     let ctx = contexts.ctx_mut();
 
@@ -153,7 +148,6 @@ pub fn left_panel(
                 ui.add(egui::DragValue::new(&mut beat_controls_params.plot_bounds.0).speed(1.0));
                 ui.label("Width:");
                 ui.add(egui::DragValue::new(&mut beat_controls_params.plot_bounds.1).speed(1.0));
-                ui.checkbox(&mut tunnelgon_accum_settings.enabled, "Tunnelgon accum");
             });
 
             let values: Vec<f64> = beat_controls_params.bpm_data.iter().map(|a| a.clone() as f64).collect();
@@ -232,44 +226,23 @@ pub fn left_panel(
             // Read changed from external
             ui.separator();
             ui.heading("Gons");
-            for ev in tunnelgon_reader.read(&tunnelgon_events) {
-                next_settings.tunnelgon = ev.affected_hexagons.clone();
-            }
-            for ev in swirlgon_reader.read(&swirlgon_events) {
-                next_settings.swirlgon = ev.affected_hexagons.clone();
-            }
-            for ev in pedrogon_reader.read(&pedrogon_events) {
-                next_settings.pedrogon = ev.affected_hexagons.clone();
+            for ev in element_reader.read(&element_events) {
+                ev.apply(&mut next_settings.elements);
             }
 
             for hex in vec![HexagonDefinition::A1, HexagonDefinition::A2, HexagonDefinition::A3,
                             HexagonDefinition::B1, HexagonDefinition::B2, HexagonDefinition::B3, HexagonDefinition::Main] {
-                ui.horizontal(|ui| {
+                ui.horizontal_wrapped(|ui| {
                     ui.label(format!("{:?}", hex));
-                    if ui.add_sized([60., 20.], egui::SelectableLabel::new(next_settings.tunnelgon.contains(&hex), "Tunnelgon"))
-                        .clicked() {
-                        insert_hex_to_vec(&mut next_settings.tunnelgon, &hex);
-                        remove_hex_from_vec(&mut next_settings.swirlgon, &hex);
-                        remove_hex_from_vec(&mut next_settings.pedrogon, &hex);
-                    };
-                    if ui.add_sized([60., 20.], egui::SelectableLabel::new(next_settings.swirlgon.contains(&hex), "Swirlgon"))
-                        .clicked() {
-                        insert_hex_to_vec(&mut next_settings.swirlgon, &hex);
-                        remove_hex_from_vec(&mut next_settings.tunnelgon, &hex);
-                        remove_hex_from_vec(&mut next_settings.pedrogon, &hex);
-                    };
-                    if ui.add_sized([60., 20.], egui::SelectableLabel::new(next_settings.pedrogon.contains(&hex), "Pedrogon"))
-                        .clicked() {
-                        insert_hex_to_vec(&mut next_settings.pedrogon, &hex);
-                        remove_hex_from_vec(&mut next_settings.swirlgon, &hex);
-                        remove_hex_from_vec(&mut next_settings.tunnelgon, &hex);
-                    };
-                    if hex == HexagonDefinition::Main {
-                        if ui.button("Off").clicked() {
-                            remove_hex_from_vec(&mut next_settings.pedrogon, &hex);
-                            remove_hex_from_vec(&mut next_settings.swirlgon, &hex);
-                            remove_hex_from_vec(&mut next_settings.tunnelgon, &hex);
-                        }
+                    for name in hexagon_elements.names() {
+                        let selected = next_settings.elements.get(&hex).map(|element| element == name).unwrap_or(false);
+                        if ui.add_sized([60., 20.], egui::SelectableLabel::new(selected, name))
+                            .clicked() {
+                            next_settings.elements.insert(hex, name.to_string());
+                        };
+                    }
+                    if ui.button("Off").clicked() {
+                        next_settings.elements.remove(&hex);
                     }
                 });
             }
@@ -349,6 +322,21 @@ pub fn left_panel(
                 };
             });
 
+            ui.horizontal(|ui| {
+                if ui.add_sized([60., 30.], egui::SelectableLabel::new(next_settings.colors.anim == ANIM_HARMONY, "Harmony"))
+                    .clicked() {
+                    next_settings.colors = AnimColors {
+                        primary: Color::BLACK,
+                        secondary: Color::BLACK,
+                        anim: ANIM_HARMONY,
+                    };
+                };
+            });
+
+            egui::CollapsingHeader::new("Harmony").show(ui, |ui| {
+                color_modes_gui(ui, &mut color_modes);
+            });
+
             // ----------------------------------------------
             // Write next settings
 
@@ -358,15 +346,9 @@ pub fn left_panel(
                 }
 
                 if next_settings.gons_next_beat {
-                    tunnelgon_events.send(SetTunnelgonEvent {
-                        affected_hexagons: next_settings.tunnelgon.clone()
-                    });
-                    swirlgon_events.send(SetSwirlagonEvent {
-                        affected_hexagons: next_settings.swirlgon.clone()
-                    });
-                    pedrogon_events.send(SetPedrogonEvent {
-                        affected_hexagons: next_settings.pedrogon.clone()
-                    });
+                    for ev in assignment_events(hexagon_elements.names(), &next_settings.elements) {
+                        element_events.send(ev);
+                    }
                     next_settings.gons_next_beat = false;
                 }
 
@@ -392,19 +374,44 @@ pub fn left_panel(
         });
 }
 
+fn color_modes_gui(ui: &mut Ui, modes: &mut ColorModes) {
+    ui.horizontal_wrapped(|ui| {
+        for harmony in Harmony::iter() {
+            ui.selectable_value(&mut modes.harmony, harmony, format!("{:?}", harmony));
+        }
+    });
+    ui.add(egui::Slider::new(&mut modes.base_hue, 0.0..=360.0).text("Base hue"));
+    ui.horizontal(|ui| {
+        ui.label("Secondary");
+        let count = modes.harmony.offsets().len() - 1;
+        ui.add(egui::DragValue::new(&mut modes.secondary_index).clamp_range(1..=count));
+    });
+    ui.add(egui::Slider::new(&mut modes.saturation, 0.0..=1.0).text("Saturation"));
+    ui.add(egui::Slider::new(&mut modes.lightness, 0.0..=1.0).text("Lightness"));
+    ui.horizontal(|ui| {
+        ui.label("Drift °/beat");
+        ui.add(egui::DragValue::new(&mut modes.drift).speed(0.1).clamp_range(-90.0..=90.0));
+    });
+    ui.horizontal(|ui| {
+        ui.label("Jump °");
+        ui.add(egui::DragValue::new(&mut modes.jump).speed(1.).clamp_range(-180.0..=180.0));
+        ui.label("every");
+        ui.add(egui::DragValue::new(&mut modes.jump_beats).speed(0.25).clamp_range(0.25..=64.0));
+        ui.label("beats");
+    });
+    ui.horizontal(|ui| {
+        ui.label("Pulse sat");
+        ui.add(egui::DragValue::new(&mut modes.saturation_pulse).speed(0.01).clamp_range(0.0..=1.0));
+        ui.label("bri");
+        ui.add(egui::DragValue::new(&mut modes.brightness_pulse).speed(0.01).clamp_range(0.0..=1.0));
+        ui.label("every");
+        ui.add(egui::DragValue::new(&mut modes.pulse_beats).speed(0.25).clamp_range(0.25..=16.0));
+    });
+}
+
 fn swirl_preset_button(ui: &mut Ui, preset: &mut usize, index: usize, text: impl Into<WidgetText>) {
     if ui.add_sized([80., 30.], egui::SelectableLabel::new(*preset == index, text))
         .clicked() {
         *preset = index;
     };
 }
-
-fn insert_hex_to_vec(vec: &mut Vec<HexagonDefinition>, hex: &HexagonDefinition) {
-    if !vec.contains(hex) {
-        vec.push(*hex);
-    }
-}
-
-fn remove_hex_from_vec(vec: &mut Vec<HexagonDefinition>, hex: &HexagonDefinition) {
-    *vec = vec.iter().filter(|h| *hex != **h).cloned().collect();
-}
\ No newline at end of file
@@ -0,0 +1,48 @@
+use bevy::prelude::{Local, ResMut};
+use bevy_egui::{egui, EguiContexts};
+use bevy_egui::egui::{Color32, RichText};
+use crate::master::MasterControls;
+
+/// Held state of the momentary buttons, so releasing them doesn't override keys or OSC
+#[derive(Default)]
+pub struct MomentaryButtons {
+    blackout: bool,
+    flash: bool,
+}
+
+pub fn master_gui(
+    mut contexts: EguiContexts,
+    mut master: ResMut<MasterControls>,
+    mut held: Local<MomentaryButtons>,
+) {
+    egui::Window::new("Master").show(contexts.ctx_mut(), |ui| {
+        ui.add(egui::Slider::new(&mut master.dimmer, 0.0..=1.0).text("Dimmer"));
+        ui.horizontal(|ui| {
+            let blackout = ui.add_sized([80., 30.], egui::SelectableLabel::new(master.blackout, "Blackout (B)"))
+                .is_pointer_button_down_on();
+            if blackout != held.blackout {
+                master.blackout = blackout;
+                held.blackout = blackout;
+            }
+            let flash = ui.add_sized([80., 30.], egui::SelectableLabel::new(master.flash, "Flash (F)"))
+                .is_pointer_button_down_on();
+            if flash != held.flash {
+                master.flash = flash;
+                held.flash = flash;
+            }
+            if ui.add_sized([80., 30.], egui::SelectableLabel::new(master.freeze, "Freeze (H)")).clicked() {
+                master.freeze = !master.freeze;
+            }
+        });
+        ui.horizontal(|ui| {
+            if ui.add_sized([80., 30.], egui::SelectableLabel::new(master.fade_out, "Fade (G)")).clicked() {
+                master.fade_out = !master.fade_out;
+            }
+            ui.add(egui::DragValue::new(&mut master.fade_beats).speed(0.1).clamp_range(0.0..=64.0).suffix(" beats"));
+        });
+        ui.add(egui::ProgressBar::new(master.level()).text("Output level"));
+        if master.freeze {
+            ui.label(RichText::new("FROZEN").color(Color32::RED).strong());
+        }
+    });
+}
@@ -4,11 +4,25 @@ use crate::GuiUpdate;
 use crate::gui::anims::anim_gui;
 use crate::gui::effectors::effectors_gui;
 use crate::gui::elements2d::elements_2d_gui;
+use crate::gui::hexagon_elements::hexagon_elements_gui;
+use crate::gui::led_output::led_output_gui;
+use crate::gui::led_shader::led_shader_gui;
+use crate::gui::master::master_gui;
 use crate::gui::left_panel::{BeatMute, left_panel};
+use crate::gui::palette::palette_gui;
+use crate::gui::pixel_map::pixel_map_gui;
+use crate::gui::tube_sequencer::tube_sequencer_gui;
 
 mod effectors;
 mod elements2d;
+mod hexagon_elements;
 mod anims;
+mod led_output;
+mod led_shader;
+mod master;
+mod palette;
+mod pixel_map;
+mod tube_sequencer;
 pub mod left_panel;
 
 pub struct GuiPlugin;
@@ -16,6 +30,6 @@ pub struct GuiPlugin;
 impl Plugin for GuiPlugin{
     fn build(&self, app: &mut App) {
         app.insert_resource(BeatMute::default());
-        app.add_systems(GuiUpdate, (/*effectors_gui, elements_2d_gui, */anim_gui, left_panel));
+        app.add_systems(GuiUpdate, (/*effectors_gui, */elements_2d_gui, anim_gui, left_panel, led_output_gui, pixel_map_gui, tube_sequencer_gui, led_shader_gui, master_gui, palette_gui, hexagon_elements_gui));
     }
 }
\ No newline at end of file
@@ -0,0 +1,222 @@
+use std::path::PathBuf;
+use bevy::prelude::{Color, Local, ResMut};
+use bevy::tasks::{AsyncComputeTaskPool, block_on, Task};
+use bevy::tasks::futures_lite::future;
+use bevy_egui::{egui, EguiContexts};
+use bevy_egui::egui::{Color32, RichText, Sense, Ui, Vec2};
+use strum::IntoEnumIterator;
+use crate::anims::meta_sequence::ALL_HEXAGONS;
+use crate::elements2d::tunnelgon_colors::{TunnelgonColorOverride, TunnelgonColors};
+use crate::palette::extract::{extract_palette, ExtractMethod, IMAGE_DIRS, image_files};
+use crate::palette::{ActivePalette, Palette, PALETTE_DIR, PaletteCycle, PaletteLibrary};
+
+fn color32(color: Color) -> Color32 {
+    let [r, g, b, _] = color.as_rgba_f32();
+    Color32::from_rgb((r * 255.) as u8, (g * 255.) as u8, (b * 255.) as u8)
+}
+
+/// Slots of a palette, followed by its first gradient
+fn palette_swatches(ui: &mut Ui, palette: &Palette) {
+    ui.horizontal(|ui| {
+        ui.spacing_mut().item_spacing.x = 2.;
+        for slot in 0..palette.colors.len() {
+            let (rect, _) = ui.allocate_exact_size(Vec2::new(16., 16.), Sense::hover());
+            ui.painter().rect_filled(rect, 0., color32(palette.color(slot)));
+        }
+        ui.add_space(6.);
+        let steps = 32;
+        for i in 0..steps {
+            let (rect, _) = ui.allocate_exact_size(Vec2::new(3., 16.), Sense::hover());
+            ui.painter().rect_filled(rect, 0., color32(palette.sample(0, i as f32 / (steps - 1) as f32)));
+        }
+    });
+}
+
+pub struct ExtractSettings {
+    files: Vec<PathBuf>,
+    file: Option<PathBuf>,
+    count: usize,
+    method: ExtractMethod,
+    /// Also drive the swirl colour rotation with the extracted palette
+    drive_swirl: bool,
+    /// Extraction running in the background, decoding GIFs and k-means take too long for a frame
+    task: Option<Task<Result<Palette, String>>>,
+    error: Option<String>,
+}
+
+impl Default for ExtractSettings {
+    fn default() -> Self {
+        Self {
+            files: image_files(),
+            file: None,
+            count: 6,
+            method: ExtractMethod::KMeans,
+            drive_swirl: true,
+            task: None,
+            error: None,
+        }
+    }
+}
+
+pub fn palette_gui(
+    mut contexts: EguiContexts,
+    mut library: ResMut<PaletteLibrary>,
+    mut active: ResMut<ActivePalette>,
+    mut extract: Local<ExtractSettings>,
+    mut tunnelgon_colors: ResMut<TunnelgonColors>,
+) {
+    if let Some(result) = extract.task.as_mut().and_then(|task| block_on(future::poll_once(task))) {
+        extract.task = None;
+        match result {
+            Ok(palette) => {
+                let index = library.insert(palette);
+                active.select(&library, index);
+                active.enabled = true;
+                if extract.drive_swirl {
+                    active.drive_swirl = true;
+                }
+                extract.error = None;
+            }
+            Err(e) => { extract.error = Some(e); }
+        }
+    }
+
+    egui::Window::new("Palettes").default_open(false).show(contexts.ctx_mut(), |ui| {
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut active.enabled, "Drive colours")
+                .on_hover_text("Pauses while rainbow or harmony colours are picked");
+            ui.checkbox(&mut active.drive_swirl, "Drive swirl");
+            if ui.button("Reload").clicked() {
+                *library = PaletteLibrary::load();
+            }
+        });
+        ui.label(format!("{}/*.ron", PALETTE_DIR));
+
+        for (index, palette) in library.palettes.iter().enumerate() {
+            ui.horizontal(|ui| {
+                if ui.add_sized([90., 20.], egui::SelectableLabel::new(active.index == index, &palette.name)).clicked() {
+                    active.select(&library, index);
+                }
+                palette_swatches(ui, palette);
+            });
+        }
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.label("Now");
+            palette_swatches(ui, &active.current());
+        });
+        if active.is_fading() {
+            ui.add(egui::ProgressBar::new(active.progress()));
+        }
+        ui.horizontal(|ui| {
+            let slots = active.target().colors.len().max(1) - 1;
+            ui.label("Primary slot");
+            ui.add(egui::DragValue::new(&mut active.primary_slot).clamp_range(0..=slots));
+            ui.label("Secondary slot");
+            ui.add(egui::DragValue::new(&mut active.secondary_slot).clamp_range(0..=slots));
+        });
+        ui.horizontal(|ui| {
+            ui.label("Crossfade beats");
+            ui.add(egui::DragValue::new(&mut active.fade_beats).speed(0.1).clamp_range(0.0..=32.0));
+        });
+        ui.horizontal(|ui| {
+            ui.label("Cycle");
+            for cycle in PaletteCycle::iter() {
+                ui.selectable_value(&mut active.cycle, cycle, format!("{:?}", cycle));
+            }
+            ui.label("every");
+            ui.add(egui::DragValue::new(&mut active.cycle_every).clamp_range(1..=64));
+        });
+
+        egui::CollapsingHeader::new("Tunnelgons").show(ui, |ui| {
+            tunnelgon_colors_gui(ui, &mut tunnelgon_colors);
+        });
+
+        egui::CollapsingHeader::new("Extract from image").show(ui, |ui| {
+            let extract = &mut *extract;
+            // Relative to the assets folder, so images of `assets` and `assets/media` are told apart
+            let file_name = |path: &PathBuf| path.strip_prefix(IMAGE_DIRS[0]).unwrap_or(path).to_string_lossy().to_string();
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_source("extract_file")
+                    .selected_text(extract.file.as_ref().map(file_name).unwrap_or_default())
+                    .show_ui(ui, |ui| {
+                        for file in extract.files.iter() {
+                            ui.selectable_value(&mut extract.file, Some(file.clone()), file_name(file));
+                        }
+                    });
+                if ui.button("Rescan").clicked() {
+                    extract.files = image_files();
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Colours");
+                ui.add(egui::DragValue::new(&mut extract.count).clamp_range(2..=16));
+                for method in ExtractMethod::iter() {
+                    ui.selectable_value(&mut extract.method, method, format!("{:?}", method));
+                }
+            });
+            ui.horizontal(|ui| {
+                let enabled = extract.file.is_some() && extract.task.is_none();
+                if ui.add_enabled(enabled, egui::Button::new("Extract and apply")).clicked() {
+                    if let Some(file) = extract.file.clone() {
+                        let (count, method) = (extract.count, extract.method);
+                        extract.task = Some(AsyncComputeTaskPool::get().spawn(async move {
+                            extract_palette(&file, count, method)
+                        }));
+                    }
+                }
+                ui.checkbox(&mut extract.drive_swirl, "Drive swirl");
+                if extract.task.is_some() {
+                    ui.spinner();
+                }
+            });
+            if let Some(error) = extract.error.as_ref() {
+                ui.label(RichText::new(error).color(Color32::RED));
+            }
+        });
+    });
+}
+
+/// Checkbox to override a colour and its picker
+fn color_override(ui: &mut Ui, color: &mut Option<Color>, default: Color) {
+    let mut enabled = color.is_some();
+    ui.checkbox(&mut enabled, "");
+    let mut col_vals = color.unwrap_or(default).as_rgba_f32();
+    ui.add_enabled_ui(enabled, |ui| {
+        ui.color_edit_button_rgba_unmultiplied(&mut col_vals);
+    });
+    *color = if enabled { Some(Color::rgba_from_array(col_vals)) } else { None };
+}
+
+fn tunnelgon_colors_gui(ui: &mut Ui, colors: &mut TunnelgonColors) {
+    ui.horizontal(|ui| {
+        ui.checkbox(&mut colors.enabled, "Colour tunnelgons");
+        ui.add(egui::Slider::new(&mut colors.amount, 0.0..=1.0).text("Amount"));
+    });
+    ui.add(egui::Slider::new(&mut colors.hue_spread, -180.0..=180.0).text("Hue spread per satellite"));
+    ui.label("Overrides");
+    egui::Grid::new("tunnelgon_color_overrides").show(ui, |ui| {
+        ui.label("");
+        ui.label("Tunnel");
+        ui.label("Lasers");
+        ui.label("Rings");
+        ui.end_row();
+        for hexagon in ALL_HEXAGONS {
+            let current = colors.overrides.get(&hexagon).copied().unwrap_or_default();
+            let mut edited = current;
+            ui.label(format!("{:?}", hexagon));
+            for color in [&mut edited.primary, &mut edited.secondary, &mut edited.accent] {
+                ui.horizontal(|ui| color_override(ui, color, Color::WHITE));
+            }
+            ui.end_row();
+            if edited != current {
+                if edited == TunnelgonColorOverride::default() {
+                    colors.overrides.remove(&hexagon);
+                } else {
+                    colors.overrides.insert(hexagon, edited);
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,31 @@
+use bevy::prelude::ResMut;
+use bevy_egui::{egui, EguiContexts};
+use strum::IntoEnumIterator;
+use crate::pixel_map::{PixelMapSettings, PixelMapSource};
+
+pub fn pixel_map_gui(
+    mut contexts: EguiContexts,
+    mut settings: ResMut<PixelMapSettings>,
+) {
+    egui::Window::new("Pixel Map").default_open(false).show(contexts.ctx_mut(), |ui| {
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut settings.enabled, "Enabled");
+            egui::ComboBox::from_id_source("pixel_map_source")
+                .selected_text(format!("{:?}", settings.source))
+                .show_ui(ui, |ui| {
+                    for source in PixelMapSource::iter() {
+                        ui.selectable_value(&mut settings.source, source, format!("{:?}", source));
+                    }
+                });
+        });
+        if settings.source == PixelMapSource::Image {
+            ui.horizontal(|ui| {
+                ui.label("Image");
+                ui.text_edit_singleline(&mut settings.image_path);
+            });
+        }
+        ui.add(egui::Slider::new(&mut settings.mix, 0.0..=1.0).text("Mix"));
+        ui.add(egui::Slider::new(&mut settings.gain, 0.0..=4.0).text("Gain"));
+        ui.add(egui::Slider::new(&mut settings.blur, 0..=8).text("Blur (LEDs)"));
+    });
+}
@@ -0,0 +1,95 @@
+use bevy::prelude::{Color, Local, Res, ResMut};
+use bevy_egui::{egui, EguiContexts};
+use bevy_egui::egui::{Color32, Stroke};
+use strum::IntoEnumIterator;
+use crate::anims::AnimColors;
+use crate::anims::tube_sequencer::{ColorSlot, SequencerCell, STEP_COUNTS, TubeSequencer};
+
+/// Cell that is painted with a left click
+pub struct SequencerBrush {
+    velocity: f32,
+    color: ColorSlot,
+}
+
+impl Default for SequencerBrush {
+    fn default() -> Self {
+        Self {
+            velocity: 1.,
+            color: ColorSlot::Primary,
+        }
+    }
+}
+
+fn to_color32(color: Color, velocity: f32) -> Color32 {
+    let [r, g, b, _] = color.as_rgba_f32();
+    let v = 0.25 + velocity.clamp(0., 1.) * 0.75;
+    Color32::from_rgb((r * v * 255.) as u8, (g * v * 255.) as u8, (b * v * 255.) as u8)
+}
+
+pub fn tube_sequencer_gui(
+    mut contexts: EguiContexts,
+    mut sequencer: ResMut<TubeSequencer>,
+    colors: Res<AnimColors>,
+    mut brush: Local<SequencerBrush>,
+) {
+    egui::Window::new("Tube Sequencer").default_open(false).show(contexts.ctx_mut(), |ui| {
+        let sequencer = &mut *sequencer;
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut sequencer.enabled, "Enabled");
+            ui.label("Steps");
+            for steps in STEP_COUNTS {
+                if ui.selectable_label(sequencer.pattern.steps == steps, steps.to_string()).clicked() {
+                    sequencer.pattern.set_steps(steps);
+                }
+            }
+            ui.label("Steps per beat");
+            for steps_per_beat in [1, 2, 4, 8] {
+                ui.selectable_value(&mut sequencer.pattern.steps_per_beat, steps_per_beat, steps_per_beat.to_string());
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Brush");
+            ui.add(egui::Slider::new(&mut brush.velocity, 0.05..=1.0).text("Velocity"));
+            for slot in ColorSlot::iter() {
+                ui.selectable_value(&mut brush.color, slot, format!("{:?}", slot));
+            }
+            if ui.button("Clear").clicked() {
+                for row in sequencer.pattern.rows.iter_mut() {
+                    row.cells.fill(SequencerCell::default());
+                }
+            }
+        });
+        ui.label("Left click sets a cell with the brush, right click clears it");
+
+        ui.separator();
+        let cell_size = egui::vec2(18., 18.);
+        egui::Grid::new("tube_sequencer_grid").spacing([2., 2.]).show(ui, |ui| {
+            for row in sequencer.pattern.rows.iter_mut() {
+                ui.label(&row.name);
+                for (step, cell) in row.cells.iter_mut().enumerate() {
+                    let fill = if cell.is_set() {
+                        to_color32(cell.color.color(&colors), cell.velocity)
+                    } else if step % sequencer.pattern.steps_per_beat == 0 {
+                        Color32::from_gray(60)
+                    } else {
+                        Color32::from_gray(35)
+                    };
+                    let stroke = if sequencer.current_step == Some(step) {
+                        Stroke::new(2., Color32::WHITE)
+                    } else {
+                        Stroke::NONE
+                    };
+                    let response = ui.add(egui::Button::new("").fill(fill).stroke(stroke).min_size(cell_size))
+                        .on_hover_text(format!("Step {} {:?} {:.2}", step + 1, cell.color, cell.velocity));
+                    if response.clicked() {
+                        *cell = SequencerCell { velocity: brush.velocity, color: brush.color };
+                    }
+                    if response.secondary_clicked() {
+                        *cell = SequencerCell::default();
+                    }
+                }
+                ui.end_row();
+            }
+        });
+    });
+}
@@ -1,7 +1,8 @@
 use bevy::prelude::{Component, Vec2};
 use bevy::render::view::RenderLayers;
+use serde::{Deserialize, Serialize};
 
-#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash, Serialize, Deserialize)]
 pub enum HexagonDefinition {
     Main,
     A1,
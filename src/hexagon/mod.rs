@@ -10,6 +10,7 @@ use bevy::sprite::{MaterialMesh2dBundle, Mesh2dHandle};
 use bevy_egui::egui::debug_text::print;
 pub use hexagon_definition::HexagonDefinition;
 use crate::hexagon::render::HexagonRenderTarget;
+use crate::physics_hexagon::lights::led_layers::composite_led_layers;
 use crate::physics_hexagon::lights::led_tube::{LedTube, LedTubeLed};
 use crate::propagating_render_layers::PropagatingRenderLayers;
 
@@ -19,7 +20,7 @@ impl Plugin for HexagonPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(Startup, (spawn_hexagons));
         app.add_systems(Update, (spawn_debug_led_tubes, spawn_debug_led_tube_leds));
-        app.add_systems(PostUpdate, (update_debug_led_tube_leds));
+        app.add_systems(PostUpdate, (update_debug_led_tube_leds.after(composite_led_layers)));
         app.init_resource::<HexagonRenderTarget>();
     }
 }
@@ -88,14 +89,13 @@ fn spawn_debug_led_tubes(
     mut materials: ResMut<Assets<ColorMaterial>>,
     query: Query<(&Transform, &LedTube), Added<LedTube>>,
 ) {
-    let mesh = Mesh2dHandle(meshes.add(
-        Rectangle { half_size: Vec2 { x: 173. / 2., y: 4. } }
-    ));
-
     for (transform, led_tube) in query.iter() {
+        let mesh = Mesh2dHandle(meshes.add(
+            Rectangle { half_size: Vec2 { x: (led_tube.get_length() + 3.) / 2., y: 4. } }
+        ));
         commands.spawn((
             MaterialMesh2dBundle {
-                mesh: mesh.clone(),
+                mesh,
                 material: materials.add(Color::rgba(0., 0., 0.0, 1.)),
                 transform: transform.clone(),
                 ..default()
@@ -27,6 +27,7 @@ impl FromWorld for HexagonRenderTarget {
                 sample_count: 1,
                 usage: TextureUsages::TEXTURE_BINDING
                     | TextureUsages::COPY_DST
+                    | TextureUsages::COPY_SRC
                     | TextureUsages::RENDER_ATTACHMENT,
                 view_formats: &[],
             },
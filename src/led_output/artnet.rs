@@ -0,0 +1,121 @@
+//! Art-Net 4 packet encoding (ArtDmx and ArtSync)
+
+pub const ARTNET_PORT: u16 = 6454;
+
+const ARTNET_ID: &[u8; 8] = b"Art-Net\0";
+const OP_DMX: u16 = 0x5000;
+const OP_SYNC: u16 = 0x5200;
+const PROTOCOL_VERSION: u16 = 14;
+
+/// Build an ArtDmx packet. `universe` is the 15 bit port address (net, subnet and universe).
+pub fn artdmx_packet(universe: u16, sequence: u8, data: &[u8]) -> Vec<u8> {
+    // DMX length must be even and between 2 and 512
+    let length = (data.len().min(512) + 1) & !1;
+    let length = length.max(2);
+
+    let mut packet = Vec::with_capacity(18 + length);
+    packet.extend_from_slice(ARTNET_ID);
+    packet.extend_from_slice(&OP_DMX.to_le_bytes());
+    packet.extend_from_slice(&PROTOCOL_VERSION.to_be_bytes());
+    packet.push(sequence);
+    packet.push(0); // Physical
+    packet.push((universe & 0xff) as u8); // SubUni
+    packet.push(((universe >> 8) & 0x7f) as u8); // Net
+    packet.extend_from_slice(&(length as u16).to_be_bytes());
+    packet.extend_from_slice(&data[..data.len().min(512)]);
+    packet.resize(18 + length, 0);
+    packet
+}
+
+/// Build an ArtSync packet, controllers that support it latch all previously received ArtDmx
+pub fn artsync_packet() -> Vec<u8> {
+    let mut packet = Vec::with_capacity(14);
+    packet.extend_from_slice(ARTNET_ID);
+    packet.extend_from_slice(&OP_SYNC.to_le_bytes());
+    packet.extend_from_slice(&PROTOCOL_VERSION.to_be_bytes());
+    packet.push(0); // Aux1
+    packet.push(0); // Aux2
+    packet
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ArtNetPacket<'a> {
+    Dmx { universe: u16, sequence: u8, data: &'a [u8] },
+    Sync,
+}
+
+/// Decode an ArtDmx or ArtSync packet, anything else returns None
+pub fn parse_artnet_packet(packet: &[u8]) -> Option<ArtNetPacket> {
+    if packet.len() < 14 || &packet[0..8] != ARTNET_ID {
+        return None;
+    }
+    let opcode = u16::from_le_bytes([packet[8], packet[9]]);
+    match opcode {
+        OP_DMX => {
+            if packet.len() < 18 { return None; }
+            let universe = packet[14] as u16 | ((packet[15] as u16 & 0x7f) << 8);
+            let length = u16::from_be_bytes([packet[16], packet[17]]) as usize;
+            if packet.len() < 18 + length { return None; }
+            Some(ArtNetPacket::Dmx { universe, sequence: packet[12], data: &packet[18..18 + length] })
+        }
+        OP_SYNC => { Some(ArtNetPacket::Sync) }
+        _ => { None }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn artdmx_header() {
+        let packet = artdmx_packet(0x1234, 7, &[1, 2, 3, 4]);
+        assert_eq!(&packet[0..8], b"Art-Net\0");
+        assert_eq!(&packet[8..10], &[0x00, 0x50]);
+        assert_eq!(&packet[10..12], &[0, 14]);
+        assert_eq!(packet[12], 7);
+        assert_eq!(packet[14], 0x34);
+        assert_eq!(packet[15], 0x12);
+        assert_eq!(&packet[16..18], &[0, 4]);
+        assert_eq!(&packet[18..], &[1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn artdmx_length_is_even_and_limited() {
+        let packet = artdmx_packet(0, 0, &[9, 9, 9]);
+        assert_eq!(&packet[16..18], &[0, 4]);
+        assert_eq!(&packet[18..], &[9, 9, 9, 0]);
+
+        assert_eq!(artdmx_packet(0, 0, &[]).len(), 20);
+
+        let packet = artdmx_packet(0, 0, &[1; 600]);
+        assert_eq!(packet.len(), 18 + 512);
+        assert_eq!(&packet[16..18], &[2, 0]);
+    }
+
+    #[test]
+    fn artdmx_roundtrip() {
+        let data: Vec<u8> = (0..170 * 3).map(|i| i as u8).collect();
+        let packet = artdmx_packet(0x7fff, 200, &data);
+        assert_eq!(parse_artnet_packet(&packet), Some(ArtNetPacket::Dmx { universe: 0x7fff, sequence: 200, data: &data }));
+    }
+
+    #[test]
+    fn artsync() {
+        let packet = artsync_packet();
+        assert_eq!(packet.len(), 14);
+        assert_eq!(&packet[8..10], &[0x00, 0x52]);
+        assert_eq!(parse_artnet_packet(&packet), Some(ArtNetPacket::Sync));
+    }
+
+    #[test]
+    fn rejects_foreign_packets() {
+        assert_eq!(parse_artnet_packet(b"Art-Net"), None);
+        let mut packet = artdmx_packet(1, 0, &[1, 2]);
+        packet[0] = b'X';
+        assert_eq!(parse_artnet_packet(&packet), None);
+        let mut packet = artdmx_packet(1, 0, &[1, 2]);
+        packet.truncate(19);
+        assert_eq!(parse_artnet_packet(&packet), None);
+    }
+}
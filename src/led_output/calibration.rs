@@ -0,0 +1,224 @@
+//! Calibration of the LED colours before they are sent: white balance per tube, global
+//! brightness, gamma per controller and a current limiter for the power supply.
+
+use std::collections::HashMap;
+use bevy::prelude::*;
+use crate::led_output::{ColorOrder, LedFrame, LedOutputConfig};
+use crate::physics_hexagon::lights::led_tube::TubeIndex;
+
+const GAMMA_LUT_SIZE: usize = 4096;
+
+/// Maps linear intensity to PWM duty for one gamma value
+pub struct GammaLut {
+    gamma: f32,
+    table: Vec<f32>,
+}
+
+impl GammaLut {
+    pub fn new(gamma: f32) -> Self {
+        let table = (0..GAMMA_LUT_SIZE)
+            .map(|i| (i as f32 / (GAMMA_LUT_SIZE - 1) as f32).powf(gamma))
+            .collect();
+        Self { gamma, table }
+    }
+
+    pub fn gamma(&self) -> f32 {
+        self.gamma
+    }
+
+    pub fn apply(&self, value: f32) -> f32 {
+        self.table[(value.clamp(0., 1.) * (GAMMA_LUT_SIZE - 1) as f32).round() as usize]
+    }
+}
+
+#[derive(Resource)]
+pub struct LedCalibration {
+    pub brightness: f32,
+    /// RGB trims per tube, missing tubes are untrimmed
+    pub white_balance: HashMap<TubeIndex, [f32; 3]>,
+    pub limiter_enabled: bool,
+    /// Current the power supply can deliver to the LEDs in mA
+    pub current_budget: f32,
+    /// Current of one channel of one LED at full duty in mA, the white channel of RGBW included
+    pub channel_current: f32,
+    /// Current of one LED when dark in mA
+    pub idle_current: f32,
+    /// Time constant in seconds when the limiter dims down
+    pub limiter_attack: f32,
+    /// Time constant in seconds when the limiter recovers
+    pub limiter_release: f32,
+    /// Estimated current of the last frame before limiting in mA
+    pub estimated_current: f32,
+    /// Brightness scale applied by the limiter
+    pub limiter_scale: f32,
+    /// Gamma tables per controller, rebuilt when the controller gamma changes
+    luts: Vec<GammaLut>,
+}
+
+impl Default for LedCalibration {
+    fn default() -> Self {
+        Self {
+            brightness: 1.,
+            white_balance: HashMap::new(),
+            limiter_enabled: true,
+            current_budget: 10000.,
+            channel_current: 20.,
+            idle_current: 1.,
+            limiter_attack: 0.05,
+            limiter_release: 1.,
+            estimated_current: 0.,
+            limiter_scale: 1.,
+            luts: vec![],
+        }
+    }
+}
+
+impl LedCalibration {
+    pub fn white_balance(&self, tube: TubeIndex) -> [f32; 3] {
+        self.white_balance.get(&tube).cloned().unwrap_or([1.; 3])
+    }
+
+    /// Linear intensity of a LED after white balance and brightness
+    pub fn linear(&self, tube: TubeIndex, color: Color) -> [f32; 3] {
+        let [r, g, b, _] = color.as_rgba_f32();
+        let [wr, wg, wb] = self.white_balance(tube);
+        [
+            (r * wr * self.brightness).clamp(0., 1.),
+            (g * wg * self.brightness).clamp(0., 1.),
+            (b * wb * self.brightness).clamp(0., 1.),
+        ]
+    }
+
+    /// PWM duty of a LED on a controller before limiting
+    fn duty(&self, tube: TubeIndex, controller_index: usize, color: Color) -> [f32; 3] {
+        let linear = self.linear(tube, color);
+        match self.luts.get(controller_index) {
+            Some(lut) => { linear.map(|v| lut.apply(v)) }
+            None => { linear }
+        }
+    }
+
+    /// Final PWM duty of a LED on a controller, including the limiter
+    pub fn output(&self, tube: TubeIndex, controller_index: usize, color: Color) -> [f32; 3] {
+        let scale = if self.limiter_enabled { self.limiter_scale } else { 1. };
+        self.duty(tube, controller_index, color).map(|v| v * scale)
+    }
+
+    /// Move the limiter scale towards the budget, with the attack time constant when dimming
+    /// and the release time constant when recovering
+    fn update_limiter(&mut self, idle_current: f32, dynamic_current: f32, delta_seconds: f32) {
+        let target = if dynamic_current > 0. {
+            ((self.current_budget - idle_current) / dynamic_current).clamp(0., 1.)
+        } else {
+            1.
+        };
+        let time_constant = if target < self.limiter_scale {
+            self.limiter_attack
+        } else {
+            self.limiter_release
+        };
+        let alpha = (delta_seconds / time_constant.max(0.001)).min(1.);
+        self.limiter_scale += (target - self.limiter_scale) * alpha;
+    }
+}
+
+/// Keep the gamma tables in sync with the controllers and estimate the current of the frame.
+/// The limiter scale follows the budget with a PT1 so the dimming isn't visible as a jump.
+pub fn update_led_calibration(
+    config: Res<LedOutputConfig>,
+    frame: Res<LedFrame>,
+    mut calibration: ResMut<LedCalibration>,
+    time: Res<Time<Real>>,
+) {
+    calibration.luts.truncate(config.controllers.len());
+    for (i, controller) in config.controllers.iter().enumerate() {
+        match calibration.luts.get(i) {
+            Some(lut) if lut.gamma() == controller.gamma => {}
+            Some(_) => { calibration.luts[i] = GammaLut::new(controller.gamma) }
+            None => { calibration.luts.push(GammaLut::new(controller.gamma)) }
+        }
+    }
+
+    let mut idle_current = 0.;
+    let mut dynamic_current = 0.;
+    for mapping in config.tubes.iter() {
+        let Some(colors) = frame.tubes.get(&mapping.tube) else { continue; };
+        let color_order = config.controllers.get(mapping.controller).map_or(ColorOrder::Rgb, |c| c.color_order);
+        for color in colors {
+            let duty = calibration.duty(mapping.tube, mapping.controller, *color);
+            dynamic_current += color_order.channel_sum(duty) * calibration.channel_current;
+            idle_current += calibration.idle_current;
+        }
+    }
+    calibration.estimated_current = idle_current + dynamic_current;
+    calibration.update_limiter(idle_current, dynamic_current, time.delta_seconds());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gamma_lut_keeps_endpoints() {
+        for gamma in [1., 2.2, 2.8] {
+            let lut = GammaLut::new(gamma);
+            assert_eq!(lut.apply(0.), 0.);
+            assert_eq!(lut.apply(1.), 1.);
+            // Out of range values are clamped
+            assert_eq!(lut.apply(-0.5), 0.);
+            assert_eq!(lut.apply(1.5), 1.);
+        }
+    }
+
+    #[test]
+    fn gamma_lut_is_monotonic() {
+        let lut = GammaLut::new(2.2);
+        let values: Vec<f32> = (0..=1000).map(|i| lut.apply(i as f32 / 1000.)).collect();
+        assert!(values.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert!(lut.apply(0.5) < 0.5);
+    }
+
+    #[test]
+    fn limiter_attacks_fast_and_releases_slowly() {
+        let mut calibration = LedCalibration {
+            current_budget: 1000.,
+            limiter_attack: 0.05,
+            limiter_release: 1.,
+            ..default()
+        };
+        // Twice the budget settles at half brightness within a few attack time constants
+        for _ in 0..30 {
+            calibration.update_limiter(0., 2000., 0.01);
+        }
+        assert!((calibration.limiter_scale - 0.5).abs() < 0.01);
+        assert!(calibration.limiter_scale * 2000. <= 1000. + 20.);
+
+        // The same time within budget only recovers a fraction of the way
+        for _ in 0..30 {
+            calibration.update_limiter(0., 500., 0.01);
+        }
+        assert!(calibration.limiter_scale > 0.5 && calibration.limiter_scale < 0.7);
+
+        // Eventually recovers fully
+        for _ in 0..1000 {
+            calibration.update_limiter(0., 500., 0.01);
+        }
+        assert!((calibration.limiter_scale - 1.).abs() < 0.001);
+    }
+
+    #[test]
+    fn limiter_subtracts_idle_current_from_budget() {
+        let mut calibration = LedCalibration { current_budget: 1000., limiter_attack: 0., ..default() };
+        calibration.update_limiter(200., 1600., 0.01);
+        assert!((calibration.limiter_scale - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn rgbw_current_counts_white_channel_once() {
+        // Full white is only the white channel
+        assert_eq!(ColorOrder::Rgbw.channel_sum([1., 1., 1.]), 1.);
+        assert_eq!(ColorOrder::Rgb.channel_sum([1., 1., 1.]), 3.);
+        // Orange: 0.5 white plus 0.5 red and 0.2 green
+        assert!((ColorOrder::Grbw.channel_sum([1., 0.7, 0.5]) - 1.2).abs() < 1e-6);
+    }
+}
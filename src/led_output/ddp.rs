@@ -0,0 +1,115 @@
+//! DDP (Distributed Display Protocol) packet encoding, as used by WLED
+
+pub const DDP_PORT: u16 = 4048;
+
+const DDP_VERSION_1: u8 = 0x40;
+const DDP_PUSH: u8 = 0x01;
+const DDP_TYPE_RGB24: u8 = 0x0b;
+const DDP_TYPE_RGBW32: u8 = 0x1b;
+const DDP_DEFAULT_OUTPUT: u8 = 0x01;
+const DDP_HEADER_LEN: usize = 10;
+/// Keeps packets below a 1500 byte MTU, divisible by 3 and 4 so pixels never get split
+pub const DDP_MAX_DATA_LEN: usize = 1440;
+
+/// Split a frame into DDP packets. The last packet has the push flag set so the receiver
+/// displays the whole frame at once.
+pub fn ddp_packets(data: &[u8], channels: usize, sequence: u8) -> Vec<Vec<u8>> {
+    let data_type = if channels == 4 { DDP_TYPE_RGBW32 } else { DDP_TYPE_RGB24 };
+    let chunk_count = (data.len() + DDP_MAX_DATA_LEN - 1) / DDP_MAX_DATA_LEN;
+
+    data.chunks(DDP_MAX_DATA_LEN)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let offset = (i * DDP_MAX_DATA_LEN) as u32;
+            let mut flags = DDP_VERSION_1;
+            if i + 1 == chunk_count {
+                flags |= DDP_PUSH;
+            }
+
+            let mut packet = Vec::with_capacity(DDP_HEADER_LEN + chunk.len());
+            packet.push(flags);
+            packet.push(sequence & 0x0f);
+            packet.push(data_type);
+            packet.push(DDP_DEFAULT_OUTPUT);
+            packet.extend_from_slice(&offset.to_be_bytes());
+            packet.extend_from_slice(&(chunk.len() as u16).to_be_bytes());
+            packet.extend_from_slice(chunk);
+            packet
+        })
+        .collect()
+}
+
+#[derive(Debug, PartialEq)]
+pub struct DdpPacket<'a> {
+    pub sequence: u8,
+    pub offset: u32,
+    pub push: bool,
+    pub data: &'a [u8],
+}
+
+/// Decode a DDP data packet
+pub fn parse_ddp_packet(packet: &[u8]) -> Option<DdpPacket> {
+    if packet.len() < DDP_HEADER_LEN || packet[0] & 0xc0 != DDP_VERSION_1 {
+        return None;
+    }
+    let offset = u32::from_be_bytes([packet[4], packet[5], packet[6], packet[7]]);
+    let length = u16::from_be_bytes([packet[8], packet[9]]) as usize;
+    if packet.len() < DDP_HEADER_LEN + length {
+        return None;
+    }
+    Some(DdpPacket {
+        sequence: packet[1] & 0x0f,
+        offset,
+        push: packet[0] & DDP_PUSH != 0,
+        data: &packet[DDP_HEADER_LEN..DDP_HEADER_LEN + length],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_packet_header() {
+        let packets = ddp_packets(&[1, 2, 3, 4, 5, 6], 3, 0x13);
+        assert_eq!(packets.len(), 1);
+        assert_eq!(&packets[0][..DDP_HEADER_LEN], &[0x41, 0x03, DDP_TYPE_RGB24, DDP_DEFAULT_OUTPUT, 0, 0, 0, 0, 0, 6]);
+        assert_eq!(&packets[0][DDP_HEADER_LEN..], &[1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn rgbw_data_type() {
+        let packets = ddp_packets(&[0; 8], 4, 0);
+        assert_eq!(packets[0][2], DDP_TYPE_RGBW32);
+    }
+
+    #[test]
+    fn frame_is_split_and_pushed_once() {
+        let data: Vec<u8> = (0..DDP_MAX_DATA_LEN * 2 + 30).map(|i| i as u8).collect();
+        let packets = ddp_packets(&data, 3, 5);
+        let parsed: Vec<DdpPacket> = packets.iter().map(|p| parse_ddp_packet(p).unwrap()).collect();
+        assert_eq!(parsed.len(), 3);
+        assert_eq!(parsed.iter().map(|p| p.offset).collect::<Vec<_>>(), vec![0, DDP_MAX_DATA_LEN as u32, 2 * DDP_MAX_DATA_LEN as u32]);
+        assert_eq!(parsed.iter().map(|p| p.push).collect::<Vec<_>>(), vec![false, false, true]);
+        assert!(parsed.iter().all(|p| p.sequence == 5));
+        let joined: Vec<u8> = parsed.iter().flat_map(|p| p.data.iter().copied()).collect();
+        assert_eq!(joined, data);
+    }
+
+    #[test]
+    fn max_data_len_keeps_pixels_whole() {
+        assert_eq!(DDP_MAX_DATA_LEN % 3, 0);
+        assert_eq!(DDP_MAX_DATA_LEN % 4, 0);
+    }
+
+    #[test]
+    fn rejects_foreign_packets() {
+        assert_eq!(parse_ddp_packet(&[0x41, 0, 0, 0]), None);
+        let mut packet = ddp_packets(&[1, 2, 3], 3, 0).remove(0);
+        packet[0] = 0x81;
+        assert_eq!(parse_ddp_packet(&packet), None);
+        packet[0] = 0x41;
+        packet.pop();
+        assert_eq!(parse_ddp_packet(&packet), None);
+    }
+}
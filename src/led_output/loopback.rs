@@ -0,0 +1,141 @@
+//! Loopback receiver for the LED output. When enabled, every packet is also sent to a local UDP
+//! socket per controller, decoded again and compared against the data that was meant to be sent.
+
+use std::collections::{HashMap, VecDeque};
+use std::net::{SocketAddr, UdpSocket};
+use bevy::prelude::{error, ResMut, Resource};
+use crate::led_output::artnet::{ArtNetPacket, parse_artnet_packet};
+use crate::led_output::ddp::parse_ddp_packet;
+use crate::led_output::LedProtocol;
+use crate::led_output::sacn::{parse_sacn_packet, SacnPacket};
+
+/// How many sent universes are remembered for verification
+const EXPECTED_CAPACITY: usize = 256;
+
+struct ExpectedUniverse {
+    /// Index into `LedOutputConfig::controllers`
+    controller: usize,
+    protocol: LedProtocol,
+    /// Universe, or the byte offset of the packet for DDP
+    universe: u32,
+    sequence: u8,
+    data: Vec<u8>,
+}
+
+#[derive(Default, Clone, Copy)]
+pub struct LoopbackStats {
+    pub received: u64,
+    pub verified: u64,
+    pub mismatched: u64,
+    pub unexpected: u64,
+    pub syncs: u64,
+}
+
+#[derive(Resource, Default)]
+pub struct LedLoopback {
+    pub enabled: bool,
+    /// One socket per controller, so controllers sending the same universes don't mix
+    sockets: HashMap<usize, UdpSocket>,
+    expected: VecDeque<ExpectedUniverse>,
+    pub stats: LoopbackStats,
+}
+
+impl LedLoopback {
+    /// Local address the packets of a controller should be mirrored to, None if the loopback is
+    /// disabled
+    pub fn address(&mut self, controller: usize) -> Option<SocketAddr> {
+        if !self.enabled {
+            self.sockets.clear();
+            return None;
+        }
+        if !self.sockets.contains_key(&controller) {
+            match UdpSocket::bind("127.0.0.1:0").and_then(|socket| {
+                socket.set_nonblocking(true)?;
+                Ok(socket)
+            }) {
+                Ok(socket) => { self.sockets.insert(controller, socket); }
+                Err(e) => {
+                    error!("Couldn't open LED loopback socket: {}", e);
+                    self.enabled = false;
+                    return None;
+                }
+            }
+        }
+        self.sockets.get(&controller).and_then(|socket| socket.local_addr().ok())
+    }
+
+    /// Remember data that is about to be sent so the received packet can be checked against it
+    pub fn expect(&mut self, controller: usize, protocol: LedProtocol, universe: u32, sequence: u8, data: Vec<u8>) {
+        if !self.enabled { return; }
+        if self.expected.len() >= EXPECTED_CAPACITY {
+            self.expected.pop_front();
+        }
+        self.expected.push_back(ExpectedUniverse { controller, protocol, universe, sequence, data });
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = LoopbackStats::default();
+    }
+
+    fn verify(&mut self, controller: usize, protocol: LedProtocol, universe: u32, sequence: u8, data: &[u8]) {
+        let Some(position) = self.expected.iter().position(|expected| {
+            expected.controller == controller
+                && expected.protocol == protocol && expected.universe == universe && expected.sequence == sequence
+        }) else {
+            self.stats.unexpected += 1;
+            return;
+        };
+        let expected = self.expected.remove(position).unwrap();
+
+        // ArtDmx pads odd lengths with a trailing zero
+        let matches = data.len() >= expected.data.len()
+            && data[..expected.data.len()] == expected.data[..]
+            && data[expected.data.len()..].iter().all(|v| *v == 0);
+
+        if matches {
+            self.stats.verified += 1;
+        } else {
+            self.stats.mismatched += 1;
+            error!("LED loopback mismatch on controller {} {:?} universe {} seq {}: expected {} bytes, got {} bytes",
+                controller, protocol, universe, sequence, expected.data.len(), data.len());
+        }
+    }
+}
+
+pub fn verify_loopback_packets(
+    mut loopback: ResMut<LedLoopback>,
+) {
+    if !loopback.enabled { return; }
+
+    let mut buf = [0u8; 1500];
+    let mut received: Vec<(usize, Vec<u8>)> = vec![];
+    for (controller, socket) in loopback.sockets.iter() {
+        while let Ok((len, _)) = socket.recv_from(&mut buf) {
+            received.push((*controller, buf[..len].to_vec()));
+        }
+    }
+
+    for (controller, packet) in received {
+        loopback.stats.received += 1;
+        if let Some(artnet_packet) = parse_artnet_packet(&packet) {
+            match artnet_packet {
+                ArtNetPacket::Dmx { universe, sequence, data } => {
+                    loopback.verify(controller, LedProtocol::ArtNet, universe as u32, sequence, data);
+                }
+                ArtNetPacket::Sync => { loopback.stats.syncs += 1; }
+            }
+        } else if let Some(sacn_packet) = parse_sacn_packet(&packet) {
+            match sacn_packet {
+                SacnPacket::Data { universe, sequence, data, .. } => {
+                    loopback.verify(controller, LedProtocol::Sacn, universe as u32, sequence, data);
+                }
+                SacnPacket::Sync { .. } => { loopback.stats.syncs += 1; }
+            }
+        } else if let Some(ddp_packet) = parse_ddp_packet(&packet) {
+            loopback.verify(controller, LedProtocol::Ddp, ddp_packet.offset, ddp_packet.sequence, ddp_packet.data);
+        } else {
+            loopback.stats.unexpected += 1;
+            error!("LED loopback received an undecodable packet of {} bytes", packet.len());
+        }
+    }
+}
@@ -0,0 +1,567 @@
+//! Send the LED tube colours to the physical tube controllers
+
+pub mod artnet;
+pub mod sacn;
+pub mod ddp;
+pub mod loopback;
+pub mod calibration;
+pub mod recorder;
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::ops::RangeInclusive;
+use bevy::prelude::*;
+use rand::{Rng, thread_rng};
+use serde::{Deserialize, Serialize};
+use strum_macros::EnumIter;
+use crate::led_output::artnet::{ARTNET_PORT, artdmx_packet, artsync_packet};
+use crate::led_output::calibration::{LedCalibration, update_led_calibration};
+use crate::led_output::ddp::{DDP_MAX_DATA_LEN, DDP_PORT, ddp_packets};
+use crate::led_output::loopback::{LedLoopback, verify_loopback_packets};
+use crate::led_output::recorder::{LedRecorder, record_led_frame};
+use crate::led_output::sacn::{e131_data_packet, e131_sync_packet, SACN_PORT};
+use crate::physics_hexagon::lights::led_layers::composite_led_layers;
+use crate::physics_hexagon::lights::led_tube::{LedTube, LedTubeLed, TubeIndex};
+use crate::physics_hexagon::lights::tube_layout::TubeLayout;
+
+const SOURCE_NAME: &str = "vjpyree";
+pub const OUTPUT_CONFIG_PATH: &str = "assets/led_output.ron";
+/// Channels of a DMX universe
+pub const DMX_CHANNELS: usize = 512;
+
+pub struct LedOutputPlugin;
+
+impl Plugin for LedOutputPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LedOutputConfig>();
+        app.init_resource::<LedFrame>();
+        app.init_resource::<LedOutputState>();
+        app.init_resource::<LedLoopback>();
+        app.init_resource::<LedCalibration>();
+        app.init_resource::<LedRecorder>();
+        app.add_systems(PostUpdate, (
+            collect_led_frame.after(composite_led_layers),
+            record_led_frame.after(collect_led_frame),
+        ));
+        app.add_systems(Last, (
+            update_led_calibration.before(send_led_output),
+            send_led_output,
+            verify_loopback_packets.after(send_led_output),
+        ));
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, EnumIter, Serialize, Deserialize)]
+pub enum LedProtocol {
+    ArtNet,
+    Sacn,
+    Ddp,
+}
+
+impl LedProtocol {
+    pub fn port(&self) -> u16 {
+        match self {
+            LedProtocol::ArtNet => { ARTNET_PORT }
+            LedProtocol::Sacn => { SACN_PORT }
+            LedProtocol::Ddp => { DDP_PORT }
+        }
+    }
+
+    /// Valid universes, sACN receivers ignore universe 0. DDP has no universes.
+    pub fn universes(&self) -> RangeInclusive<u16> {
+        match self {
+            LedProtocol::ArtNet | LedProtocol::Ddp => { 0..=32767 }
+            LedProtocol::Sacn => { 1..=63999 }
+        }
+    }
+}
+
+/// Channel order of the pixels on the strip. The RGBW orders extract the common white part of
+/// the colour into the white channel.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, EnumIter, Serialize, Deserialize)]
+pub enum ColorOrder {
+    Rgb,
+    Rbg,
+    Grb,
+    Gbr,
+    Brg,
+    Bgr,
+    Rgbw,
+    Grbw,
+}
+
+impl ColorOrder {
+    pub fn channels(&self) -> usize {
+        match self {
+            ColorOrder::Rgbw | ColorOrder::Grbw => { 4 }
+            _ => { 3 }
+        }
+    }
+
+    /// Append the pixel to `out` in this channel order
+    pub fn write(&self, rgb: [u8; 3], out: &mut Vec<u8>) {
+        let [r, g, b] = rgb;
+        let w = r.min(g).min(b);
+        match self {
+            ColorOrder::Rgb => { out.extend_from_slice(&[r, g, b]) }
+            ColorOrder::Rbg => { out.extend_from_slice(&[r, b, g]) }
+            ColorOrder::Grb => { out.extend_from_slice(&[g, r, b]) }
+            ColorOrder::Gbr => { out.extend_from_slice(&[g, b, r]) }
+            ColorOrder::Brg => { out.extend_from_slice(&[b, r, g]) }
+            ColorOrder::Bgr => { out.extend_from_slice(&[b, g, r]) }
+            ColorOrder::Rgbw => { out.extend_from_slice(&[r - w, g - w, b - w, w]) }
+            ColorOrder::Grbw => { out.extend_from_slice(&[g - w, r - w, b - w, w]) }
+        }
+    }
+
+    /// Sum of the channel duties of a pixel as `write` splits it, the white channel included
+    pub fn channel_sum(&self, duty: [f32; 3]) -> f32 {
+        let [r, g, b] = duty;
+        match self {
+            ColorOrder::Rgbw | ColorOrder::Grbw => { r + g + b - 2. * r.min(g).min(b) }
+            _ => { r + g + b }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LedController {
+    pub name: String,
+    /// IP address of the controller, parsed on every send so it can be edited live
+    pub address: String,
+    pub protocol: LedProtocol,
+    /// Universe of the first pixel, the following pixels continue in the next universes.
+    /// Clamped to `LedProtocol::universes`, unused for DDP, which addresses pixels by byte offset.
+    pub start_universe: u16,
+    /// Pixels per universe, capped to what fits into 512 channels in the colour order so pixels
+    /// never straddle two universes
+    pub pixels_per_universe: usize,
+    pub color_order: ColorOrder,
+    /// Gamma of the strip, linear intensity is raised to this power
+    pub gamma: f32,
+    /// Limit how often this controller receives frames, 0 sends at the output frame rate
+    pub max_frame_rate: f32,
+}
+
+impl Default for LedController {
+    fn default() -> Self {
+        Self {
+            name: "Tubes".to_owned(),
+            address: "2.0.0.10".to_owned(),
+            protocol: LedProtocol::ArtNet,
+            start_universe: 0,
+            pixels_per_universe: 170,
+            color_order: ColorOrder::Rgb,
+            gamma: 2.2,
+            max_frame_rate: 0.,
+        }
+    }
+}
+
+impl LedController {
+    /// Pixels that fit into one universe in the colour order of the controller
+    pub fn max_pixels_per_universe(&self) -> usize {
+        DMX_CHANNELS / self.color_order.channels()
+    }
+
+    /// `pixels_per_universe` limited to a full universe
+    pub fn universe_pixels(&self) -> usize {
+        self.pixels_per_universe.clamp(1, self.max_pixels_per_universe())
+    }
+
+    /// `start_universe` limited to the universes of the protocol
+    pub fn first_universe(&self) -> u16 {
+        let universes = self.protocol.universes();
+        self.start_universe.clamp(*universes.start(), *universes.end())
+    }
+
+    /// Universe and DMX channel (1 based) of a pixel on this controller
+    pub fn universe_and_channel(&self, pixel: usize) -> (u16, usize) {
+        let pixels_per_universe = self.universe_pixels();
+        (
+            self.first_universe() + (pixel / pixels_per_universe) as u16,
+            (pixel % pixels_per_universe) * self.color_order.channels() + 1,
+        )
+    }
+}
+
+/// Where the LEDs of one tube end up on a controller
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TubeMapping {
+    pub tube: TubeIndex,
+    /// Index into `LedOutputConfig::controllers`
+    pub controller: usize,
+    /// Pixel index of the first LED of the tube on the controller
+    pub pixel_offset: usize,
+    /// LED 0 of the tube is the last pixel of the tube on the strip
+    pub reversed: bool,
+}
+
+#[derive(Resource)]
+pub struct LedOutputConfig {
+    pub enabled: bool,
+    pub frame_rate: f32,
+    /// Send ArtSync / E1.31 sync packets after every frame
+    pub sync: bool,
+    pub sacn_sync_universe: u16,
+    pub controllers: Vec<LedController>,
+    pub tubes: Vec<TubeMapping>,
+}
+
+/// Controllers and tube mapping as stored in `OUTPUT_CONFIG_PATH`
+#[derive(Serialize, Deserialize)]
+struct LedOutputFile {
+    controllers: Vec<LedController>,
+    /// Empty chains all tubes on the first controller in layout order
+    #[serde(default)]
+    tubes: Vec<TubeMapping>,
+}
+
+/// All tubes chained on the first controller in layout order
+fn chained_tubes(layout: &TubeLayout) -> Vec<TubeMapping> {
+    let mut pixel_offset = 0;
+    layout.tubes.iter().map(|tube| {
+        let mapping = TubeMapping {
+            tube: tube.index(),
+            controller: 0,
+            pixel_offset,
+            reversed: false,
+        };
+        pixel_offset += tube.led_count;
+        mapping
+    }).collect()
+}
+
+impl FromWorld for LedOutputConfig {
+    fn from_world(world: &mut World) -> Self {
+        let layout = world.get_resource_or_insert_with(TubeLayout::load);
+        let mut config = Self {
+            enabled: false,
+            frame_rate: 44.,
+            sync: true,
+            sacn_sync_universe: 999,
+            controllers: vec![LedController::default()],
+            tubes: chained_tubes(&layout),
+        };
+        match config.load(&layout) {
+            Ok(()) => { info!("Loaded {} LED controllers from {}", config.controllers.len(), OUTPUT_CONFIG_PATH); }
+            Err(e) => { error!("Couldn't load LED output config {}, using a single Art-Net controller: {}", OUTPUT_CONFIG_PATH, e); }
+        }
+        config
+    }
+}
+
+impl LedOutputConfig {
+    /// Replace controllers and tube mapping with the ones of the config file
+    pub fn load(&mut self, layout: &TubeLayout) -> Result<(), String> {
+        let content = std::fs::read_to_string(OUTPUT_CONFIG_PATH).map_err(|e| e.to_string())?;
+        let file = ron::from_str::<LedOutputFile>(&content).map_err(|e| e.to_string())?;
+        if file.controllers.is_empty() {
+            return Err("no controllers".to_string());
+        }
+        self.tubes = if file.tubes.is_empty() { chained_tubes(layout) } else { file.tubes };
+        self.controllers = file.controllers;
+        Ok(())
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let file = LedOutputFile { controllers: self.controllers.clone(), tubes: self.tubes.clone() };
+        let content = ron::ser::to_string_pretty(&file, ron::ser::PrettyConfig::default()).map_err(|e| e.to_string())?;
+        std::fs::write(OUTPUT_CONFIG_PATH, content).map_err(|e| e.to_string())
+    }
+
+    /// Remove a controller, its tubes move to the first controller
+    pub fn remove_controller(&mut self, index: usize) {
+        if self.controllers.len() <= 1 || index >= self.controllers.len() { return; }
+        self.controllers.remove(index);
+        for mapping in self.tubes.iter_mut() {
+            if mapping.controller == index {
+                mapping.controller = 0;
+            } else if mapping.controller > index {
+                mapping.controller -= 1;
+            }
+        }
+    }
+}
+
+/// Composited colours of every tube, ordered by LED index. Shared by all LED outputs.
+#[derive(Resource, Default)]
+pub struct LedFrame {
+    pub tubes: HashMap<TubeIndex, Vec<Color>>,
+}
+
+pub fn collect_led_frame(
+    mut frame: ResMut<LedFrame>,
+    tube_query: Query<(&LedTube, &Children)>,
+    led_query: Query<&LedTubeLed>,
+) {
+    frame.tubes.clear();
+    for (led_tube, children) in tube_query.iter() {
+        let mut leds: Vec<&LedTubeLed> = children.iter()
+            .filter_map(|child| led_query.get(*child).ok())
+            .collect();
+        leds.sort_by_key(|ltl| ltl.get_index());
+        frame.tubes.insert(led_tube.get_tube_index(), leds.iter().map(|ltl| ltl.color).collect());
+    }
+}
+
+/// Calibrated duty of all pixels of one controller, unmapped pixels stay black
+pub fn controller_pixels(config: &LedOutputConfig, frame: &LedFrame, calibration: &LedCalibration, controller_index: usize) -> Vec<[f32; 3]> {
+    let mut pixels: Vec<[f32; 3]> = vec![];
+    for mapping in config.tubes.iter().filter(|mapping| mapping.controller == controller_index) {
+        let Some(colors) = frame.tubes.get(&mapping.tube) else { continue; };
+        let end = mapping.pixel_offset + colors.len();
+        if pixels.len() < end {
+            pixels.resize(end, [0.; 3]);
+        }
+        for (i, color) in colors.iter().enumerate() {
+            let pixel = if mapping.reversed { colors.len() - 1 - i } else { i };
+            pixels[mapping.pixel_offset + pixel] = calibration.output(mapping.tube, controller_index, *color);
+        }
+    }
+    pixels
+}
+
+pub fn duty_to_rgb8(duty: [f32; 3]) -> [u8; 3] {
+    let [r, g, b] = duty;
+    [
+        (r.clamp(0., 1.) * 255.).round() as u8,
+        (g.clamp(0., 1.) * 255.).round() as u8,
+        (b.clamp(0., 1.) * 255.).round() as u8,
+    ]
+}
+
+/// Encode pixels as channel data in the controller's colour order
+pub fn pixel_data(controller: &LedController, pixels: &[[f32; 3]]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(pixels.len() * controller.color_order.channels());
+    for duty in pixels {
+        controller.color_order.write(duty_to_rgb8(*duty), &mut data);
+    }
+    data
+}
+
+/// Split the pixels of a controller into DMX data per universe
+pub fn universe_data(controller: &LedController, pixels: &[[f32; 3]]) -> Vec<(u16, Vec<u8>)> {
+    pixels.chunks(controller.universe_pixels())
+        .enumerate()
+        .map(|(i, chunk)| {
+            (controller.first_universe() + i as u16, pixel_data(controller, chunk))
+        })
+        .collect()
+}
+
+#[derive(Resource)]
+pub struct LedOutputState {
+    socket: Option<UdpSocket>,
+    accum: f32,
+    sequences: HashMap<(usize, u16), u8>,
+    sync_sequence: u8,
+    /// Time of the last frame sent to each controller, for the per controller frame rate limit
+    last_sent: HashMap<usize, f32>,
+    cid: [u8; 16],
+    pub packets_sent: u64,
+    pub last_error: Option<String>,
+}
+
+impl Default for LedOutputState {
+    fn default() -> Self {
+        let socket = UdpSocket::bind("0.0.0.0:0")
+            .and_then(|socket| {
+                socket.set_nonblocking(true)?;
+                socket.set_broadcast(true)?;
+                Ok(socket)
+            });
+        let (socket, last_error) = match socket {
+            Ok(socket) => { (Some(socket), None) }
+            Err(e) => {
+                error!("Couldn't open LED output socket: {}", e);
+                (None, Some(e.to_string()))
+            }
+        };
+
+        Self {
+            socket,
+            accum: 0.,
+            sequences: HashMap::new(),
+            sync_sequence: 0,
+            last_sent: HashMap::new(),
+            cid: thread_rng().gen(),
+            packets_sent: 0,
+            last_error,
+        }
+    }
+}
+
+impl LedOutputState {
+    fn next_sequence(&mut self, controller_index: usize, universe: u16, protocol: LedProtocol) -> u8 {
+        let sequence = self.sequences.entry((controller_index, universe)).or_insert(0);
+        *sequence = match protocol {
+            // Art-Net sequence 0 disables sequencing on the receiver
+            LedProtocol::ArtNet => { if *sequence == 255 { 1 } else { *sequence + 1 } }
+            LedProtocol::Sacn => { sequence.wrapping_add(1) }
+            // DDP only has 4 bits of sequence number, 0 means unused
+            LedProtocol::Ddp => { if *sequence >= 15 { 1 } else { *sequence + 1 } }
+        };
+        *sequence
+    }
+
+    fn send(&mut self, address: SocketAddr, packet: &[u8]) {
+        let Some(socket) = &self.socket else { return; };
+        match socket.send_to(packet, address) {
+            Ok(_) => { self.packets_sent += 1; }
+            Err(e) => { self.last_error = Some(format!("{}: {}", address, e)); }
+        }
+    }
+}
+
+/// Send the current LED frame to all controllers at the configured frame rate
+pub fn send_led_output(
+    config: Res<LedOutputConfig>,
+    frame: Res<LedFrame>,
+    calibration: Res<LedCalibration>,
+    mut state: ResMut<LedOutputState>,
+    mut loopback: ResMut<LedLoopback>,
+    time: Res<Time<Real>>,
+) {
+    if !config.enabled {
+        state.accum = 0.;
+        return;
+    }
+
+    let frame_time = 1. / config.frame_rate.max(1.);
+    state.accum += time.delta_seconds();
+    if state.accum < frame_time {
+        return;
+    }
+    state.accum = (state.accum - frame_time).min(frame_time);
+
+    let now = time.elapsed_seconds();
+    let sync_address = if config.sync { config.sacn_sync_universe } else { 0 };
+    let mut packets: Vec<(usize, SocketAddr, Vec<u8>)> = vec![];
+    // Controller the sync packet is mirrored for, per address and protocol
+    let mut sync_targets: Vec<(usize, SocketAddr, LedProtocol)> = vec![];
+
+    for (controller_index, controller) in config.controllers.iter().enumerate() {
+        let address: IpAddr = match controller.address.parse() {
+            Ok(address) => { address }
+            Err(_) => {
+                state.last_error = Some(format!("Invalid address for {}: {}", controller.name, controller.address));
+                continue;
+            }
+        };
+        let socket_address = SocketAddr::new(address, controller.protocol.port());
+
+        if controller.max_frame_rate > 0. {
+            let last_sent = state.last_sent.get(&controller_index).cloned().unwrap_or(f32::MIN);
+            if now - last_sent < 1. / controller.max_frame_rate {
+                continue;
+            }
+        }
+        state.last_sent.insert(controller_index, now);
+
+        let pixels = controller_pixels(&config, &frame, &calibration, controller_index);
+
+        match controller.protocol {
+            LedProtocol::ArtNet => {
+                for (universe, data) in universe_data(controller, &pixels) {
+                    let sequence = state.next_sequence(controller_index, universe, controller.protocol);
+                    packets.push((controller_index, socket_address, artdmx_packet(universe, sequence, &data)));
+                    loopback.expect(controller_index, controller.protocol, universe as u32, sequence, data);
+                }
+            }
+            LedProtocol::Sacn => {
+                for (universe, data) in universe_data(controller, &pixels) {
+                    let sequence = state.next_sequence(controller_index, universe, controller.protocol);
+                    packets.push((controller_index, socket_address, e131_data_packet(&state.cid, SOURCE_NAME, universe, sequence, sync_address, &data)));
+                    loopback.expect(controller_index, controller.protocol, universe as u32, sequence, data);
+                }
+            }
+            LedProtocol::Ddp => {
+                let sequence = state.next_sequence(controller_index, 0, controller.protocol);
+                let data = pixel_data(controller, &pixels);
+                for packet in ddp_packets(&data, controller.color_order.channels(), sequence) {
+                    packets.push((controller_index, socket_address, packet));
+                }
+                for (i, chunk) in data.chunks(DDP_MAX_DATA_LEN).enumerate() {
+                    loopback.expect(controller_index, controller.protocol, (i * DDP_MAX_DATA_LEN) as u32, sequence, chunk.to_vec());
+                }
+                // DDP latches on the push flag, no separate sync packet
+                continue;
+            }
+        }
+
+        if !sync_targets.iter().any(|(_, address, protocol)| *address == socket_address && *protocol == controller.protocol) {
+            sync_targets.push((controller_index, socket_address, controller.protocol));
+        }
+    }
+
+    if config.sync {
+        state.sync_sequence = state.sync_sequence.wrapping_add(1);
+        for (controller_index, socket_address, protocol) in sync_targets {
+            let packet = match protocol {
+                LedProtocol::ArtNet => { artsync_packet() }
+                LedProtocol::Sacn => { e131_sync_packet(&state.cid, state.sync_sequence, config.sacn_sync_universe) }
+                LedProtocol::Ddp => { continue; }
+            };
+            packets.push((controller_index, socket_address, packet));
+        }
+    }
+
+    for (controller_index, socket_address, packet) in packets {
+        state.send(socket_address, &packet);
+        if let Some(loopback_address) = loopback.address(controller_index) {
+            state.send(loopback_address, &packet);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::led_output::artnet::{ArtNetPacket, parse_artnet_packet};
+
+    #[test]
+    fn rgbw_universes_fit_into_dmx() {
+        let controller = LedController {
+            color_order: ColorOrder::Rgbw,
+            pixels_per_universe: 170,
+            ..default()
+        };
+        assert_eq!(controller.universe_pixels(), 128);
+        // Full white ends up in the white channel only
+        let pixels = vec![[1., 1., 1.]; 170];
+        let universes = universe_data(&controller, &pixels);
+        assert_eq!(universes.iter().map(|(universe, data)| (*universe, data.len())).collect::<Vec<_>>(), vec![(0, 512), (1, 42 * 4)]);
+
+        let mut sent = 0;
+        for (universe, data) in universes.iter() {
+            let packet = artdmx_packet(*universe, 0, data);
+            let Some(ArtNetPacket::Dmx { data: received, .. }) = parse_artnet_packet(&packet) else { panic!("not an ArtDmx packet"); };
+            assert_eq!(&received[..data.len()], data.as_slice());
+            sent += data.len() / 4;
+        }
+        assert_eq!(sent, 170);
+        assert_eq!(&universes[1].1[..4], &[0, 0, 0, 255]);
+
+        assert_eq!(controller.universe_and_channel(127), (0, 127 * 4 + 1));
+        assert_eq!(controller.universe_and_channel(128), (1, 1));
+    }
+
+    #[test]
+    fn sacn_starts_at_universe_one() {
+        let controller = LedController { protocol: LedProtocol::Sacn, ..default() };
+        assert_eq!(controller.start_universe, 0);
+        assert_eq!(controller.universe_and_channel(0), (1, 1));
+        assert_eq!(universe_data(&controller, &[[0., 0., 0.]; 171])[1].0, 2);
+        let controller = LedController { start_universe: 40000, ..default() };
+        assert_eq!(controller.first_universe(), 32767);
+    }
+
+    #[test]
+    fn rgb_keeps_configured_pixels() {
+        let controller = LedController { pixels_per_universe: 100, ..default() };
+        assert_eq!(controller.universe_pixels(), 100);
+        let universes = universe_data(&controller, &[[0., 0., 0.]; 250]);
+        assert_eq!(universes.iter().map(|(_, data)| data.len()).collect::<Vec<_>>(), vec![300, 300, 150]);
+    }
+}
@@ -0,0 +1,344 @@
+//! Record the composited LED colours of every frame to a file, and read recordings back.
+//!
+//! Binary format (little endian): the magic `VJLEDREC`, a u16 tube count, then per tube the
+//! u16 tube ID and u16 LED count. Each frame is a f32 timestamp in seconds followed by the
+//! RGB8 colours of all LEDs, tube by tube.
+//!
+//! CSV format: a `time,tube,led,r,g,b` header, then one row per LED and frame.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::io;
+use std::path::Path;
+use bevy::prelude::*;
+use strum_macros::EnumIter;
+use crate::led_output::LedFrame;
+use crate::physics_hexagon::lights::led_tube::TubeIndex;
+
+const MAGIC: &[u8; 8] = b"VJLEDREC";
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, EnumIter)]
+pub enum RecordingFormat {
+    Binary,
+    Csv,
+}
+
+impl RecordingFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            RecordingFormat::Binary => { "ledrec" }
+            RecordingFormat::Csv => { "csv" }
+        }
+    }
+}
+
+pub struct RecordedFrame {
+    pub time: f32,
+    /// RGB8 colours per tube, in the order of `LedRecording::tubes`
+    pub tubes: Vec<Vec<[u8; 3]>>,
+}
+
+pub struct LedRecording {
+    /// Recorded tubes and their LED count
+    pub tubes: Vec<(TubeIndex, usize)>,
+    pub frames: Vec<RecordedFrame>,
+}
+
+#[derive(Resource)]
+pub struct LedRecorder {
+    pub format: RecordingFormat,
+    /// File name without extension, a timestamp and the extension are appended
+    pub path: String,
+    writer: Option<BufWriter<File>>,
+    /// Tube layout written to the header, frames are recorded in this layout
+    tubes: Vec<(TubeIndex, usize)>,
+    /// The header is written with the first frame of a recording
+    header_written: bool,
+    start: f32,
+    pub recording_path: Option<String>,
+    pub frames: u64,
+    pub last_error: Option<String>,
+}
+
+impl Default for LedRecorder {
+    fn default() -> Self {
+        Self {
+            format: RecordingFormat::Binary,
+            path: "recordings/leds".to_owned(),
+            writer: None,
+            tubes: vec![],
+            header_written: false,
+            start: 0.,
+            recording_path: None,
+            frames: 0,
+            last_error: None,
+        }
+    }
+}
+
+impl LedRecorder {
+    pub fn is_recording(&self) -> bool {
+        self.writer.is_some()
+    }
+
+    pub fn start(&mut self) {
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let path = format!("{}_{}.{}", self.path, timestamp, self.format.extension());
+        let file = Path::new(&path).parent()
+            .map(|parent| std::fs::create_dir_all(parent))
+            .unwrap_or(Ok(()))
+            .and_then(|_| File::create(&path));
+        match file {
+            Ok(file) => {
+                info!("Recording LEDs to {}", path);
+                self.writer = Some(BufWriter::new(file));
+                self.tubes.clear();
+                self.header_written = false;
+                self.frames = 0;
+                self.recording_path = Some(path);
+                self.last_error = None;
+            }
+            Err(e) => { self.last_error = Some(format!("{}: {}", path, e)); }
+        }
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(mut writer) = self.writer.take() {
+            if let Err(e) = writer.flush() {
+                self.last_error = Some(e.to_string());
+            }
+        }
+    }
+
+    fn write_frame(&mut self, frame: &LedFrame, now: f32) -> io::Result<()> {
+        let Some(writer) = &mut self.writer else { return Ok(()); };
+
+        if !self.header_written {
+            self.header_written = true;
+            self.tubes = frame.tubes.iter()
+                .map(|(tube, colors)| (*tube, colors.len()))
+                .collect();
+            self.tubes.sort_by_key(|(tube, _)| *tube);
+            self.start = now;
+            match self.format {
+                RecordingFormat::Binary => {
+                    writer.write_all(MAGIC)?;
+                    writer.write_all(&(self.tubes.len() as u16).to_le_bytes())?;
+                    for (tube, led_count) in self.tubes.iter() {
+                        writer.write_all(&tube.0.to_le_bytes())?;
+                        writer.write_all(&(*led_count as u16).to_le_bytes())?;
+                    }
+                }
+                RecordingFormat::Csv => { writeln!(writer, "time,tube,led,r,g,b")?; }
+            }
+        }
+
+        let time = now - self.start;
+        if self.format == RecordingFormat::Binary {
+            writer.write_all(&time.to_le_bytes())?;
+        }
+        for (tube, led_count) in self.tubes.iter() {
+            let colors = frame.tubes.get(tube);
+            for led in 0..*led_count {
+                let color = colors.and_then(|colors| colors.get(led)).cloned().unwrap_or(Color::BLACK);
+                let [r, g, b] = color_to_srgb8(color);
+                match self.format {
+                    RecordingFormat::Binary => { writer.write_all(&[r, g, b])?; }
+                    RecordingFormat::Csv => { writeln!(writer, "{:.4},{},{},{},{},{}", time, tube.0, led, r, g, b)?; }
+                }
+            }
+        }
+        self.frames += 1;
+        Ok(())
+    }
+}
+
+fn color_to_srgb8(color: Color) -> [u8; 3] {
+    let [r, g, b, _] = color.as_rgba_f32();
+    [r, g, b].map(|v| (v.clamp(0., 1.) * 255.).round() as u8)
+}
+
+pub fn record_led_frame(
+    mut recorder: ResMut<LedRecorder>,
+    frame: Res<LedFrame>,
+    time: Res<Time<Real>>,
+) {
+    if !recorder.is_recording() { return; }
+    if let Err(e) = recorder.write_frame(&frame, time.elapsed_seconds()) {
+        error!("Couldn't record LED frame: {}", e);
+        recorder.last_error = Some(e.to_string());
+        recorder.stop();
+    }
+}
+
+/// Read a binary or CSV recording, the format is detected from the file content
+pub fn read_recording(path: impl AsRef<Path>) -> io::Result<LedRecording> {
+    let mut reader = BufReader::new(File::open(path)?);
+    if reader.fill_buf()?.starts_with(MAGIC) {
+        read_binary(reader)
+    } else {
+        read_csv(reader)
+    }
+}
+
+/// A truncated last frame, as left behind when the app dies while recording, is dropped
+fn read_binary(mut reader: impl Read) -> io::Result<LedRecording> {
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+
+    let mut u16_buf = [0u8; 2];
+    reader.read_exact(&mut u16_buf)?;
+    let tube_count = u16::from_le_bytes(u16_buf);
+    let mut tubes = vec![];
+    for _ in 0..tube_count {
+        reader.read_exact(&mut u16_buf)?;
+        let tube = TubeIndex(u16::from_le_bytes(u16_buf));
+        reader.read_exact(&mut u16_buf)?;
+        tubes.push((tube, u16::from_le_bytes(u16_buf) as usize));
+    }
+
+    let frame_size = 4 + tubes.iter().map(|(_, led_count)| led_count * 3).sum::<usize>();
+    let mut frames = vec![];
+    let mut data = vec![0u8; frame_size];
+    loop {
+        match reader.read_exact(&mut data) {
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => { break; }
+            Err(e) => { return Err(e); }
+        }
+        let time = f32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+        let mut offset = 4;
+        let mut frame_tubes = vec![];
+        for (_, led_count) in tubes.iter() {
+            let end = offset + led_count * 3;
+            frame_tubes.push(data[offset..end].chunks(3).map(|c| [c[0], c[1], c[2]]).collect());
+            offset = end;
+        }
+        frames.push(RecordedFrame { time, tubes: frame_tubes });
+    }
+
+    Ok(LedRecording { tubes, frames })
+}
+
+fn read_csv(reader: impl BufRead) -> io::Result<LedRecording> {
+    let invalid = |line: &str| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid line: {}", line));
+
+    // (time, tube, led, rgb) rows, grouped into frames by timestamp
+    let mut rows: Vec<(f32, TubeIndex, usize, [u8; 3])> = vec![];
+    for line in reader.lines().skip(1) {
+        let line = line?;
+        if line.trim().is_empty() { continue; }
+        let fields: Vec<&str> = line.split(',').collect();
+        if fields.len() != 6 { return Err(invalid(&line)); }
+        let time: f32 = fields[0].parse().map_err(|_| invalid(&line))?;
+        let tube: u16 = fields[1].parse().map_err(|_| invalid(&line))?;
+        let led: usize = fields[2].parse().map_err(|_| invalid(&line))?;
+        let mut rgb = [0u8; 3];
+        for (channel, field) in rgb.iter_mut().zip(&fields[3..]) {
+            *channel = field.parse().map_err(|_| invalid(&line))?;
+        }
+        rows.push((time, TubeIndex(tube), led, rgb));
+    }
+
+    let mut tubes: Vec<(TubeIndex, usize)> = vec![];
+    for (_, tube, led, _) in rows.iter() {
+        match tubes.iter_mut().find(|(t, _)| t == tube) {
+            Some((_, led_count)) => { *led_count = (*led_count).max(led + 1) }
+            None => { tubes.push((*tube, led + 1)) }
+        }
+    }
+
+    let mut frames: Vec<RecordedFrame> = vec![];
+    for (time, tube, led, rgb) in rows {
+        if frames.last().map(|frame| frame.time != time).unwrap_or(true) {
+            frames.push(RecordedFrame {
+                time,
+                tubes: tubes.iter().map(|(_, led_count)| vec![[0; 3]; *led_count]).collect(),
+            });
+        }
+        let tube_position = tubes.iter().position(|(t, _)| *t == tube).unwrap();
+        frames.last_mut().unwrap().tubes[tube_position][led] = rgb;
+    }
+
+    Ok(LedRecording { tubes, frames })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use super::*;
+
+    fn frame(tubes: &[(u16, Vec<Color>)]) -> LedFrame {
+        LedFrame { tubes: tubes.iter().map(|(tube, colors)| (TubeIndex(*tube), colors.clone())).collect::<HashMap<_, _>>() }
+    }
+
+    /// Record the frames at 0.5 s intervals and return the path of the recording
+    fn record(name: &str, format: RecordingFormat, frames: &[LedFrame]) -> String {
+        let mut recorder = LedRecorder {
+            format,
+            path: std::env::temp_dir().join(format!("vjpyree_recorder_{}", name)).to_string_lossy().to_string(),
+            ..default()
+        };
+        recorder.start();
+        assert!(recorder.is_recording(), "{:?}", recorder.last_error);
+        for (i, frame) in frames.iter().enumerate() {
+            recorder.write_frame(frame, 10. + i as f32 * 0.5).unwrap();
+        }
+        recorder.stop();
+        recorder.recording_path.unwrap()
+    }
+
+    fn sample_frames() -> Vec<LedFrame> {
+        vec![
+            frame(&[(2, vec![Color::RED, Color::BLACK]), (1, vec![Color::WHITE])]),
+            frame(&[(2, vec![Color::BLACK, Color::rgb(0., 0., 1.)]), (1, vec![Color::rgb(0.5, 0.5, 0.5)])]),
+        ]
+    }
+
+    fn assert_sample(recording: &LedRecording) {
+        assert_eq!(recording.tubes, vec![(TubeIndex(1), 1), (TubeIndex(2), 2)]);
+        assert_eq!(recording.frames.len(), 2);
+        assert_eq!(recording.frames[0].time, 0.);
+        assert_eq!(recording.frames[1].time, 0.5);
+        assert_eq!(recording.frames[0].tubes, vec![vec![[255, 255, 255]], vec![[255, 0, 0], [0, 0, 0]]]);
+        assert_eq!(recording.frames[1].tubes, vec![vec![[128, 128, 128]], vec![[0, 0, 0], [0, 0, 255]]]);
+    }
+
+    #[test]
+    fn binary_roundtrip() {
+        let path = record("binary", RecordingFormat::Binary, &sample_frames());
+        assert!(path.ends_with(".ledrec"));
+        assert_sample(&read_recording(&path).unwrap());
+    }
+
+    #[test]
+    fn csv_roundtrip() {
+        let path = record("csv", RecordingFormat::Csv, &sample_frames());
+        assert!(path.ends_with(".csv"));
+        assert_sample(&read_recording(&path).unwrap());
+    }
+
+    #[test]
+    fn frames_without_tubes_keep_one_header() {
+        let path = record("empty", RecordingFormat::Binary, &[frame(&[]), frame(&[]), frame(&[])]);
+        let bytes = std::fs::read(&path).unwrap();
+        // Magic, tube count and three timestamps
+        assert_eq!(bytes.len(), 8 + 2 + 3 * 4);
+        let recording = read_recording(&path).unwrap();
+        assert!(recording.tubes.is_empty());
+        assert_eq!(recording.frames.iter().map(|frame| frame.time).collect::<Vec<_>>(), vec![0., 0.5, 1.]);
+    }
+
+    #[test]
+    fn truncated_frame_is_dropped() {
+        let path = record("truncated", RecordingFormat::Binary, &sample_frames());
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &bytes[..bytes.len() - 4]).unwrap();
+        let recording = read_recording(&path).unwrap();
+        assert_eq!(recording.frames.len(), 1);
+        assert_eq!(recording.frames[0].tubes, vec![vec![[255, 255, 255]], vec![[255, 0, 0], [0, 0, 0]]]);
+    }
+}
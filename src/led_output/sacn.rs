@@ -0,0 +1,167 @@
+//! sACN (ANSI E1.31) packet encoding for data and synchronization packets
+
+pub const SACN_PORT: u16 = 5568;
+
+const ACN_PACKET_IDENTIFIER: [u8; 12] = [0x41, 0x53, 0x43, 0x2d, 0x45, 0x31, 0x2e, 0x31, 0x37, 0x00, 0x00, 0x00];
+const VECTOR_ROOT_E131_DATA: u32 = 0x00000004;
+const VECTOR_ROOT_E131_EXTENDED: u32 = 0x00000008;
+const VECTOR_E131_DATA_PACKET: u32 = 0x00000002;
+const VECTOR_E131_EXTENDED_SYNCHRONIZATION: u32 = 0x00000001;
+const VECTOR_DMP_SET_PROPERTY: u8 = 0x02;
+const DEFAULT_PRIORITY: u8 = 100;
+
+fn flags_and_length(length: usize) -> [u8; 2] {
+    (0x7000 | (length as u16 & 0x0fff)).to_be_bytes()
+}
+
+fn root_layer(packet: &mut Vec<u8>, total_length: usize, vector: u32, cid: &[u8; 16]) {
+    packet.extend_from_slice(&0x0010u16.to_be_bytes()); // Preamble size
+    packet.extend_from_slice(&0x0000u16.to_be_bytes()); // Postamble size
+    packet.extend_from_slice(&ACN_PACKET_IDENTIFIER);
+    packet.extend_from_slice(&flags_and_length(total_length - 16));
+    packet.extend_from_slice(&vector.to_be_bytes());
+    packet.extend_from_slice(cid);
+}
+
+/// Build an E1.31 data packet. A `sync_address` of 0 means the receiver outputs immediately.
+pub fn e131_data_packet(cid: &[u8; 16], source_name: &str, universe: u16, sequence: u8, sync_address: u16, data: &[u8]) -> Vec<u8> {
+    let data = &data[..data.len().min(512)];
+    let total_length = 126 + data.len();
+    let mut packet = Vec::with_capacity(total_length);
+
+    root_layer(&mut packet, total_length, VECTOR_ROOT_E131_DATA, cid);
+
+    // Framing layer
+    packet.extend_from_slice(&flags_and_length(total_length - 38));
+    packet.extend_from_slice(&VECTOR_E131_DATA_PACKET.to_be_bytes());
+    let mut name = [0u8; 64];
+    let name_bytes = source_name.as_bytes();
+    let name_length = name_bytes.len().min(63);
+    name[..name_length].copy_from_slice(&name_bytes[..name_length]);
+    packet.extend_from_slice(&name);
+    packet.push(DEFAULT_PRIORITY);
+    packet.extend_from_slice(&sync_address.to_be_bytes());
+    packet.push(sequence);
+    packet.push(0); // Options
+    packet.extend_from_slice(&universe.to_be_bytes());
+
+    // DMP layer
+    packet.extend_from_slice(&flags_and_length(total_length - 115));
+    packet.push(VECTOR_DMP_SET_PROPERTY);
+    packet.push(0xa1); // Address type & data type
+    packet.extend_from_slice(&0x0000u16.to_be_bytes()); // First property address
+    packet.extend_from_slice(&0x0001u16.to_be_bytes()); // Address increment
+    packet.extend_from_slice(&((data.len() + 1) as u16).to_be_bytes());
+    packet.push(0x00); // DMX start code
+    packet.extend_from_slice(data);
+
+    packet
+}
+
+/// Build an E1.31 synchronization packet for the given sync universe
+pub fn e131_sync_packet(cid: &[u8; 16], sequence: u8, sync_address: u16) -> Vec<u8> {
+    let total_length = 49;
+    let mut packet = Vec::with_capacity(total_length);
+
+    root_layer(&mut packet, total_length, VECTOR_ROOT_E131_EXTENDED, cid);
+
+    packet.extend_from_slice(&flags_and_length(total_length - 38));
+    packet.extend_from_slice(&VECTOR_E131_EXTENDED_SYNCHRONIZATION.to_be_bytes());
+    packet.push(sequence);
+    packet.extend_from_slice(&sync_address.to_be_bytes());
+    packet.extend_from_slice(&[0, 0]); // Reserved
+
+    packet
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SacnPacket<'a> {
+    Data { universe: u16, sequence: u8, sync_address: u16, data: &'a [u8] },
+    Sync { sequence: u8, sync_address: u16 },
+}
+
+/// Decode an E1.31 data or synchronization packet
+pub fn parse_sacn_packet(packet: &[u8]) -> Option<SacnPacket> {
+    if packet.len() < 49 || packet[4..16] != ACN_PACKET_IDENTIFIER {
+        return None;
+    }
+    let root_vector = u32::from_be_bytes([packet[18], packet[19], packet[20], packet[21]]);
+    match root_vector {
+        VECTOR_ROOT_E131_DATA => {
+            if packet.len() < 126 { return None; }
+            let sync_address = u16::from_be_bytes([packet[109], packet[110]]);
+            let universe = u16::from_be_bytes([packet[113], packet[114]]);
+            let value_count = u16::from_be_bytes([packet[123], packet[124]]) as usize;
+            if value_count < 1 || packet.len() < 125 + value_count { return None; }
+            Some(SacnPacket::Data {
+                universe,
+                sequence: packet[111],
+                sync_address,
+                data: &packet[126..125 + value_count],
+            })
+        }
+        VECTOR_ROOT_E131_EXTENDED => {
+            Some(SacnPacket::Sync {
+                sequence: packet[44],
+                sync_address: u16::from_be_bytes([packet[45], packet[46]]),
+            })
+        }
+        _ => { None }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CID: [u8; 16] = [7; 16];
+
+    #[test]
+    fn data_packet_layout() {
+        let packet = e131_data_packet(&CID, "vjpyree", 42, 9, 999, &[10, 20, 30]);
+        assert_eq!(packet.len(), 126 + 3);
+        assert_eq!(&packet[4..16], &ACN_PACKET_IDENTIFIER);
+        // Root, framing and DMP layer lengths
+        assert_eq!(&packet[16..18], &(0x7000u16 | 113).to_be_bytes());
+        assert_eq!(&packet[38..40], &(0x7000u16 | 91).to_be_bytes());
+        assert_eq!(&packet[115..117], &(0x7000u16 | 14).to_be_bytes());
+        assert_eq!(&packet[22..38], &CID);
+        assert_eq!(&packet[44..51], b"vjpyree");
+        assert_eq!(packet[51], 0);
+        assert_eq!(packet[108], DEFAULT_PRIORITY);
+        assert_eq!(&packet[123..125], &[0, 4]);
+        assert_eq!(packet[125], 0);
+    }
+
+    #[test]
+    fn data_roundtrip() {
+        let data: Vec<u8> = (0..600).map(|i| i as u8).collect();
+        let packet = e131_data_packet(&CID, "vjpyree", 63999, 255, 0, &data);
+        assert_eq!(packet.len(), 126 + 512);
+        assert_eq!(parse_sacn_packet(&packet), Some(SacnPacket::Data { universe: 63999, sequence: 255, sync_address: 0, data: &data[..512] }));
+    }
+
+    #[test]
+    fn long_source_name_keeps_terminator() {
+        let packet = e131_data_packet(&CID, &"x".repeat(100), 1, 0, 0, &[]);
+        assert_eq!(packet[44 + 62], b'x');
+        assert_eq!(packet[44 + 63], 0);
+    }
+
+    #[test]
+    fn sync_roundtrip() {
+        let packet = e131_sync_packet(&CID, 3, 999);
+        assert_eq!(packet.len(), 49);
+        assert_eq!(&packet[16..18], &(0x7000u16 | 33).to_be_bytes());
+        assert_eq!(&packet[38..40], &(0x7000u16 | 11).to_be_bytes());
+        assert_eq!(parse_sacn_packet(&packet), Some(SacnPacket::Sync { sequence: 3, sync_address: 999 }));
+    }
+
+    #[test]
+    fn rejects_foreign_packets() {
+        let mut packet = e131_data_packet(&CID, "vjpyree", 1, 0, 0, &[1]);
+        packet[4] = 0;
+        assert_eq!(parse_sacn_packet(&packet), None);
+        assert_eq!(parse_sacn_packet(&[0; 20]), None);
+    }
+}
@@ -0,0 +1,647 @@
+//! Expression language of the LED shaders.
+//!
+//! A shader is a list of `name = expression;` bindings followed by the expression for the
+//! colour, `#` starts a comment. Values are scalars or RGB colours, operators and functions work
+//! per channel when a colour is involved. A scalar result scales the primary colour.
+
+use std::fmt;
+use std::ops::RangeInclusive;
+use bevy::math::Vec3;
+use noise::{NoiseFn, OpenSimplex, Perlin};
+
+/// Built-in variables, in the order of their slots
+pub const VARIABLES: [&str; 10] = ["x", "y", "tube", "led", "pos", "t", "beat", "phase", "primary", "secondary"];
+
+pub const FUNCTIONS: [&str; 30] = [
+    "sin", "cos", "tan", "asin", "acos", "atan", "abs", "floor", "ceil", "fract", "sqrt", "exp", "log", "sign",
+    "atan2", "min", "max", "pow", "mod", "step", "clamp", "mix", "smoothstep", "if", "rgb", "hsv", "gray",
+    "noise", "simplex", "luma",
+];
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Value {
+    Scalar(f32),
+    Color(Vec3),
+}
+
+impl Value {
+    pub fn to_vec3(self) -> Vec3 {
+        match self {
+            Value::Scalar(v) => { Vec3::splat(v) }
+            Value::Color(c) => { c }
+        }
+    }
+
+    /// Colours are reduced to the average of their channels
+    pub fn to_scalar(self) -> f32 {
+        match self {
+            Value::Scalar(v) => { v }
+            Value::Color(c) => { (c.x + c.y + c.z) / 3. }
+        }
+    }
+
+    fn map(self, f: impl Fn(f32) -> f32) -> Value {
+        match self {
+            Value::Scalar(v) => { Value::Scalar(f(v)) }
+            Value::Color(c) => { Value::Color(Vec3::new(f(c.x), f(c.y), f(c.z))) }
+        }
+    }
+
+    fn zip(self, other: Value, f: impl Fn(f32, f32) -> f32) -> Value {
+        match (self, other) {
+            (Value::Scalar(a), Value::Scalar(b)) => { Value::Scalar(f(a, b)) }
+            (a, b) => {
+                let (a, b) = (a.to_vec3(), b.to_vec3());
+                Value::Color(Vec3::new(f(a.x, b.x), f(a.y, b.y), f(a.z, b.z)))
+            }
+        }
+    }
+
+    fn zip3(self, b: Value, c: Value, f: impl Fn(f32, f32, f32) -> f32) -> Value {
+        match (self, b, c) {
+            (Value::Scalar(a), Value::Scalar(b), Value::Scalar(c)) => { Value::Scalar(f(a, b, c)) }
+            (a, b, c) => {
+                let (a, b, c) = (a.to_vec3(), b.to_vec3(), c.to_vec3());
+                Value::Color(Vec3::new(f(a.x, b.x, c.x), f(a.y, b.y, c.y), f(a.z, b.z, c.z)))
+            }
+        }
+    }
+}
+
+/// Values of the built-in variables for one LED
+pub struct ShaderInputs {
+    /// World position divided by 540, so y runs from -1 to 1 across the screen
+    pub x: f32,
+    pub y: f32,
+    pub tube: f32,
+    pub led: f32,
+    /// Position along the tube, 0 to 1
+    pub pos: f32,
+    /// Seconds since start
+    pub t: f32,
+    /// Continuous beat position
+    pub beat: f32,
+    /// Position within the beat, 0 to 1
+    pub phase: f32,
+    pub primary: Vec3,
+    pub secondary: Vec3,
+}
+
+impl ShaderInputs {
+    fn slots(&self) -> [Value; VARIABLES.len()] {
+        [
+            Value::Scalar(self.x),
+            Value::Scalar(self.y),
+            Value::Scalar(self.tube),
+            Value::Scalar(self.led),
+            Value::Scalar(self.pos),
+            Value::Scalar(self.t),
+            Value::Scalar(self.beat),
+            Value::Scalar(self.phase),
+            Value::Color(self.primary),
+            Value::Color(self.secondary),
+        ]
+    }
+}
+
+/// Noise generators available to the shaders
+pub struct ShaderNoise {
+    perlin: Perlin,
+    simplex: OpenSimplex,
+}
+
+impl ShaderNoise {
+    pub fn new(seed: u32) -> Self {
+        Self {
+            perlin: Perlin::new(seed),
+            simplex: OpenSimplex::new(seed),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShaderError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Pow,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Func {
+    Sin, Cos, Tan, Asin, Acos, Atan, Abs, Floor, Ceil, Fract, Sqrt, Exp, Log, Sign,
+    Atan2, Min, Max, Pow, Mod, Step, Clamp, Mix, Smoothstep, Rgb, Hsv, Gray,
+    Noise, Simplex, Luma,
+}
+
+impl Func {
+    fn lookup(name: &str) -> Option<(Func, RangeInclusive<usize>)> {
+        let func = match name {
+            "sin" => { (Func::Sin, 1..=1) }
+            "cos" => { (Func::Cos, 1..=1) }
+            "tan" => { (Func::Tan, 1..=1) }
+            "asin" => { (Func::Asin, 1..=1) }
+            "acos" => { (Func::Acos, 1..=1) }
+            "atan" => { (Func::Atan, 1..=1) }
+            "abs" => { (Func::Abs, 1..=1) }
+            "floor" => { (Func::Floor, 1..=1) }
+            "ceil" => { (Func::Ceil, 1..=1) }
+            "fract" => { (Func::Fract, 1..=1) }
+            "sqrt" => { (Func::Sqrt, 1..=1) }
+            "exp" => { (Func::Exp, 1..=1) }
+            "log" => { (Func::Log, 1..=1) }
+            "sign" => { (Func::Sign, 1..=1) }
+            "atan2" => { (Func::Atan2, 2..=2) }
+            "min" => { (Func::Min, 2..=2) }
+            "max" => { (Func::Max, 2..=2) }
+            "pow" => { (Func::Pow, 2..=2) }
+            "mod" => { (Func::Mod, 2..=2) }
+            "step" => { (Func::Step, 2..=2) }
+            "clamp" => { (Func::Clamp, 3..=3) }
+            "mix" => { (Func::Mix, 3..=3) }
+            "smoothstep" => { (Func::Smoothstep, 3..=3) }
+            "rgb" => { (Func::Rgb, 3..=3) }
+            "hsv" => { (Func::Hsv, 3..=3) }
+            "gray" => { (Func::Gray, 1..=1) }
+            "noise" => { (Func::Noise, 2..=3) }
+            "simplex" => { (Func::Simplex, 2..=3) }
+            "luma" => { (Func::Luma, 1..=1) }
+            _ => { return None; }
+        };
+        Some(func)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Expr {
+    Number(f32),
+    Slot(usize),
+    Neg(Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+    /// `if(condition, then, else)`, only the taken branch is evaluated
+    If(Box<Expr>, Box<Expr>, Box<Expr>),
+    Call(Func, Vec<Expr>),
+}
+
+/// Compiled shader, variables are resolved to slots
+#[derive(Clone, Debug, PartialEq)]
+pub struct Program {
+    /// Bindings in order, binding `i` writes slot `VARIABLES.len() + i`
+    bindings: Vec<Expr>,
+    result: Expr,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f32),
+    Ident(String),
+    Op(&'static str),
+    End,
+}
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize, usize)>, ShaderError> {
+    const OPS: [&str; 15] = ["<=", ">=", "+", "-", "*", "/", "%", "^", "(", ")", ",", ";", "=", "<", ">"];
+    let mut tokens = vec![];
+    let mut line_number = 0;
+    for (line_index, line) in source.lines().enumerate() {
+        line_number = line_index + 1;
+        let line = line.split('#').next().unwrap_or_default();
+        let chars: Vec<char> = line.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            let column = i + 1;
+            if c.is_whitespace() {
+                i += 1;
+            } else if c.is_ascii_digit() || c == '.' {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') { i += 1; }
+                let text: String = chars[start..i].iter().collect();
+                let number = text.parse().map_err(|_| ShaderError { line: line_number, column, message: format!("Invalid number {}", text) })?;
+                tokens.push((Token::Number(number), line_number, column));
+            } else if c.is_alphabetic() || c == '_' {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') { i += 1; }
+                tokens.push((Token::Ident(chars[start..i].iter().collect()), line_number, column));
+            } else {
+                let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+                let Some(op) = OPS.iter().find(|op| rest.starts_with(**op)) else {
+                    return Err(ShaderError { line: line_number, column, message: format!("Unexpected character {}", c) });
+                };
+                i += op.len();
+                tokens.push((Token::Op(*op), line_number, column));
+            }
+        }
+    }
+    tokens.push((Token::End, line_number.max(1), 1));
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize, usize)>,
+    position: usize,
+    /// Binding names after the built-in variables
+    names: Vec<String>,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.position].0
+    }
+
+    fn peek_at(&self, offset: usize) -> &Token {
+        &self.tokens[(self.position + offset).min(self.tokens.len() - 1)].0
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.peek().clone();
+        if self.position + 1 < self.tokens.len() { self.position += 1; }
+        token
+    }
+
+    fn error(&self, message: impl Into<String>) -> ShaderError {
+        let (_, line, column) = self.tokens[self.position];
+        ShaderError { line, column, message: message.into() }
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), ShaderError> {
+        if self.is_op(op) {
+            self.next();
+            Ok(())
+        } else {
+            Err(self.error(format!("Expected {}", op)))
+        }
+    }
+
+    fn is_op(&self, op: &str) -> bool {
+        matches!(self.peek(), Token::Op(o) if *o == op)
+    }
+
+    fn program(&mut self) -> Result<Program, ShaderError> {
+        let mut bindings = vec![];
+        loop {
+            let Token::Ident(name) = self.peek().clone() else { break; };
+            if *self.peek_at(1) != Token::Op("=") { break; }
+            if VARIABLES.contains(&name.as_str()) {
+                return Err(self.error(format!("Can't assign to built-in variable {}", name)));
+            }
+            self.next();
+            self.next();
+            bindings.push(self.expression()?);
+            self.expect(";")?;
+            self.names.push(name);
+        }
+
+        let result = self.expression()?;
+        if self.is_op(";") { self.next(); }
+        if *self.peek() != Token::End {
+            return Err(self.error("Expected end of shader"));
+        }
+        Ok(Program { bindings, result })
+    }
+
+    fn expression(&mut self) -> Result<Expr, ShaderError> {
+        let mut expr = self.additive()?;
+        loop {
+            let op = match self.peek() {
+                Token::Op("<") => { BinOp::Lt }
+                Token::Op(">") => { BinOp::Gt }
+                Token::Op("<=") => { BinOp::Le }
+                Token::Op(">=") => { BinOp::Ge }
+                _ => { return Ok(expr); }
+            };
+            self.next();
+            expr = Expr::Binary(op, Box::new(expr), Box::new(self.additive()?));
+        }
+    }
+
+    fn additive(&mut self) -> Result<Expr, ShaderError> {
+        let mut expr = self.term()?;
+        loop {
+            let op = match self.peek() {
+                Token::Op("+") => { BinOp::Add }
+                Token::Op("-") => { BinOp::Sub }
+                _ => { return Ok(expr); }
+            };
+            self.next();
+            expr = Expr::Binary(op, Box::new(expr), Box::new(self.term()?));
+        }
+    }
+
+    fn term(&mut self) -> Result<Expr, ShaderError> {
+        let mut expr = self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Token::Op("*") => { BinOp::Mul }
+                Token::Op("/") => { BinOp::Div }
+                Token::Op("%") => { BinOp::Mod }
+                _ => { return Ok(expr); }
+            };
+            self.next();
+            expr = Expr::Binary(op, Box::new(expr), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, ShaderError> {
+        if self.is_op("-") {
+            self.next();
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        self.power()
+    }
+
+    /// `^` binds tighter than unary minus and is right associative
+    fn power(&mut self) -> Result<Expr, ShaderError> {
+        let base = self.primary()?;
+        if self.is_op("^") {
+            self.next();
+            return Ok(Expr::Binary(BinOp::Pow, Box::new(base), Box::new(self.unary()?)));
+        }
+        Ok(base)
+    }
+
+    fn primary(&mut self) -> Result<Expr, ShaderError> {
+        match self.peek().clone() {
+            Token::Number(number) => {
+                self.next();
+                Ok(Expr::Number(number))
+            }
+            Token::Op("(") => {
+                self.next();
+                let expr = self.expression()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Token::Ident(name) => {
+                if *self.peek_at(1) == Token::Op("(") {
+                    return self.call(&name);
+                }
+                let slot = self.names.iter().rposition(|n| *n == name)
+                    .map(|i| VARIABLES.len() + i)
+                    .or_else(|| VARIABLES.iter().position(|v| *v == name))
+                    .ok_or_else(|| self.error(format!("Unknown variable {}", name)))?;
+                self.next();
+                Ok(Expr::Slot(slot))
+            }
+            Token::Op(op) => { Err(self.error(format!("Unexpected {}", op))) }
+            Token::End => { Err(self.error("Unexpected end of shader")) }
+        }
+    }
+
+    fn call(&mut self, name: &str) -> Result<Expr, ShaderError> {
+        // `if` is parsed like a call but only evaluates one branch, so it isn't a `Func`
+        let (func, arity) = match name {
+            "if" => { (None, 3..=3) }
+            _ => {
+                let (func, arity) = Func::lookup(name)
+                    .ok_or_else(|| self.error(format!("Unknown function {}", name)))?;
+                (Some(func), arity)
+            }
+        };
+        let call_error = self.error(format!("{} takes {} to {} arguments", name, arity.start(), arity.end()));
+        self.next();
+        self.next();
+        let mut args = vec![];
+        if !self.is_op(")") {
+            loop {
+                args.push(self.expression()?);
+                if !self.is_op(",") { break; }
+                self.next();
+            }
+        }
+        self.expect(")")?;
+        if !arity.contains(&args.len()) {
+            return Err(call_error);
+        }
+        let Some(func) = func else {
+            let Ok([condition, then, otherwise]) = <[Expr; 3]>::try_from(args) else { return Err(call_error); };
+            return Ok(Expr::If(Box::new(condition), Box::new(then), Box::new(otherwise)));
+        };
+        Ok(Expr::Call(func, args))
+    }
+}
+
+pub fn compile(source: &str) -> Result<Program, ShaderError> {
+    let mut parser = Parser { tokens: tokenize(source)?, position: 0, names: vec![] };
+    parser.program()
+}
+
+/// Hue from 0 to 1
+fn hsv_to_rgb(h: f32, s: f32, v: f32) -> Vec3 {
+    let channel = |n: f32| {
+        let k = (n + h.rem_euclid(1.) * 6.).rem_euclid(6.);
+        v - v * s.clamp(0., 1.) * k.min(4. - k).clamp(0., 1.)
+    };
+    Vec3::new(channel(5.), channel(3.), channel(1.))
+}
+
+impl Program {
+    /// Colour of one LED
+    pub fn eval(&self, inputs: &ShaderInputs, noise: &ShaderNoise) -> Vec3 {
+        let mut slots: Vec<Value> = inputs.slots().to_vec();
+        for binding in self.bindings.iter() {
+            let value = eval_expr(binding, &slots, noise);
+            slots.push(value);
+        }
+        let color = match eval_expr(&self.result, &slots, noise) {
+            Value::Scalar(v) => { inputs.primary * v }
+            Value::Color(c) => { c }
+        };
+        Vec3::select(color.is_nan_mask(), Vec3::ZERO, color)
+    }
+}
+
+fn eval_expr(expr: &Expr, slots: &[Value], noise: &ShaderNoise) -> Value {
+    match expr {
+        Expr::Number(v) => { Value::Scalar(*v) }
+        Expr::Slot(slot) => { slots[*slot] }
+        Expr::Neg(e) => { eval_expr(e, slots, noise).map(|v| -v) }
+        Expr::Binary(op, a, b) => {
+            let a = eval_expr(a, slots, noise);
+            let b = eval_expr(b, slots, noise);
+            let bool_f32 = |b: bool| if b { 1. } else { 0. };
+            match op {
+                BinOp::Add => { a.zip(b, |a, b| a + b) }
+                BinOp::Sub => { a.zip(b, |a, b| a - b) }
+                BinOp::Mul => { a.zip(b, |a, b| a * b) }
+                BinOp::Div => { a.zip(b, |a, b| a / b) }
+                BinOp::Mod => { a.zip(b, |a, b| a.rem_euclid(b)) }
+                BinOp::Pow => { a.zip(b, |a, b| a.powf(b)) }
+                BinOp::Lt => { a.zip(b, |a, b| bool_f32(a < b)) }
+                BinOp::Gt => { a.zip(b, |a, b| bool_f32(a > b)) }
+                BinOp::Le => { a.zip(b, |a, b| bool_f32(a <= b)) }
+                BinOp::Ge => { a.zip(b, |a, b| bool_f32(a >= b)) }
+            }
+        }
+        Expr::If(condition, then, otherwise) => {
+            if eval_expr(condition, slots, noise).to_scalar() > 0. {
+                eval_expr(then, slots, noise)
+            } else {
+                eval_expr(otherwise, slots, noise)
+            }
+        }
+        Expr::Call(func, args) => {
+            let args: Vec<Value> = args.iter().map(|arg| eval_expr(arg, slots, noise)).collect();
+            let scalar = |i: usize| args[i].to_scalar();
+            match func {
+                Func::Sin => { args[0].map(f32::sin) }
+                Func::Cos => { args[0].map(f32::cos) }
+                Func::Tan => { args[0].map(f32::tan) }
+                Func::Asin => { args[0].map(f32::asin) }
+                Func::Acos => { args[0].map(f32::acos) }
+                Func::Atan => { args[0].map(f32::atan) }
+                Func::Abs => { args[0].map(f32::abs) }
+                Func::Floor => { args[0].map(f32::floor) }
+                Func::Ceil => { args[0].map(f32::ceil) }
+                Func::Fract => { args[0].map(|v| v - v.floor()) }
+                Func::Sqrt => { args[0].map(f32::sqrt) }
+                Func::Exp => { args[0].map(f32::exp) }
+                Func::Log => { args[0].map(f32::ln) }
+                Func::Sign => { args[0].map(f32::signum) }
+                Func::Atan2 => { args[0].zip(args[1], f32::atan2) }
+                Func::Min => { args[0].zip(args[1], f32::min) }
+                Func::Max => { args[0].zip(args[1], f32::max) }
+                Func::Pow => { args[0].zip(args[1], f32::powf) }
+                Func::Mod => { args[0].zip(args[1], f32::rem_euclid) }
+                Func::Step => { args[0].zip(args[1], |edge, v| if v < edge { 0. } else { 1. }) }
+                Func::Clamp => { args[0].zip3(args[1], args[2], |v, min, max| v.max(min).min(max)) }
+                Func::Mix => { args[0].zip3(args[1], args[2], |a, b, t| a + (b - a) * t) }
+                Func::Smoothstep => {
+                    args[0].zip3(args[1], args[2], |e0, e1, v| {
+                        let t = ((v - e0) / (e1 - e0)).clamp(0., 1.);
+                        t * t * (3. - 2. * t)
+                    })
+                }
+                Func::Rgb => { Value::Color(Vec3::new(scalar(0), scalar(1), scalar(2))) }
+                Func::Hsv => { Value::Color(hsv_to_rgb(scalar(0), scalar(1), scalar(2))) }
+                Func::Gray => { Value::Color(Vec3::splat(scalar(0))) }
+                Func::Luma => { Value::Scalar(args[0].to_vec3().dot(Vec3::new(0.2126, 0.7152, 0.0722))) }
+                Func::Noise | Func::Simplex => {
+                    let point = [
+                        scalar(0) as f64,
+                        scalar(1) as f64,
+                        args.get(2).map(|v| v.to_scalar()).unwrap_or_default() as f64,
+                    ];
+                    let n = if *func == Func::Noise { noise.perlin.get(point) } else { noise.simplex.get(point) };
+                    Value::Scalar(0.5 + 0.5 * n as f32)
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inputs() -> ShaderInputs {
+        ShaderInputs {
+            x: 0.5,
+            y: -0.25,
+            tube: 3.,
+            led: 7.,
+            pos: 0.25,
+            t: 2.,
+            beat: 4.5,
+            phase: 0.5,
+            primary: Vec3::new(1., 0., 0.),
+            secondary: Vec3::new(0., 0., 1.),
+        }
+    }
+
+    fn run(source: &str) -> Vec3 {
+        compile(source).unwrap().eval(&inputs(), &ShaderNoise::new(0))
+    }
+
+    fn compile_error(source: &str) -> ShaderError {
+        compile(source).err().unwrap()
+    }
+
+    #[test]
+    fn precedence() {
+        assert!(run("rgb(1 + 2 * 3, -2 ^ 2, 2 ^ 3 ^ 2)").abs_diff_eq(Vec3::new(7., -4., 512.), 1e-3));
+        assert_eq!(run("gray((1 + 2) * 3 < 10)"), Vec3::ONE);
+        assert_eq!(run("gray(-7 % 3)"), Vec3::splat(2.));
+    }
+
+    #[test]
+    fn scalar_result_scales_primary() {
+        assert_eq!(run("pos * 2"), Vec3::new(0.5, 0., 0.));
+    }
+
+    #[test]
+    fn colours_work_per_channel() {
+        assert_eq!(run("primary + secondary * 0.5"), Vec3::new(1., 0., 0.5));
+        assert_eq!(run("mix(primary, secondary, phase)"), Vec3::new(0.5, 0., 0.5));
+    }
+
+    #[test]
+    fn bindings_and_shadowing() {
+        let source = "
+            # comment
+            a = tube + 1;
+            a = a * 2; # the second binding sees the first
+            gray(a)
+        ";
+        assert_eq!(run(source), Vec3::splat(8.));
+    }
+
+    #[test]
+    fn if_picks_a_branch() {
+        assert_eq!(run("if(x > 0, primary, secondary)"), Vec3::new(1., 0., 0.));
+        assert_eq!(run("if(y > 0, primary, secondary)"), Vec3::new(0., 0., 1.));
+        assert_eq!(run("gray(max(if(y > 0, 1, 0.25), 0))"), Vec3::splat(0.25));
+    }
+
+    #[test]
+    fn nan_becomes_black() {
+        assert_eq!(run("gray(sqrt(-1))"), Vec3::ZERO);
+    }
+
+    #[test]
+    fn hsv_primaries() {
+        assert!(run("hsv(0, 1, 1)").abs_diff_eq(Vec3::new(1., 0., 0.), 1e-5));
+        assert!(run("hsv(1 / 3, 1, 1)").abs_diff_eq(Vec3::new(0., 1., 0.), 1e-5));
+        assert!(run("hsv(2 / 3, 1, 1)").abs_diff_eq(Vec3::new(0., 0., 1.), 1e-5));
+    }
+
+    #[test]
+    fn noise_follows_seed() {
+        let program = compile("gray(noise(x * 3.7, y, t))").unwrap();
+        let a = program.eval(&inputs(), &ShaderNoise::new(1));
+        assert_eq!(a, program.eval(&inputs(), &ShaderNoise::new(1)));
+        assert_ne!(a, program.eval(&inputs(), &ShaderNoise::new(2)));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(compile_error("foo").message, "Unknown variable foo");
+        assert_eq!(compile_error("sin(1, 2)").message, "sin takes 1 to 1 arguments");
+        assert_eq!(compile_error("bar(1)").message, "Unknown function bar");
+        assert_eq!(compile_error("if(1, 2)").message, "if takes 3 to 3 arguments");
+        assert_eq!(compile_error("t = 1; t").message, "Can't assign to built-in variable t");
+        let error = compile_error("a = 1;\n  a $ 2");
+        assert_eq!((error.line, error.column), (2, 5));
+        assert_eq!(compile_error("1 2").message, "Expected end of shader");
+        assert_eq!(compile_error("(1").message, "Expected )");
+    }
+}
@@ -0,0 +1,162 @@
+//! LED shaders written in a small expression language, evaluated on the CPU for every LED.
+//! Shaders are loaded from `assets/led_shaders/*.expr` and reloaded when a file changes, a
+//! shader that doesn't compile keeps running its last working version.
+
+pub mod expr;
+
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use bevy::prelude::*;
+use crate::anims::AnimColors;
+use crate::beat::beat_clock::BeatClock;
+use crate::led_shader::expr::{compile, Program, ShaderInputs, ShaderNoise};
+use crate::MetaAnimUpdate;
+use crate::physics_hexagon::lights::led_layers::LedLayer;
+use crate::physics_hexagon::lights::led_tube::{LedTube, LedTubeLed};
+
+pub const SHADER_DIR: &str = "assets/led_shaders";
+pub const SHADER_EXTENSION: &str = "expr";
+/// Seconds between checks for changed shader files
+const RELOAD_INTERVAL: f32 = 0.5;
+
+pub struct LedShaderPlugin;
+
+impl Plugin for LedShaderPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LedShaders>();
+        app.add_systems(Update, reload_led_shaders);
+        app.add_systems(MetaAnimUpdate, apply_led_shader);
+    }
+}
+
+pub struct LedShader {
+    /// File name without extension
+    pub name: String,
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    pub program: Option<Program>,
+    /// Compile error of the current file content
+    pub error: Option<String>,
+}
+
+impl LedShader {
+    fn load(&mut self) {
+        match std::fs::read_to_string(&self.path) {
+            Ok(source) => {
+                match compile(&source) {
+                    Ok(program) => {
+                        info!("Loaded LED shader {}", self.name);
+                        self.program = Some(program);
+                        self.error = None;
+                    }
+                    Err(e) => {
+                        error!("Couldn't compile LED shader {}: {}", self.name, e);
+                        self.error = Some(e.to_string());
+                    }
+                }
+            }
+            Err(e) => { self.error = Some(e.to_string()); }
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct LedShaders {
+    pub enabled: bool,
+    /// Name of the running shader
+    pub selected: Option<String>,
+    pub shaders: Vec<LedShader>,
+    noise: ShaderNoise,
+    reload_timer: f32,
+}
+
+impl Default for LedShaders {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            selected: None,
+            shaders: vec![],
+            noise: ShaderNoise::new(1),
+            reload_timer: 0.,
+        }
+    }
+}
+
+impl LedShaders {
+    pub fn selected(&self) -> Option<&LedShader> {
+        let selected = self.selected.as_ref()?;
+        self.shaders.iter().find(|shader| shader.name == *selected)
+    }
+}
+
+fn shader_files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else { return vec![]; };
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().map(|ext| ext == SHADER_EXTENSION).unwrap_or(false))
+        .collect();
+    files.sort();
+    files
+}
+
+/// Pick up new, changed and removed shader files
+pub fn reload_led_shaders(
+    mut shaders: ResMut<LedShaders>,
+    time: Res<Time<Real>>,
+) {
+    shaders.reload_timer -= time.delta_seconds();
+    if shaders.reload_timer > 0. { return; }
+    shaders.reload_timer = RELOAD_INTERVAL;
+
+    let files = shader_files(Path::new(SHADER_DIR));
+    shaders.shaders.retain(|shader| files.contains(&shader.path));
+    for path in files {
+        let modified = std::fs::metadata(&path).and_then(|metadata| metadata.modified()).ok();
+        let shader = match shaders.shaders.iter().position(|shader| shader.path == path) {
+            Some(i) => { &mut shaders.shaders[i] }
+            None => {
+                let name = path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+                shaders.shaders.push(LedShader { name, path, modified: None, program: None, error: None });
+                shaders.shaders.last_mut().unwrap()
+            }
+        };
+        if shader.modified != modified || shader.modified.is_none() {
+            shader.modified = modified;
+            shader.load();
+        }
+    }
+}
+
+/// Evaluate the selected shader for every LED into the shader layer
+pub fn apply_led_shader(
+    shaders: Res<LedShaders>,
+    colors: Res<AnimColors>,
+    clock: Res<BeatClock>,
+    time: Res<Time<Real>>,
+    tube_query: Query<&LedTube>,
+    mut led_query: Query<(&mut LedTubeLed, &GlobalTransform, &Parent)>,
+) {
+    if !shaders.enabled { return; }
+    let Some(program) = shaders.selected().and_then(|shader| shader.program.as_ref()) else { return; };
+
+    let [pr, pg, pb, _] = colors.primary.as_rgba_f32();
+    let [sr, sg, sb, _] = colors.secondary.as_rgba_f32();
+    for (mut ltl, gt, parent) in led_query.iter_mut() {
+        let Ok(led_tube) = tube_query.get(parent.get()) else { continue; };
+        let position = gt.translation().truncate() / 540.;
+        let inputs = ShaderInputs {
+            x: position.x,
+            y: position.y,
+            tube: led_tube.get_tube_index().0 as f32,
+            led: ltl.get_index() as f32,
+            pos: ltl.get_relative_position(),
+            t: time.elapsed_seconds(),
+            beat: clock.position as f32,
+            phase: clock.phase(),
+            primary: Vec3::new(pr, pg, pb),
+            secondary: Vec3::new(sr, sg, sb),
+        };
+        let color = program.eval(&inputs, &shaders.noise);
+        *ltl.layer_mut(LedLayer::Shader) = Color::rgb(color.x, color.y, color.z);
+    }
+}
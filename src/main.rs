@@ -11,6 +11,12 @@ pub mod beat;
 pub mod anims;
 mod render_main;
 pub mod swirl;
+pub mod led_output;
+pub mod pixel_map;
+pub mod led_shader;
+pub mod master;
+pub mod palette;
+mod cli;
 
 use bevy::app::MainScheduleOrder;
 use bevy::core::Zeroable;
@@ -28,6 +34,11 @@ use crate::beat::OscBeatReceiverPlugin;
 use crate::elements2d::Elements2DPlugin;
 use crate::gui::GuiPlugin;
 use crate::hexagon::HexagonPlugin;
+use crate::led_output::LedOutputPlugin;
+use crate::led_shader::LedShaderPlugin;
+use crate::master::MasterPlugin;
+use crate::palette::PalettePlugin;
+use crate::pixel_map::PixelMapPlugin;
 use crate::physics_hexagon::PhysicsHexagonPlugin;
 use crate::propagating_render_layers::{PropagatingRenderLayersPlugin};
 use crate::render_main::RenderMainPlugin;
@@ -53,6 +64,10 @@ pub struct AsyncUpdate2;
 pub struct AsyncUpdate3;
 
 fn main() {
+    if cli::run_cli() {
+        return;
+    }
+
     let mut app = App::new();
     app
         .add_plugins(DefaultPlugins)
@@ -73,6 +88,8 @@ fn main() {
         .add_plugins(HexagonPlugin)
         .add_plugins(RenderMainPlugin)
         .add_plugins(PhysicsHexagonPlugin)
+        .add_plugins(LedOutputPlugin)
+        .add_plugins(PixelMapPlugin)
         .add_plugins(Elements2DPlugin)
         .add_plugins(GuiPlugin)
         .add_plugins(ParameterAnimationPlugin)
@@ -83,6 +100,9 @@ fn main() {
         ))
         .add_systems(Startup, startup)
         .add_plugins(AnimPlugin)
+        .add_plugins(PalettePlugin)
+        .add_plugins(LedShaderPlugin)
+        .add_plugins(MasterPlugin)
         .add_plugins(SwirlPlugin)
         .add_plugins(AnimatedGifPlugin);
     ;
@@ -0,0 +1,150 @@
+//! Freeze frame of the projection. When the freeze starts, the render targets of the composite
+//! are copied into freeze images on the GPU, which are then shown on top of the live composite.
+
+use bevy::prelude::*;
+use bevy::render::RenderApp;
+use bevy::render::extract_resource::{ExtractResource, ExtractResourcePlugin};
+use bevy::render::graph::CameraDriverLabel;
+use bevy::render::render_asset::RenderAssets;
+use bevy::render::render_graph::{Node, NodeRunError, RenderGraph, RenderGraphContext, RenderLabel};
+use bevy::render::render_resource::TextureUsages;
+use bevy::render::renderer::RenderContext;
+use bevy::render::view::RenderLayers;
+use bevy::sprite::{MaterialMesh2dBundle, Mesh2dHandle};
+use crate::elements2d::render::Elements2dRendertarget;
+use crate::hexagon::render::HexagonRenderTarget;
+use crate::master::MasterControls;
+use crate::physics_hexagon::render::PhysicsHexagonRenderTarget;
+
+pub struct FreezePlugin;
+
+impl Plugin for FreezePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FreezeCapture>();
+        app.add_plugins(ExtractResourcePlugin::<FreezeCapture>::default());
+    }
+
+    fn finish(&self, app: &mut App) {
+        let Ok(render_app) = app.get_sub_app_mut(RenderApp) else { return; };
+        let mut render_graph = render_app.world.resource_mut::<RenderGraph>();
+        render_graph.add_node(FreezeCaptureLabel, FreezeCaptureNode);
+        render_graph.add_node_edge(CameraDriverLabel, FreezeCaptureLabel);
+    }
+}
+
+/// Render targets of the composite and the images they are frozen into
+#[derive(Resource, Clone, Default, ExtractResource)]
+pub struct FreezeCapture {
+    pub pairs: Vec<(Handle<Image>, Handle<Image>)>,
+    /// Copy the render targets this frame
+    pub capture: bool,
+}
+
+/// Frozen composite, hidden while not frozen
+#[derive(Component)]
+pub struct FreezeOverlay;
+
+/// Frozen copies of the composite quads from `render_main`, above the live ones
+pub fn spawn_freeze_overlay(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut images: ResMut<Assets<Image>>,
+    mut capture: ResMut<FreezeCapture>,
+    physics_hexagon_rt: Res<PhysicsHexagonRenderTarget>,
+    elements2d_rt: Res<Elements2dRendertarget>,
+    hexagon_rt: Res<HexagonRenderTarget>,
+) {
+    let mesh = Mesh2dHandle(meshes.add(Rectangle::new(1., 1.)));
+
+    // Black backing so the live composite doesn't show through transparent parts
+    commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: mesh.clone(),
+            material: materials.add(ColorMaterial::from(Color::BLACK)),
+            transform: Transform::from_xyz(0., 0., 10.),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        RenderLayers::layer(31),
+        FreezeOverlay,
+    ));
+
+    let sources = [
+        (physics_hexagon_rt.render_target.clone(), 11.),
+        (elements2d_rt.render_target.clone(), 12.),
+        (hexagon_rt.render_target.clone(), 13.),
+    ];
+    for (source, z) in sources {
+        let Some(mut image) = images.get(&source).cloned() else { continue; };
+        image.texture_descriptor.usage = TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST;
+        let frozen = images.add(image);
+        capture.pairs.push((source, frozen.clone()));
+
+        commands.spawn((
+            MaterialMesh2dBundle {
+                mesh: mesh.clone(),
+                material: materials.add(ColorMaterial {
+                    color: Default::default(),
+                    texture: Some(frozen),
+                }),
+                transform: Transform::from_xyz(0., 0., z),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            RenderLayers::layer(31),
+            FreezeOverlay,
+        ));
+    }
+}
+
+/// Capture on the first frozen frame, show the frozen images from the next frame on
+pub fn update_freeze(
+    master: Res<MasterControls>,
+    mut capture: ResMut<FreezeCapture>,
+    mut was_frozen: Local<bool>,
+    mut query: Query<&mut Visibility, With<FreezeOverlay>>,
+) {
+    let start = master.freeze && !*was_frozen;
+    if capture.capture != start {
+        capture.capture = start;
+    }
+    let visible = master.freeze && *was_frozen;
+    *was_frozen = master.freeze;
+
+    for mut visibility in query.iter_mut() {
+        *visibility = if visible { Visibility::Visible } else { Visibility::Hidden };
+    }
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+struct FreezeCaptureLabel;
+
+struct FreezeCaptureNode;
+
+impl Node for FreezeCaptureNode {
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let Some(capture) = world.get_resource::<FreezeCapture>() else { return Ok(()); };
+        if !capture.capture { return Ok(()); }
+        let gpu_images = world.resource::<RenderAssets<Image>>();
+
+        let encoder = render_context.command_encoder();
+        for (source, frozen) in capture.pairs.iter() {
+            let (Some(source), Some(frozen)) = (gpu_images.get(source), gpu_images.get(frozen)) else { continue; };
+            if source.texture.size() != frozen.texture.size() || source.texture_format != frozen.texture_format {
+                continue;
+            }
+            encoder.copy_texture_to_texture(
+                source.texture.as_image_copy(),
+                frozen.texture.as_image_copy(),
+                source.texture.size(),
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,240 @@
+//! Master controls that act on every output at once: the projected composite, the LED tubes and
+//! the spotlights driven by them. Controlled from the GUI, keys and OSC (`/master/...`).
+
+pub mod freeze;
+
+use std::collections::HashMap;
+use bevy::prelude::*;
+use bevy::render::view::RenderLayers;
+use bevy::sprite::{MaterialMesh2dBundle, Mesh2dHandle};
+use bevy_egui::EguiContexts;
+use bevy_rosc::{method_dispatcher_system, MultiAddressOscMethod};
+use rosc::OscType;
+use crate::beat::beat_clock::BeatClock;
+use crate::led_output::collect_led_frame;
+use crate::master::freeze::{FreezePlugin, spawn_freeze_overlay, update_freeze};
+use crate::physics_hexagon::lights::led_layers::composite_led_layers;
+use crate::physics_hexagon::lights::led_tube::LedTubeLed;
+use crate::physics_hexagon::lights::physical_lights::drive_lights_system;
+
+pub struct MasterPlugin;
+
+impl Plugin for MasterPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MasterControls>();
+        app.init_resource::<MasterKeys>();
+        app.add_plugins(FreezePlugin);
+        app.add_systems(Startup, (spawn_master_overlay, spawn_freeze_overlay, spawn_master_osc));
+        app.add_systems(PreUpdate, master_osc_system.after(method_dispatcher_system::<MultiAddressOscMethod>));
+        app.add_systems(Update, (master_keys_system, update_master_fade.after(master_keys_system)));
+        app.add_systems(PostUpdate, (
+            apply_master_to_leds
+                .after(composite_led_layers)
+                .before(drive_lights_system)
+                .before(collect_led_frame),
+            update_master_overlay,
+            update_freeze,
+        ));
+    }
+}
+
+#[derive(Resource)]
+pub struct MasterControls {
+    /// 0 to 1
+    pub dimmer: f32,
+    /// Momentary, all outputs black
+    pub blackout: bool,
+    /// Momentary, all outputs white
+    pub flash: bool,
+    /// Hold the last output
+    pub freeze: bool,
+    /// Duration of the fade to black
+    pub fade_beats: f32,
+    /// Fade out to black when set, fade back in when cleared
+    pub fade_out: bool,
+    /// 1 is fully faded in
+    pub fade_level: f32,
+}
+
+impl Default for MasterControls {
+    fn default() -> Self {
+        Self {
+            dimmer: 1.,
+            blackout: false,
+            flash: false,
+            freeze: false,
+            fade_beats: 4.,
+            fade_out: false,
+            fade_level: 1.,
+        }
+    }
+}
+
+impl MasterControls {
+    /// Brightness every output is scaled with
+    pub fn level(&self) -> f32 {
+        if self.blackout { 0. } else { self.dimmer.clamp(0., 1.) * self.fade_level }
+    }
+
+    pub fn apply(&self, color: Color) -> Color {
+        if self.flash { Color::WHITE } else { color * self.level() }
+    }
+}
+
+#[derive(Resource)]
+pub struct MasterKeys {
+    pub blackout: KeyCode,
+    pub flash: KeyCode,
+    pub freeze: KeyCode,
+    pub fade: KeyCode,
+}
+
+impl Default for MasterKeys {
+    fn default() -> Self {
+        Self {
+            blackout: KeyCode::KeyB,
+            flash: KeyCode::KeyF,
+            freeze: KeyCode::KeyH,
+            fade: KeyCode::KeyG,
+        }
+    }
+}
+
+/// Blackout and flash are held, freeze and fade toggle
+fn master_keys_system(
+    mut master: ResMut<MasterControls>,
+    master_keys: Res<MasterKeys>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut contexts: EguiContexts,
+) {
+    if contexts.ctx_mut().wants_keyboard_input() { return; }
+
+    if keys.just_pressed(master_keys.blackout) { master.blackout = true; }
+    if keys.just_released(master_keys.blackout) { master.blackout = false; }
+    if keys.just_pressed(master_keys.flash) { master.flash = true; }
+    if keys.just_released(master_keys.flash) { master.flash = false; }
+    if keys.just_pressed(master_keys.freeze) { master.freeze = !master.freeze; }
+    if keys.just_pressed(master_keys.fade) { master.fade_out = !master.fade_out; }
+}
+
+#[derive(Component)]
+pub struct MasterOscReceiver;
+
+pub const MASTER_OSC_ADDRESSES: [&str; 6] = [
+    "/master/dimmer",
+    "/master/blackout",
+    "/master/flash",
+    "/master/freeze",
+    "/master/fade",
+    "/master/fade_beats",
+];
+
+fn spawn_master_osc(mut commands: Commands) {
+    commands.spawn((
+        MultiAddressOscMethod::new(MASTER_OSC_ADDRESSES.iter().map(|address| address.to_string()).collect()).unwrap(),
+        MasterOscReceiver,
+    ));
+}
+
+fn osc_value(args: &[OscType]) -> Option<f32> {
+    match args.first()? {
+        OscType::Float(v) => { Some(*v) }
+        OscType::Double(v) => { Some(*v as f32) }
+        OscType::Int(v) => { Some(*v as f32) }
+        OscType::Bool(v) => { Some(if *v { 1. } else { 0. }) }
+        _ => { None }
+    }
+}
+
+/// Switches take 1 or 0, freeze and fade toggle when sent without an argument
+fn master_osc_system(
+    mut query: Query<&mut MultiAddressOscMethod, (With<MasterOscReceiver>, Changed<MultiAddressOscMethod>)>,
+    mut master: ResMut<MasterControls>,
+) {
+    for mut osc in query.iter_mut() {
+        while let Some(message) = osc.get_message() {
+            let value = osc_value(&message.args);
+            let on = value.map(|v| v > 0.5);
+            match message.addr.as_str() {
+                "/master/dimmer" => { if let Some(v) = value { master.dimmer = v.clamp(0., 1.); } }
+                "/master/blackout" => { master.blackout = on.unwrap_or(true); }
+                "/master/flash" => { master.flash = on.unwrap_or(true); }
+                "/master/freeze" => { master.freeze = on.unwrap_or(!master.freeze); }
+                "/master/fade" => { master.fade_out = on.unwrap_or(!master.fade_out); }
+                "/master/fade_beats" => { if let Some(v) = value { master.fade_beats = v.max(0.); } }
+                _ => {}
+            }
+        }
+    }
+}
+
+fn update_master_fade(
+    mut master: ResMut<MasterControls>,
+    clock: Res<BeatClock>,
+    time: Res<Time<Real>>,
+) {
+    let target = if master.fade_out { 0. } else { 1. };
+    let duration = master.fade_beats * clock.beat_duration();
+    let step = if duration > 0. { time.delta_seconds() / duration } else { 1. };
+    let level = master.fade_level;
+    if level != target {
+        master.fade_level = if level < target { (level + step).min(target) } else { (level - step).max(target) };
+    }
+}
+
+/// Hold the LED colours while frozen and apply the master level
+fn apply_master_to_leds(
+    master: Res<MasterControls>,
+    mut frozen: Local<HashMap<Entity, Color>>,
+    mut query: Query<(Entity, &mut LedTubeLed)>,
+) {
+    for (entity, mut ltl) in query.iter_mut() {
+        if master.freeze {
+            if let Some(color) = frozen.get(&entity) {
+                ltl.color = *color;
+            }
+        } else {
+            frozen.insert(entity, ltl.color);
+        }
+        ltl.color = master.apply(ltl.color);
+    }
+}
+
+/// Quad in front of the projected composite that dims, blacks out and flashes it
+#[derive(Component)]
+pub struct MasterOverlay;
+
+fn spawn_master_overlay(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: Mesh2dHandle(meshes.add(Rectangle::new(1., 1.))),
+            material: materials.add(ColorMaterial::from(Color::NONE)),
+            transform: Transform::from_xyz(0., 0., 20.),
+            ..default()
+        },
+        RenderLayers::layer(31),
+        MasterOverlay,
+    ));
+}
+
+fn update_master_overlay(
+    master: Res<MasterControls>,
+    query: Query<&Handle<ColorMaterial>, With<MasterOverlay>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let color = if master.flash {
+        Color::WHITE
+    } else {
+        Color::rgba(0., 0., 0., 1. - master.level())
+    };
+    for handle in query.iter() {
+        let Some(material) = materials.get(handle) else { continue; };
+        if material.color != color {
+            materials.get_mut(handle).unwrap().color = color;
+        }
+    }
+}
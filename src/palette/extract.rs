@@ -0,0 +1,285 @@
+//! Palette extraction from images and animated GIFs. Pixels of every frame are sampled and
+//! clustered with median cut, optionally refined with k-means. Clusters are ordered so the vivid,
+//! common colours end up in the primary and secondary slots.
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use image::AnimationDecoder;
+use image::codecs::gif::GifDecoder;
+use strum_macros::EnumIter;
+use crate::palette::{Gradient, Palette, PaletteColor};
+
+/// Searched in order, the media library keeps its GIFs in `assets/media`
+pub const IMAGE_DIRS: [&str; 2] = ["assets", "assets/media"];
+pub const IMAGE_EXTENSIONS: [&str; 2] = ["png", "gif"];
+/// Pixels used for clustering, spread over all frames
+const MAX_SAMPLES: usize = 40000;
+const KMEANS_ITERATIONS: usize = 16;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, EnumIter)]
+pub enum ExtractMethod {
+    #[default]
+    KMeans,
+    MedianCut,
+}
+
+impl ExtractMethod {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "kmeans" | "k-means" => { Some(Self::KMeans) }
+            "median-cut" | "mediancut" => { Some(Self::MedianCut) }
+            _ => { None }
+        }
+    }
+}
+
+fn is_image(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| IMAGE_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// Images in the asset folders a palette can be extracted from
+pub fn image_files() -> Vec<PathBuf> {
+    let mut files = vec![];
+    for dir in IMAGE_DIRS {
+        let Ok(entries) = std::fs::read_dir(dir) else { continue; };
+        let mut dir_files: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| is_image(path))
+            .collect();
+        dir_files.sort();
+        files.extend(dir_files);
+    }
+    files
+}
+
+/// Opaque pixels of every frame
+fn load_pixels(path: &Path) -> Result<Vec<[u8; 3]>, String> {
+    let is_gif = path.extension().map(|ext| ext.to_string_lossy().to_lowercase() == "gif").unwrap_or(false);
+    let frames = if is_gif {
+        let file = File::open(path).map_err(|e| e.to_string())?;
+        let decoder = GifDecoder::new(BufReader::new(file)).map_err(|e| e.to_string())?;
+        decoder.into_frames().collect_frames().map_err(|e| e.to_string())?
+            .into_iter()
+            .map(|frame| frame.into_buffer())
+            .collect()
+    } else {
+        vec![image::open(path).map_err(|e| e.to_string())?.to_rgba8()]
+    };
+
+    let total: usize = frames.iter().map(|frame| frame.pixels().len()).sum();
+    let stride = (total / MAX_SAMPLES).max(1);
+    let pixels: Vec<[u8; 3]> = frames.iter()
+        .flat_map(|frame| frame.pixels())
+        .step_by(stride)
+        .filter(|pixel| pixel.0[3] >= 128)
+        .map(|pixel| [pixel.0[0], pixel.0[1], pixel.0[2]])
+        .collect();
+    if pixels.is_empty() {
+        return Err("image has no opaque pixels".to_string());
+    }
+    Ok(pixels)
+}
+
+struct Cluster {
+    color: [f32; 3],
+    weight: usize,
+}
+
+fn mean(pixels: &[[u8; 3]]) -> [f32; 3] {
+    let mut sum = [0f32; 3];
+    for pixel in pixels {
+        for c in 0..3 { sum[c] += pixel[c] as f32; }
+    }
+    let n = pixels.len().max(1) as f32 * 255.;
+    [sum[0] / n, sum[1] / n, sum[2] / n]
+}
+
+fn channel_range(pixels: &[[u8; 3]], channel: usize) -> u8 {
+    let min = pixels.iter().map(|pixel| pixel[channel]).min().unwrap_or(0);
+    let max = pixels.iter().map(|pixel| pixel[channel]).max().unwrap_or(0);
+    max - min
+}
+
+/// Split the box with the widest channel at its median until there are `count` boxes
+fn median_cut(pixels: &[[u8; 3]], count: usize) -> Vec<Cluster> {
+    let mut boxes = vec![pixels.to_vec()];
+    while boxes.len() < count {
+        let widest = boxes.iter().enumerate()
+            .filter(|(_, pixels)| pixels.len() > 1)
+            .map(|(i, pixels)| {
+                let (channel, range) = (0..3).map(|c| (c, channel_range(pixels, c))).max_by_key(|(_, range)| *range).unwrap();
+                (i, channel, range)
+            })
+            .max_by_key(|(_, _, range)| *range);
+        let Some((i, channel, range)) = widest else { break; };
+        if range == 0 { break; }
+
+        let mut pixels = boxes.swap_remove(i);
+        pixels.sort_unstable_by_key(|pixel| pixel[channel]);
+        let upper = pixels.split_off(pixels.len() / 2);
+        boxes.push(pixels);
+        boxes.push(upper);
+    }
+    boxes.iter().map(|pixels| Cluster { color: mean(pixels), weight: pixels.len() }).collect()
+}
+
+fn distance(a: [f32; 3], b: [f32; 3]) -> f32 {
+    (0..3).map(|c| (a[c] - b[c]) * (a[c] - b[c])).sum()
+}
+
+/// Refine the median cut clusters with k-means
+fn kmeans(pixels: &[[u8; 3]], count: usize) -> Vec<Cluster> {
+    let mut centroids: Vec<[f32; 3]> = median_cut(pixels, count).iter().map(|cluster| cluster.color).collect();
+    let mut weights = vec![0; centroids.len()];
+    for _ in 0..KMEANS_ITERATIONS {
+        let mut sums = vec![[0f32; 3]; centroids.len()];
+        weights = vec![0; centroids.len()];
+        for pixel in pixels {
+            let color = [pixel[0] as f32 / 255., pixel[1] as f32 / 255., pixel[2] as f32 / 255.];
+            let nearest = (0..centroids.len())
+                .min_by(|a, b| distance(color, centroids[*a]).total_cmp(&distance(color, centroids[*b])))
+                .unwrap();
+            for c in 0..3 { sums[nearest][c] += color[c]; }
+            weights[nearest] += 1;
+        }
+
+        let mut moved = false;
+        for (i, centroid) in centroids.iter_mut().enumerate() {
+            if weights[i] == 0 { continue; }
+            let new = [sums[i][0] / weights[i] as f32, sums[i][1] / weights[i] as f32, sums[i][2] / weights[i] as f32];
+            moved |= distance(new, *centroid) > 1e-6;
+            *centroid = new;
+        }
+        if !moved { break; }
+    }
+    centroids.into_iter().zip(weights)
+        .filter(|(_, weight)| *weight > 0)
+        .map(|(color, weight)| Cluster { color, weight })
+        .collect()
+}
+
+fn saturation_value(color: [f32; 3]) -> (f32, f32) {
+    let max = color[0].max(color[1]).max(color[2]);
+    let min = color[0].min(color[1]).min(color[2]);
+    (if max > 0. { (max - min) / max } else { 0. }, max)
+}
+
+fn luminance(color: [f32; 3]) -> f32 {
+    0.2126 * color[0] + 0.7152 * color[1] + 0.0722 * color[2]
+}
+
+/// Palette of up to `count` colours, the most vivid common colours first and a gradient ordered
+/// by luminance
+pub fn extract_palette(path: &Path, count: usize, method: ExtractMethod) -> Result<Palette, String> {
+    let pixels = load_pixels(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let count = count.max(2);
+    let mut clusters = match method {
+        ExtractMethod::KMeans => { kmeans(&pixels, count) }
+        ExtractMethod::MedianCut => { median_cut(&pixels, count) }
+    };
+
+    // Dark and grey backgrounds are common but make poor tube colours
+    let score = |cluster: &Cluster| {
+        let (saturation, value) = saturation_value(cluster.color);
+        (cluster.weight as f32).sqrt() * (0.1 + saturation * value)
+    };
+    clusters.sort_by(|a, b| score(b).total_cmp(&score(a)));
+
+    let colors = clusters.iter().enumerate().map(|(i, cluster)| PaletteColor {
+        name: format!("color{}", i + 1),
+        color: (cluster.color[0], cluster.color[1], cluster.color[2]),
+    }).collect();
+
+    let mut by_luminance: Vec<[f32; 3]> = clusters.iter().map(|cluster| cluster.color).collect();
+    by_luminance.sort_by(|a, b| luminance(*a).total_cmp(&luminance(*b)));
+    let last = by_luminance.len().saturating_sub(1).max(1) as f32;
+    let gradient = Gradient {
+        name: "luminance".to_string(),
+        stops: by_luminance.iter().enumerate().map(|(i, c)| (i as f32 / last, (c[0], c[1], c[2]))).collect(),
+    };
+
+    Ok(Palette {
+        name: path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default(),
+        colors,
+        gradients: vec![gradient],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, RgbaImage};
+    use super::*;
+
+    fn close(a: (f32, f32, f32), b: (f32, f32, f32)) -> bool {
+        (a.0 - b.0).abs() < 1e-3 && (a.1 - b.1).abs() < 1e-3 && (a.2 - b.2).abs() < 1e-3
+    }
+
+    /// PNG in the temp folder, `pixel` gets the index of every pixel of a 100x100 image
+    fn temp_image(name: &str, pixel: impl Fn(u32) -> [u8; 4]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("vjpyree_extract_{}.png", name));
+        RgbaImage::from_fn(100, 100, |x, y| Rgba(pixel(y * 100 + x))).save(&path).unwrap();
+        path
+    }
+
+    #[test]
+    fn parse_method() {
+        assert_eq!(ExtractMethod::parse("k-means"), Some(ExtractMethod::KMeans));
+        assert_eq!(ExtractMethod::parse("median-cut"), Some(ExtractMethod::MedianCut));
+        assert_eq!(ExtractMethod::parse("octree"), None);
+    }
+
+    #[test]
+    fn median_cut_splits_widest_channel() {
+        let pixels = [[255, 0, 0], [255, 0, 0], [0, 0, 255], [0, 0, 200]];
+        let clusters = median_cut(&pixels, 2);
+        assert_eq!(clusters.len(), 2);
+        assert_eq!(clusters.iter().map(|c| c.weight).sum::<usize>(), 4);
+        assert!(clusters.iter().any(|c| c.color == [1., 0., 0.]));
+    }
+
+    #[test]
+    fn median_cut_stops_on_uniform_pixels() {
+        assert_eq!(median_cut(&[[10, 20, 30]; 8], 4).len(), 1);
+    }
+
+    #[test]
+    fn kmeans_finds_both_colours() {
+        let mut pixels = vec![[255, 0, 0]; 30];
+        pixels.extend([[0, 255, 0]; 10]);
+        let clusters = kmeans(&pixels, 2);
+        let mut weights: Vec<usize> = clusters.iter().map(|c| c.weight).collect();
+        weights.sort();
+        assert_eq!(weights, vec![10, 30]);
+    }
+
+    #[test]
+    fn vivid_colour_comes_first() {
+        // Three quarters dark grey background, one quarter red
+        let path = temp_image("vivid", |i| if i % 4 == 0 { [255, 0, 0, 255] } else { [25, 25, 25, 255] });
+        // Median cut alone would split the background and mix half of it into the red box
+        let palette = extract_palette(&path, 2, ExtractMethod::KMeans).unwrap();
+        assert_eq!(palette.name, "vjpyree_extract_vivid");
+        assert_eq!(palette.colors.len(), 2);
+        assert!(close(palette.colors[0].color, (1., 0., 0.)));
+        let grey = 25. / 255.;
+        assert!(close(palette.colors[1].color, (grey, grey, grey)));
+        // Darkest first
+        let stops = &palette.gradients[0].stops;
+        assert!(close(stops[0].1, (grey, grey, grey)));
+        assert!(close(stops[1].1, (1., 0., 0.)));
+    }
+
+    #[test]
+    fn transparent_pixels_are_ignored() {
+        let path = temp_image("transparent", |i| if i % 2 == 0 { [0, 0, 255, 0] } else { [0, 255, 0, 255] });
+        let palette = extract_palette(&path, 4, ExtractMethod::KMeans).unwrap();
+        assert_eq!(palette.colors.len(), 1);
+        assert!(close(palette.colors[0].color, (0., 1., 0.)));
+
+        let path = temp_image("empty", |_| [0, 0, 0, 0]);
+        assert!(extract_palette(&path, 4, ExtractMethod::KMeans).is_err());
+    }
+}
@@ -0,0 +1,438 @@
+//! Colour palettes with any number of named colours and gradients, loaded from
+//! `assets/palettes/*.ron`. The active palette drives the primary and secondary slots of
+//! `AnimColors`, crossfades into the next palette and can cycle through the library on beat or bar.
+
+pub mod extract;
+
+use std::path::{Path, PathBuf};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use strum_macros::EnumIter;
+use crate::anims::AnimColors;
+use crate::beat::beat_clock::{beat_clock_system, BeatClock};
+use crate::beat::{BEATS_PER_BAR, BeatEvent};
+use crate::swirl::SwirlAutomation;
+
+pub const PALETTE_DIR: &str = "assets/palettes";
+pub const PALETTE_EXTENSION: &str = "ron";
+/// Stops a gradient is resampled to when two palettes are blended
+const BLEND_STOPS: usize = 16;
+
+pub struct PalettePlugin;
+
+impl Plugin for PalettePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(PaletteLibrary::load());
+        app.init_resource::<ActivePalette>();
+        app.add_systems(PreUpdate, update_palette.after(beat_clock_system));
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PaletteColor {
+    pub name: String,
+    /// sRGB, 0 to 1
+    pub color: (f32, f32, f32),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Gradient {
+    pub name: String,
+    /// Position from 0 to 1 and sRGB colour, sorted by position
+    pub stops: Vec<(f32, (f32, f32, f32))>,
+}
+
+impl Gradient {
+    pub fn sample(&self, t: f32) -> Color {
+        let t = t.clamp(0., 1.);
+        let Some(first) = self.stops.first() else { return Color::BLACK; };
+        if t <= first.0 { return rgb(first.1); }
+        for pair in self.stops.windows(2) {
+            let ((t0, c0), (t1, c1)) = (pair[0], pair[1]);
+            if t <= t1 {
+                let f = if t1 > t0 { (t - t0) / (t1 - t0) } else { 1. };
+                return lerp_color(rgb(c0), rgb(c1), f);
+            }
+        }
+        rgb(self.stops.last().unwrap().1)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Palette {
+    /// Taken from the file name when empty
+    #[serde(default)]
+    pub name: String,
+    pub colors: Vec<PaletteColor>,
+    #[serde(default)]
+    pub gradients: Vec<Gradient>,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            name: "Red/Blue".to_string(),
+            colors: vec![
+                PaletteColor { name: "red".to_string(), color: (1., 0., 0.) },
+                PaletteColor { name: "blue".to_string(), color: (0., 0., 1.) },
+            ],
+            gradients: vec![],
+        }
+    }
+}
+
+impl Palette {
+    /// Colour of a slot, slots wrap around the palette
+    pub fn color(&self, slot: usize) -> Color {
+        if self.colors.is_empty() { return Color::BLACK; }
+        rgb(self.colors[slot % self.colors.len()].color)
+    }
+
+    pub fn color_by_name(&self, name: &str) -> Option<Color> {
+        self.colors.iter().find(|c| c.name == name).map(|c| rgb(c.color))
+    }
+
+    /// Sample a gradient, palettes without gradients run through their colours
+    pub fn sample(&self, gradient: usize, t: f32) -> Color {
+        match self.gradients.get(gradient).or(self.gradients.first()) {
+            Some(gradient) => { gradient.sample(t) }
+            None => { self.colors_gradient().sample(t) }
+        }
+    }
+
+    pub fn sample_by_name(&self, name: &str, t: f32) -> Color {
+        let index = self.gradients.iter().position(|g| g.name == name).unwrap_or(0);
+        self.sample(index, t)
+    }
+
+    fn colors_gradient(&self) -> Gradient {
+        let last = self.colors.len().saturating_sub(1).max(1) as f32;
+        Gradient {
+            name: String::new(),
+            stops: self.colors.iter().enumerate().map(|(i, c)| (i as f32 / last, c.color)).collect(),
+        }
+    }
+
+    /// Blend of two palettes, the longer one defines the slots and gradients
+    pub fn lerp(&self, other: &Palette, f: f32) -> Palette {
+        if f <= 0. { return self.clone(); }
+        if f >= 1. { return other.clone(); }
+        let longer = if other.colors.len() >= self.colors.len() { other } else { self };
+        let colors = longer.colors.iter().enumerate().map(|(i, c)| PaletteColor {
+            name: c.name.clone(),
+            color: tuple(lerp_color(self.color(i), other.color(i), f)),
+        }).collect();
+        let gradient_count = self.gradients.len().max(other.gradients.len()).max(1);
+        let gradients = (0..gradient_count).map(|g| Gradient {
+            name: longer.gradients.get(g).map(|g| g.name.clone()).unwrap_or_default(),
+            stops: (0..BLEND_STOPS).map(|i| {
+                let t = i as f32 / (BLEND_STOPS - 1) as f32;
+                (t, tuple(lerp_color(self.sample(g, t), other.sample(g, t), f)))
+            }).collect(),
+        }).collect();
+        Palette { name: other.name.clone(), colors, gradients }
+    }
+}
+
+pub fn rgb(color: (f32, f32, f32)) -> Color {
+    Color::rgb(color.0, color.1, color.2)
+}
+
+pub fn tuple(color: Color) -> (f32, f32, f32) {
+    let [r, g, b, _] = color.as_rgba_f32();
+    (r, g, b)
+}
+
+pub fn lerp_color(a: Color, b: Color, f: f32) -> Color {
+    let [ar, ag, ab, aa] = a.as_rgba_f32();
+    let [br, bg, bb, ba] = b.as_rgba_f32();
+    Color::rgba(ar + (br - ar) * f, ag + (bg - ag) * f, ab + (bb - ab) * f, aa + (ba - aa) * f)
+}
+
+#[derive(Resource)]
+pub struct PaletteLibrary {
+    pub palettes: Vec<Palette>,
+}
+
+fn palette_files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else { return vec![]; };
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().map(|ext| ext == PALETTE_EXTENSION).unwrap_or(false))
+        .collect();
+    files.sort();
+    files
+}
+
+impl PaletteLibrary {
+    /// Load every palette file, falling back to the built in palette
+    pub fn load() -> Self {
+        let mut palettes = vec![];
+        for path in palette_files(Path::new(PALETTE_DIR)) {
+            let palette = std::fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|content| ron::from_str::<Palette>(&content).map_err(|e| e.to_string()));
+            match palette {
+                Ok(mut palette) => {
+                    if palette.name.is_empty() {
+                        palette.name = path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+                    }
+                    palettes.push(palette);
+                }
+                Err(e) => { error!("Couldn't load palette {}: {}", path.display(), e); }
+            }
+        }
+        info!("Loaded {} palettes from {}", palettes.len(), PALETTE_DIR);
+        if palettes.is_empty() {
+            palettes.push(Palette::default());
+        }
+        Self { palettes }
+    }
+
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.palettes.iter().position(|palette| palette.name == name)
+    }
+
+    /// Add a palette or replace the one with the same name, returns its index
+    pub fn insert(&mut self, palette: Palette) -> usize {
+        match self.find(&palette.name) {
+            Some(index) => {
+                self.palettes[index] = palette;
+                index
+            }
+            None => {
+                self.palettes.push(palette);
+                self.palettes.len() - 1
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, EnumIter)]
+pub enum PaletteCycle {
+    #[default]
+    Off,
+    Beat,
+    Bar,
+}
+
+#[derive(Resource)]
+pub struct ActivePalette {
+    /// Write the palette into `AnimColors`
+    pub enabled: bool,
+    /// Use the primary colour as axis of the swirl colour rotation
+    pub drive_swirl: bool,
+    /// Index in the library of the palette faded to
+    pub index: usize,
+    pub primary_slot: usize,
+    pub secondary_slot: usize,
+    /// Crossfade duration, 0 switches instantly
+    pub fade_beats: f32,
+    pub cycle: PaletteCycle,
+    /// Beats or bars between palette changes
+    pub cycle_every: u64,
+    from: Palette,
+    to: Palette,
+    progress: f32,
+}
+
+/// Starts on the first palette of the library, the one the GUI shows as selected
+impl FromWorld for ActivePalette {
+    fn from_world(world: &mut World) -> Self {
+        let palette = world.get_resource::<PaletteLibrary>()
+            .and_then(|library| library.palettes.first().cloned())
+            .unwrap_or_default();
+        Self {
+            enabled: false,
+            drive_swirl: false,
+            index: 0,
+            primary_slot: 0,
+            secondary_slot: 1,
+            fade_beats: 2.,
+            cycle: PaletteCycle::Off,
+            cycle_every: 1,
+            from: palette.clone(),
+            to: palette,
+            progress: 1.,
+        }
+    }
+}
+
+impl ActivePalette {
+    /// Crossfade from the current blend into a palette of the library
+    pub fn select(&mut self, library: &PaletteLibrary, index: usize) {
+        let Some(palette) = library.palettes.get(index) else { return; };
+        self.from = self.current();
+        self.to = palette.clone();
+        self.index = index;
+        self.progress = 0.;
+    }
+
+    /// Crossfade into a palette that isn't part of the library
+    pub fn fade_to(&mut self, palette: Palette) {
+        self.from = self.current();
+        self.to = palette;
+        self.progress = 0.;
+    }
+
+    pub fn is_fading(&self) -> bool {
+        self.progress < 1.
+    }
+
+    pub fn progress(&self) -> f32 {
+        self.progress
+    }
+
+    /// Palette as seen this frame, blended while crossfading
+    pub fn current(&self) -> Palette {
+        self.from.lerp(&self.to, self.progress)
+    }
+
+    pub fn target(&self) -> &Palette {
+        &self.to
+    }
+
+    pub fn color(&self, slot: usize) -> Color {
+        lerp_color(self.from.color(slot), self.to.color(slot), self.progress)
+    }
+
+    pub fn primary(&self) -> Color {
+        self.color(self.primary_slot)
+    }
+
+    pub fn secondary(&self) -> Color {
+        self.color(self.secondary_slot)
+    }
+
+    /// Sample a gradient at `t` from 0 to 1
+    pub fn sample(&self, gradient: usize, t: f32) -> Color {
+        lerp_color(self.from.sample(gradient, t), self.to.sample(gradient, t), self.progress)
+    }
+}
+
+/// Cycle on beat or bar, advance the crossfade and write the primary and secondary slots
+pub fn update_palette(
+    mut active: ResMut<ActivePalette>,
+    library: Res<PaletteLibrary>,
+    mut colors: ResMut<AnimColors>,
+    mut swirl: ResMut<SwirlAutomation>,
+    clock: Res<BeatClock>,
+    time: Res<Time<Real>>,
+    mut beat_reader: EventReader<BeatEvent>,
+    mut was_enabled: Local<bool>,
+) {
+    for beat in beat_reader.read() {
+        let every = match active.cycle {
+            PaletteCycle::Off => { continue; }
+            PaletteCycle::Beat => { active.cycle_every.max(1) }
+            PaletteCycle::Bar => { active.cycle_every.max(1) * BEATS_PER_BAR }
+        };
+        if beat.count % every == 0 && !library.palettes.is_empty() {
+            let next = (active.index + 1) % library.palettes.len();
+            active.select(&library, next);
+        }
+    }
+
+    let duration = active.fade_beats * clock.beat_duration();
+    if active.is_fading() {
+        active.progress = if duration > 0. {
+            (active.progress + time.delta_seconds() / duration).min(1.)
+        } else {
+            1.
+        };
+    }
+
+    // Switching the palette on takes over from rainbow and harmony, picking one of those again
+    // afterwards takes over from the palette
+    if active.enabled && !*was_enabled {
+        colors.anim = 0;
+    }
+    *was_enabled = active.enabled;
+    if active.enabled && colors.anim == 0 {
+        colors.primary = active.primary();
+        colors.secondary = active.secondary();
+    }
+    let col_rot = if active.drive_swirl { Some(active.primary()) } else { None };
+    if swirl.palette_col_rot != col_rot {
+        swirl.palette_col_rot = col_rot;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn color(name: &str, color: (f32, f32, f32)) -> PaletteColor {
+        PaletteColor { name: name.to_string(), color }
+    }
+
+    fn three_colors() -> Palette {
+        Palette {
+            name: "Three".to_string(),
+            colors: vec![color("green", (0., 1., 0.)), color("white", (1., 1., 1.)), color("black", (0., 0., 0.))],
+            gradients: vec![],
+        }
+    }
+
+    #[test]
+    fn gradient_sample() {
+        let gradient = Gradient {
+            name: "fire".to_string(),
+            stops: vec![(0., (0., 0., 0.)), (0.5, (1., 0., 0.)), (1., (1., 1., 1.))],
+        };
+        assert_eq!(tuple(gradient.sample(0.25)), (0.5, 0., 0.));
+        assert_eq!(tuple(gradient.sample(0.75)), (1., 0.5, 0.5));
+        assert_eq!(tuple(gradient.sample(-1.)), (0., 0., 0.));
+        assert_eq!(tuple(gradient.sample(2.)), (1., 1., 1.));
+        assert_eq!(Gradient { name: String::new(), stops: vec![] }.sample(0.5), Color::BLACK);
+    }
+
+    #[test]
+    fn lerp_ends_are_the_palettes() {
+        let (a, b) = (Palette::default(), three_colors());
+        assert_eq!(a.lerp(&b, 0.), a);
+        assert_eq!(a.lerp(&b, 1.), b);
+    }
+
+    #[test]
+    fn lerp_blends_slots_of_the_longer_palette() {
+        let blend = Palette::default().lerp(&three_colors(), 0.5);
+        assert_eq!(blend.name, "Three");
+        assert_eq!(blend.colors, vec![
+            color("green", (0.5, 0.5, 0.)),
+            color("white", (0.5, 0.5, 1.)),
+            // Red/Blue wraps around to red
+            color("black", (0.5, 0., 0.)),
+        ]);
+        // The shorter palette on the target side keeps the slots of the longer one
+        let blend = three_colors().lerp(&Palette::default(), 0.5);
+        assert_eq!(blend.colors.len(), 3);
+        assert_eq!(blend.colors[0].name, "green");
+    }
+
+    #[test]
+    fn lerp_resamples_gradients() {
+        let blend = Palette::default().lerp(&three_colors(), 0.5);
+        assert_eq!(blend.gradients.len(), 1);
+        let stops = &blend.gradients[0].stops;
+        assert_eq!(stops.len(), BLEND_STOPS);
+        assert_eq!(stops[0], (0., (0.5, 0.5, 0.)));
+        assert_eq!(stops[BLEND_STOPS - 1], (1., (0., 0., 0.5)));
+    }
+
+    #[test]
+    fn crossfade_progress() {
+        let mut world = World::new();
+        world.insert_resource(PaletteLibrary { palettes: vec![Palette::default(), three_colors()] });
+        let mut active = ActivePalette::from_world(&mut world);
+        assert_eq!(active.current(), Palette::default());
+        active.select(world.resource::<PaletteLibrary>(), 1);
+        assert!(active.is_fading());
+        assert_eq!(tuple(active.primary()), (1., 0., 0.));
+        active.progress = 0.5;
+        assert_eq!(tuple(active.primary()), (0.5, 0.5, 0.));
+        assert_eq!(tuple(active.secondary()), (0.5, 0.5, 1.));
+        active.progress = 1.;
+        assert_eq!(active.current(), three_colors());
+    }
+}
@@ -0,0 +1,97 @@
+//! Layer stack for the LED tubes. Every animation writes into its own layer, the layers are then
+//! composited into `LedTubeLed::color` once per frame.
+
+use bevy::prelude::{Color, Query, Res, Resource};
+use strum::{EnumCount, IntoEnumIterator};
+use strum_macros::{EnumCount, EnumIter};
+use crate::physics_hexagon::lights::led_tube::LedTubeLed;
+
+/// Layers are composited bottom to top in declaration order
+#[derive(Copy, Clone, EnumIter, EnumCount, Eq, PartialEq, Debug, Hash)]
+pub enum LedLayer {
+    Base,
+    Wave,
+    Shader,
+    Sweep,
+    PixelMap,
+    Punch,
+    Strobe,
+}
+
+impl LedLayer {
+    pub fn index(&self) -> usize { *self as usize }
+}
+
+#[derive(Copy, Clone, EnumIter, Eq, PartialEq, Debug, Default)]
+pub enum LedBlendMode {
+    Add,
+    Screen,
+    Multiply,
+    Max,
+    #[default]
+    Alpha,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct LedLayerSetting {
+    pub opacity: f32,
+    pub blend_mode: LedBlendMode,
+}
+
+impl Default for LedLayerSetting {
+    fn default() -> Self {
+        Self {
+            opacity: 1.,
+            blend_mode: LedBlendMode::Alpha,
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct LedLayerSettings {
+    pub layers: [LedLayerSetting; LedLayer::COUNT],
+}
+
+impl LedLayerSettings {
+    pub fn get(&self, layer: LedLayer) -> &LedLayerSetting { &self.layers[layer.index()] }
+    pub fn get_mut(&mut self, layer: LedLayer) -> &mut LedLayerSetting { &mut self.layers[layer.index()] }
+}
+
+/// Blend `src` on top of `dst`. The alpha of `src` is clamped to 0..1 and scaled by the opacity.
+pub fn blend_color(dst: Color, src: Color, blend_mode: LedBlendMode, opacity: f32) -> Color {
+    let d = dst.as_rgba_f32();
+    let s = src.as_rgba_f32();
+    let a = s[3].clamp(0., 1.) * opacity.clamp(0., 1.);
+    if a <= 0. {
+        return dst;
+    }
+
+    let mut out = [0f32; 3];
+    for c in 0..3 {
+        let blended = match blend_mode {
+            LedBlendMode::Add => { d[c] + s[c] }
+            LedBlendMode::Screen => { 1. - (1. - d[c].clamp(0., 1.)) * (1. - s[c].clamp(0., 1.)) }
+            LedBlendMode::Multiply => { d[c] * s[c] }
+            LedBlendMode::Max => { d[c].max(s[c]) }
+            LedBlendMode::Alpha => { s[c] }
+        };
+        out[c] = d[c] + (blended - d[c]) * a;
+    }
+
+    Color::rgb(out[0], out[1], out[2])
+}
+
+/// Composite all layers of every LED into its final colour
+pub fn composite_led_layers(
+    mut query: Query<&mut LedTubeLed>,
+    settings: Res<LedLayerSettings>,
+) {
+    for mut ltl in query.iter_mut() {
+        let mut color = Color::BLACK;
+        for layer in LedLayer::iter() {
+            let setting = settings.get(layer);
+            color = blend_color(color, ltl.layer(layer), setting.blend_mode, setting.opacity);
+        }
+        ltl.color = color;
+    }
+}
@@ -0,0 +1,152 @@
+//! Symmetry modifiers for the tube animations. Animations only need to render the canonical
+//! sector, every other LED copies the layers of the LED it folds onto.
+
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use bevy::prelude::*;
+use strum::{EnumCount, IntoEnumIterator};
+use strum_macros::EnumIter;
+use crate::physics_hexagon::lights::led_layers::LedLayer;
+use crate::physics_hexagon::lights::led_tube::{LedTube, LedTubeLed, TubeIndex};
+
+/// Max distance between a folded position and the LED it's mapped to, a bit more than half
+/// the LED spacing so eyeballed tube positions still match up
+const MATCH_DISTANCE: f32 = 8.;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, EnumIter)]
+pub enum SymmetryMode {
+    #[default]
+    None,
+    /// Right half copies the left half
+    MirrorX,
+    /// Bottom half copies the top half
+    MirrorY,
+    MirrorXY,
+    /// Every 60° sector copies the sector from 0° to 60°
+    Rotate60,
+    /// Rotate60 with each sector mirrored at its 30° line
+    Kaleidoscope,
+}
+
+impl SymmetryMode {
+    /// Fold a position (centred on the main hexagon) into the canonical sector
+    pub fn fold(&self, position: Vec2) -> Vec2 {
+        match self {
+            SymmetryMode::None => { position }
+            SymmetryMode::MirrorX => { Vec2::new(-position.x.abs(), position.y) }
+            SymmetryMode::MirrorY => { Vec2::new(position.x, position.y.abs()) }
+            SymmetryMode::MirrorXY => { Vec2::new(-position.x.abs(), position.y.abs()) }
+            SymmetryMode::Rotate60 => {
+                let sector = (position.y.atan2(position.x) / (PI / 3.)).floor();
+                Vec2::from_angle(-sector * PI / 3.).rotate(position)
+            }
+            SymmetryMode::Kaleidoscope => {
+                let rotated = SymmetryMode::Rotate60.fold(position);
+                let angle = rotated.y.atan2(rotated.x);
+                if angle > PI / 6. {
+                    Vec2::from_angle(PI / 3. - angle) * rotated.length()
+                } else {
+                    rotated
+                }
+            }
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct LedSymmetry {
+    pub mode: SymmetryMode,
+    /// Layers the symmetry is applied to
+    pub layers: [bool; LedLayer::COUNT],
+    /// Randomised strobes fire a tube together with its mirrored partner
+    pub strobe_pairs: bool,
+    /// LED entity to the LED it copies, rebuilt when the mode changes
+    sources: HashMap<Entity, Entity>,
+    built_for: Option<(SymmetryMode, usize)>,
+}
+
+impl Default for LedSymmetry {
+    fn default() -> Self {
+        let mut layers = [true; LedLayer::COUNT];
+        layers[LedLayer::Base.index()] = false;
+        layers[LedLayer::PixelMap.index()] = false;
+        Self {
+            mode: SymmetryMode::None,
+            layers,
+            strobe_pairs: false,
+            sources: HashMap::new(),
+            built_for: None,
+        }
+    }
+}
+
+impl LedSymmetry {
+    fn rebuild(&mut self, leds: &[(Entity, Vec2)]) {
+        self.sources.clear();
+        self.built_for = Some((self.mode, leds.len()));
+        if self.mode == SymmetryMode::None { return; }
+
+        for (entity, position) in leds {
+            let folded = self.mode.fold(*position);
+            if folded.distance(*position) < MATCH_DISTANCE { continue; }
+            let nearest = leds.iter()
+                .map(|(other, other_position)| (*other, other_position.distance(folded)))
+                .min_by(|a, b| a.1.total_cmp(&b.1));
+            if let Some((source, distance)) = nearest {
+                if distance < MATCH_DISTANCE && source != *entity {
+                    self.sources.insert(*entity, source);
+                }
+            }
+        }
+    }
+}
+
+/// Tube mirrored at the vertical axis, if there is one
+pub fn mirror_tube(tubes: &[(TubeIndex, Vec2)], tube: TubeIndex) -> Option<TubeIndex> {
+    let (_, position) = tubes.iter().find(|(index, _)| *index == tube)?;
+    let mirrored = Vec2::new(-position.x, position.y);
+    tubes.iter()
+        .filter(|(index, other)| *index != tube && other.distance(mirrored) < MATCH_DISTANCE)
+        .map(|(index, _)| *index)
+        .next()
+}
+
+/// Copy the layers of the canonical sector to all symmetric LEDs
+pub fn apply_led_symmetry(
+    mut symmetry: ResMut<LedSymmetry>,
+    mut led_query: Query<(Entity, &mut LedTubeLed, &GlobalTransform)>,
+) {
+    let led_count = led_query.iter().len();
+    if symmetry.built_for != Some((symmetry.mode, led_count)) {
+        let leds: Vec<(Entity, Vec2)> = led_query.iter()
+            .map(|(entity, _, gt)| (entity, gt.translation().truncate()))
+            .collect();
+        symmetry.rebuild(&leds);
+    }
+    if symmetry.mode == SymmetryMode::None { return; }
+
+    let copies: Vec<(Entity, Vec<(LedLayer, Color)>)> = symmetry.sources.iter()
+        .filter_map(|(target, source)| {
+            let (_, source_ltl, _) = led_query.get(*source).ok()?;
+            let layers = LedLayer::iter()
+                .filter(|layer| symmetry.layers[layer.index()])
+                .map(|layer| (layer, source_ltl.layer(layer)))
+                .collect();
+            Some((*target, layers))
+        })
+        .collect();
+
+    for (target, layers) in copies {
+        let Ok((_, mut ltl, _)) = led_query.get_mut(target) else { continue; };
+        for (layer, color) in layers {
+            *ltl.layer_mut(layer) = color;
+        }
+    }
+}
+
+/// Tube centres, for finding mirrored tube pairs
+pub fn tube_positions(tube_query: &Query<(&LedTube, &Children, &GlobalTransform)>) -> Vec<(TubeIndex, Vec2)> {
+    tube_query.iter()
+        .map(|(led_tube, _, gt)| (led_tube.get_tube_index(), gt.translation().truncate()))
+        .collect()
+}
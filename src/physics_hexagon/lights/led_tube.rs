@@ -1,146 +1,81 @@
-use std::f32::consts::PI;
+use std::fmt;
 use bevy::math::Quat;
 use bevy::prelude::{Commands, Component, SpatialBundle, Transform, Vec2, Vec3, BuildChildren, Color};
 use bevy::utils::default;
-use strum_macros::EnumIter;
+use serde::{Deserialize, Serialize};
+use strum::EnumCount;
 use crate::parameter_animation::Pt1Anim;
+use crate::physics_hexagon::lights::led_layers::LedLayer;
+use crate::physics_hexagon::lights::tube_layout::TubeDefinition;
 
-pub const TUBE_LENGTH: f32 = 170.;
-pub const LEDS_COUNT: isize = 16;
+/// ID of a tube in the `TubeLayout`
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct TubeIndex(pub u16);
 
-#[derive(Copy, Clone, EnumIter, Eq, PartialEq, Debug)]
-pub enum TubeIndex {
-    One,
-    Two,
-    Three,
-    Four,
-    Five,
-    Six,
-    Seven,
-    Eight,
-    Nine,
-    Ten,
-    Eleven,
-    Twelve,
-    Thirteen,
-    Fourteen,
-    Fifteen,
-    Sixteen,
-    Seventeen,
-    Eighteen,
-    Nineteen,
-    Twenty,
-    Twentyone,
-    Twentytwo,
-}
-
-// Offset vertical:
-// * Main first diagonal: 83
-// * Main second diagonal: 166
-// * Main into top: ?
-// * Main horizontal distance from edge: 17
-// Horizontal:
-// * Main first diagonal: 142.5
-// * Second diagonal: ? (unnecessary, we go from the middle)
-// * Third diagonal: 142.5
-impl TubeIndex {
-    pub fn get_position(&self) -> Vec2 {
-        match self {
-            // Left fork
-            TubeIndex::One => { Vec2 { x: 194., y: 706.5 } } // ✅
-            TubeIndex::Two => { Vec2 { x: 194., y: 373.5 } } // ✅
-            TubeIndex::Three => { Vec2 { x: 336.5, y: 623. } } // ✅
-            TubeIndex::Four => { Vec2 { x: 336.5, y: 457. } } // ✅
-            TubeIndex::Five => { Vec2 { x: 479., y: 540. } } // ✅
-
-            // Main Diagonals left
-            TubeIndex::Six => { Vec2 { x: 621.5 +5., y: 623.+5. } } // ✅
-            TubeIndex::Seven => { Vec2 { x: 621.5+6., y: 457.-4. } } // ✅
-            TubeIndex::Eight => { Vec2 { x: 720.-3., y: 790.-5. } } // ✅ X EYEBALLED
-            TubeIndex::Nine => { Vec2 { x: 720.-2., y: 290.+5. } } // ✅ X EYEBALLED
-
-            // Main Horizontals
-            TubeIndex::Ten => { Vec2 { x: 868.75, y: 873. } } // ✅
-            TubeIndex::Eleven => { Vec2 { x: 868.75, y: 207. } } // ✅
-            TubeIndex::Twelve => { Vec2 { x: 1051.25, y: 873. } } // ✅
-            TubeIndex::Thirteen => { Vec2 { x: 1051.25, y: 207. } } // ✅
-
-            // Main Diagonals right
-            TubeIndex::Fourteen => { Vec2 { x: 1200.+3., y: 790.-6. } } // ✅ X EYEBALLED
-            TubeIndex::Fifteen => { Vec2 { x: 1200.+3., y: 290.+6. } } // ✅ X EYEBALLED
-            TubeIndex::Sixteen => { Vec2 { x: 1299.-7., y: 623.+5. } } // ✅
-            TubeIndex::Seventeen => { Vec2 { x: 1299.-7., y: 457.-5. } } // ✅
-
-            // Right fork
-            TubeIndex::Eighteen => { Vec2 { x: 1440., y: 540. } } // ✅
-            TubeIndex::Nineteen => { Vec2 { x: 1582.5, y: 623. } } // ✅
-            TubeIndex::Twenty => { Vec2 { x: 1582.5, y: 457. } } // ✅
-            TubeIndex::Twentyone => { Vec2 { x: 1725., y: 706.5 } } // ✅
-            TubeIndex::Twentytwo => { Vec2 { x: 1725., y: 373.5 } } // ✅
-
-            _ => { Vec2::default() }
-        }
-    }
-
-    pub fn get_rotation(&self) -> f32 {
-        match self {
-            TubeIndex::One => { 0. } // ✅
-            TubeIndex::Two => { PI } // ✅
-            TubeIndex::Three => { -PI / 3. } // ✅
-            TubeIndex::Four => { PI / 3. + PI  } // ✅
-            TubeIndex::Five => { 0. } // ✅
-            TubeIndex::Six => { PI / 3. } // ✅
-            TubeIndex::Seven => { -PI / 3. + PI } // ✅
-            TubeIndex::Eight => { PI / 3. } // ✅
-            TubeIndex::Nine => { -PI / 3. + PI } // ✅
-            TubeIndex::Ten => { 0. } // ✅
-            TubeIndex::Eleven => { PI } // ✅
-            TubeIndex::Twelve => { 0. } // ✅
-            TubeIndex::Thirteen => { PI } // ✅
-            TubeIndex::Fourteen => { -PI / 3. } // ✅
-            TubeIndex::Fifteen => { PI / 3. + PI } // ✅
-            TubeIndex::Sixteen => { -PI / 3. } // ✅
-            TubeIndex::Seventeen => { PI / 3. + PI } // ✅
-            TubeIndex::Eighteen => { 0. } // ✅
-            TubeIndex::Nineteen => { PI / 3. } // ✅
-            TubeIndex::Twenty => { -PI / 3. + PI  } // ✅
-            TubeIndex::Twentyone => { 0. } // ✅
-            TubeIndex::Twentytwo => { PI } // ✅
-        }
+impl fmt::Display for TubeIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Tube {:0>2}", self.0)
     }
 }
 
-/// One LED tube (size in screenspace: 171x9 )
+/// One LED tube (default size in screenspace: 171x9 )
 #[derive(Component)]
 pub struct LedTube {
     index: TubeIndex,
+    length: f32,
+    led_count: usize,
     //adjacent_hexagons: (HexagonDefinition, Option<HexagonDefinition>),
 }
 
 impl LedTube {
     pub fn get_tube_index(&self) -> TubeIndex { self.index }
+    pub fn get_length(&self) -> f32 { self.length }
+    pub fn get_led_count(&self) -> usize { self.led_count }
 }
 
 /// Single LED as part of the LED tube
-#[derive(Component, Default)]
+#[derive(Component)]
 pub struct LedTubeLed {
     index: isize,
+    /// LED count of the tube this LED belongs to
+    count: usize,
+    /// Composited colour of all layers, this is what the lights and outputs show
     pub color: Color,
+    /// Colour buffer of every layer, animations write into these
+    layers: [Color; LedLayer::COUNT],
+}
+
+impl Default for LedTubeLed {
+    fn default() -> Self {
+        Self {
+            index: 0,
+            count: 1,
+            color: Color::BLACK,
+            layers: [Color::NONE; LedLayer::COUNT],
+        }
+    }
 }
 
 impl LedTubeLed {
     pub fn get_index(&self) -> isize { self.index }
+    /// Position along the tube from 0 (first LED) to 1 (last LED)
+    pub fn get_relative_position(&self) -> f32 { self.index as f32 / (self.count.max(2) - 1) as f32 }
+    pub fn layer(&self, layer: LedLayer) -> Color { self.layers[layer.index()] }
+    pub fn layer_mut(&mut self, layer: LedLayer) -> &mut Color { &mut self.layers[layer.index()] }
+    pub fn clear_layers(&mut self) { self.layers = [Color::NONE; LedLayer::COUNT]; }
 }
 
 pub fn spawn_tube(
-    tube_index: TubeIndex,
+    definition: &TubeDefinition,
     mut commands: &mut Commands,
 ) {
-    let position = tube_index.get_position() - Vec2::new(1920./2., 1080./2.);
-    let angle = tube_index.get_rotation();
+    let position = definition.position() - Vec2::new(1920./2., 1080./2.);
+    let angle = definition.rotation();
     let tube_entity = commands.spawn((
         LedTube {
-            index: tube_index,
+            index: definition.index(),
+            length: definition.length,
+            led_count: definition.led_count,
         },
         SpatialBundle {
             transform: Transform::from_xyz(position.x, position.y, 0.).with_rotation(Quat::from_rotation_z(angle)),
@@ -148,12 +83,10 @@ pub fn spawn_tube(
         }
     )).id();
 
-    let step = TUBE_LENGTH / (LEDS_COUNT) as f32;
-
-    for i in 0..LEDS_COUNT {
-        let offset = -TUBE_LENGTH / 2. + (step) * (i as f32 + 0.5);
+    for i in 0..definition.led_count {
+        let offset = definition.led_offset(i);
         let led_tube_led_entity = commands.spawn((
-            LedTubeLed { index: i, ..default() },
+            LedTubeLed { index: i as isize, count: definition.led_count, ..default() },
             SpatialBundle {
                 transform: Transform::from_translation(Vec3::new(offset, 0., 0.)),
                 ..default()
@@ -162,4 +95,4 @@ pub fn spawn_tube(
         )).id();
         commands.entity(tube_entity).push_children(&[led_tube_led_entity]);
     }
-}
\ No newline at end of file
+}
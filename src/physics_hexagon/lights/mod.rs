@@ -1,18 +1,22 @@
 use bevy::app::App;
 use bevy::hierarchy::Children;
-use bevy::prelude::{Commands, Entity, Plugin, Query, Startup, Update, With};
-use strum::IntoEnumIterator;
+use bevy::prelude::{Commands, Entity, Plugin, Query, Res, Startup, Update, With};
 use crate::hexagon::HexagonDefinition;
-use crate::physics_hexagon::lights::led_tube::{LedTube, LedTubeLed, spawn_tube, TubeIndex};
+use crate::physics_hexagon::lights::led_tube::{LedTube, LedTubeLed, spawn_tube};
+use crate::physics_hexagon::lights::tube_layout::TubeLayout;
 use crate::physics_hexagon::PhysicsHexagon;
 
 pub mod led_tube;
+pub mod tube_layout;
+pub mod led_layers;
+pub mod led_symmetry;
 pub mod physical_lights;
 
 pub fn spawn_led_tubes(
-    mut commands: Commands
+    mut commands: Commands,
+    layout: Res<TubeLayout>,
 ) {
-    for index in TubeIndex::iter() {
-        spawn_tube(index, &mut commands)
+    for definition in layout.tubes.iter() {
+        spawn_tube(definition, &mut commands)
     }
 }
\ No newline at end of file
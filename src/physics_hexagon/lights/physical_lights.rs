@@ -6,7 +6,7 @@ use bevy::render::view::RenderLayers;
 use bevy::tasks::futures_lite::StreamExt;
 use bevy::utils::default;
 use crate::hexagon::HexagonDefinition;
-use crate::physics_hexagon::lights::led_tube::{LEDS_COUNT, LedTube, LedTubeLed, TUBE_LENGTH, TubeIndex};
+use crate::physics_hexagon::lights::led_tube::{LedTube, LedTubeLed, TubeIndex};
 use crate::physics_hexagon::PhysicsHexagon;
 use crate::propagating_render_layers::PropagatingRenderLayers;
 
@@ -15,7 +15,8 @@ const EDGE_LENGTH: f32 = 340.;
 const EDGE_LED_COUNT: i32 = 32;
 const EDGE_LED_DIVISOR: i32 = 4;
 const ACTUAL_EDGE_LED_COUNT: i32 = EDGE_LED_COUNT / EDGE_LED_DIVISOR;
-const LIGHTS_PER_TUBE: i32 = 16 / EDGE_LED_DIVISOR;
+/// Physical lights driven by one LED tube, each averages an equal part of the tube
+const LIGHTS_PER_TUBE: i32 = 4;
 
 /// Root component for all the lights of a hexagon
 #[derive(Component, Reflect)]
@@ -154,8 +155,9 @@ fn get_led_tube_led_entities(
         return vec![];
     };
 
-    let start_led_index = (index % LIGHTS_PER_TUBE) * LIGHTS_PER_TUBE;
-    let end_index = start_led_index + EDGE_LED_DIVISOR; //Exclusive index
+    let led_count = led_tube.get_led_count() as i32;
+    let start_led_index = (index % LIGHTS_PER_TUBE) * led_count / LIGHTS_PER_TUBE;
+    let end_index = (index % LIGHTS_PER_TUBE + 1) * led_count / LIGHTS_PER_TUBE; //Exclusive index
     let mut led_tube_led_matched_entities: Vec<Entity> = vec![];
     for led_tube_entity in led_tube_led_entities {
         let led_tube_led = led_tube_led_query.get(*led_tube_entity).unwrap();
@@ -164,8 +166,8 @@ fn get_led_tube_led_entities(
         }
     };
 
-    if led_tube_led_matched_entities.len() != EDGE_LED_DIVISOR as usize {
-        error!("Matched LED count for index {} is incorrect: {} (expected: {})", index, led_tube_led_matched_entities.len(), EDGE_LED_DIVISOR);
+    if led_tube_led_matched_entities.len() != (end_index - start_led_index) as usize {
+        error!("Matched LED count for index {} is incorrect: {} (expected: {})", index, led_tube_led_matched_entities.len(), end_index - start_led_index);
     }
 
     led_tube_led_matched_entities
@@ -174,21 +176,21 @@ fn get_led_tube_led_entities(
 fn map_index_to_tube_index(
     index: i32,
 ) -> TubeIndex {
-    if (00 * LIGHTS_PER_TUBE..01 * LIGHTS_PER_TUBE).contains(&index) { return TubeIndex::Six; };
-    if (01 * LIGHTS_PER_TUBE..02 * LIGHTS_PER_TUBE).contains(&index) { return TubeIndex::Eight; };
-    if (02 * LIGHTS_PER_TUBE..03 * LIGHTS_PER_TUBE).contains(&index) { return TubeIndex::Ten; };
-    if (03 * LIGHTS_PER_TUBE..04 * LIGHTS_PER_TUBE).contains(&index) { return TubeIndex::Twelve; };
-    if (04 * LIGHTS_PER_TUBE..05 * LIGHTS_PER_TUBE).contains(&index) { return TubeIndex::Fourteen; };
-    if (05 * LIGHTS_PER_TUBE..06 * LIGHTS_PER_TUBE).contains(&index) { return TubeIndex::Sixteen; };
-    if (06 * LIGHTS_PER_TUBE..07 * LIGHTS_PER_TUBE).contains(&index) { return TubeIndex::Seventeen; };
-    if (07 * LIGHTS_PER_TUBE..08 * LIGHTS_PER_TUBE).contains(&index) { return TubeIndex::Fifteen; };
-    if (08 * LIGHTS_PER_TUBE..09 * LIGHTS_PER_TUBE).contains(&index) { return TubeIndex::Thirteen; };
-    if (09 * LIGHTS_PER_TUBE..10 * LIGHTS_PER_TUBE).contains(&index) { return TubeIndex::Eleven; };
-    if (10 * LIGHTS_PER_TUBE..11 * LIGHTS_PER_TUBE).contains(&index) { return TubeIndex::Nine; };
-    if (11 * LIGHTS_PER_TUBE..12 * LIGHTS_PER_TUBE).contains(&index) { return TubeIndex::Seven; };
+    if (00 * LIGHTS_PER_TUBE..01 * LIGHTS_PER_TUBE).contains(&index) { return TubeIndex(6); };
+    if (01 * LIGHTS_PER_TUBE..02 * LIGHTS_PER_TUBE).contains(&index) { return TubeIndex(8); };
+    if (02 * LIGHTS_PER_TUBE..03 * LIGHTS_PER_TUBE).contains(&index) { return TubeIndex(10); };
+    if (03 * LIGHTS_PER_TUBE..04 * LIGHTS_PER_TUBE).contains(&index) { return TubeIndex(12); };
+    if (04 * LIGHTS_PER_TUBE..05 * LIGHTS_PER_TUBE).contains(&index) { return TubeIndex(14); };
+    if (05 * LIGHTS_PER_TUBE..06 * LIGHTS_PER_TUBE).contains(&index) { return TubeIndex(16); };
+    if (06 * LIGHTS_PER_TUBE..07 * LIGHTS_PER_TUBE).contains(&index) { return TubeIndex(17); };
+    if (07 * LIGHTS_PER_TUBE..08 * LIGHTS_PER_TUBE).contains(&index) { return TubeIndex(15); };
+    if (08 * LIGHTS_PER_TUBE..09 * LIGHTS_PER_TUBE).contains(&index) { return TubeIndex(13); };
+    if (09 * LIGHTS_PER_TUBE..10 * LIGHTS_PER_TUBE).contains(&index) { return TubeIndex(11); };
+    if (10 * LIGHTS_PER_TUBE..11 * LIGHTS_PER_TUBE).contains(&index) { return TubeIndex(9); };
+    if (11 * LIGHTS_PER_TUBE..12 * LIGHTS_PER_TUBE).contains(&index) { return TubeIndex(7); };
 
     error!("Index {} couldn't be mapped, returning default", index);
-    TubeIndex::Six
+    TubeIndex(6)
 }
 
 /// Update the lights by averaging the color values of all LEDs linked to them
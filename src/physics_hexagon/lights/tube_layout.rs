@@ -0,0 +1,92 @@
+//! Layout of the LED tubes, loaded from `assets/led_layout.ron` so the same binary can drive a
+//! different installation.
+
+use bevy::prelude::{error, info, Resource, Vec2};
+use serde::{Deserialize, Serialize};
+use crate::physics_hexagon::lights::led_tube::TubeIndex;
+
+pub const LAYOUT_PATH: &str = "assets/led_layout.ron";
+/// Layout of the GPN installation, used when the layout file can't be read
+const DEFAULT_LAYOUT: &str = include_str!("../../../assets/led_layout.ron");
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TubeDefinition {
+    pub id: u16,
+    /// Centre of the tube in screen space (1920x1080, origin bottom left, y up)
+    pub position: (f32, f32),
+    /// Degrees, counter clockwise
+    pub angle: f32,
+    pub length: f32,
+    pub led_count: usize,
+    /// LED 0 sits at the end of the tube instead of the start
+    #[serde(default)]
+    pub reversed: bool,
+}
+
+impl TubeDefinition {
+    pub fn index(&self) -> TubeIndex {
+        TubeIndex(self.id)
+    }
+
+    pub fn position(&self) -> Vec2 {
+        Vec2::new(self.position.0, self.position.1)
+    }
+
+    pub fn rotation(&self) -> f32 {
+        self.angle.to_radians()
+    }
+
+    /// Offset of a LED from the tube centre along the tube
+    pub fn led_offset(&self, led: usize) -> f32 {
+        let step = self.length / self.led_count.max(1) as f32;
+        let slot = if self.reversed { self.led_count.saturating_sub(led + 1) } else { led };
+        -self.length / 2. + step * (slot as f32 + 0.5)
+    }
+
+    /// Position of a LED in screen space
+    pub fn led_position(&self, led: usize) -> Vec2 {
+        self.position() + Vec2::from_angle(self.rotation()).rotate(Vec2::new(self.led_offset(led), 0.))
+    }
+}
+
+#[derive(Resource, Serialize, Deserialize, Clone, Debug)]
+pub struct TubeLayout {
+    pub tubes: Vec<TubeDefinition>,
+}
+
+impl Default for TubeLayout {
+    fn default() -> Self {
+        ron::from_str(DEFAULT_LAYOUT).expect("Default LED layout is invalid")
+    }
+}
+
+impl TubeLayout {
+    /// Load the layout file, falling back to the built in layout
+    pub fn load() -> Self {
+        let layout = std::fs::read_to_string(LAYOUT_PATH)
+            .map_err(|e| e.to_string())
+            .and_then(|content| ron::from_str::<TubeLayout>(&content).map_err(|e| e.to_string()));
+        match layout {
+            Ok(layout) => {
+                info!("Loaded {} LED tubes from {}", layout.tubes.len(), LAYOUT_PATH);
+                layout
+            }
+            Err(e) => {
+                error!("Couldn't load LED layout {}, using the default layout: {}", LAYOUT_PATH, e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn get(&self, index: TubeIndex) -> Option<&TubeDefinition> {
+        self.tubes.iter().find(|tube| tube.id == index.0)
+    }
+
+    pub fn indices(&self) -> impl Iterator<Item = TubeIndex> + '_ {
+        self.tubes.iter().map(|tube| tube.index())
+    }
+
+    pub fn led_count(&self) -> usize {
+        self.tubes.iter().map(|tube| tube.led_count).sum()
+    }
+}
@@ -16,6 +16,9 @@ use crate::hexagon::HexagonDefinition;
 use crate::physics_hexagon::effectors::EffectorsPlugin;
 use crate::physics_hexagon::hexagon_colliders::spawn_hexagon_collier;
 use crate::physics_hexagon::lights::{spawn_led_tubes};
+use crate::physics_hexagon::lights::led_layers::{composite_led_layers, LedLayerSettings};
+use crate::physics_hexagon::lights::led_symmetry::{apply_led_symmetry, LedSymmetry};
+use crate::physics_hexagon::lights::tube_layout::TubeLayout;
 use crate::physics_hexagon::lights::physical_lights::{drive_lights_system, HexagonLights, PhysicalLedTube, PhysicalLedTubeLed, PhysicalTubeIndex, spawn_physical_leds};
 use crate::physics_hexagon::render::PhysicsHexagonRenderTarget;
 use crate::propagating_render_layers::PropagatingRenderLayers;
@@ -26,13 +29,22 @@ impl Plugin for PhysicsHexagonPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins((EffectorsPlugin));
         app.init_resource::<PhysicsHexagonRenderTarget>();
+        app.insert_resource(TubeLayout::load());
         app.add_systems(Startup, (
             init_physics_hexagons,
             spawn_led_tubes.after(init_physics_hexagons),
             spawn_physical_leds.after(spawn_led_tubes)
         ));
         app.add_systems(Update, hexagon_physics_element_cleanup_system);
-        app.add_systems(PostUpdate, (drive_lights_system, lights_primary, lights_secondary));
+        app.init_resource::<LedLayerSettings>();
+        app.init_resource::<LedSymmetry>();
+        app.add_systems(PostUpdate, (
+            apply_led_symmetry.before(composite_led_layers),
+            composite_led_layers,
+            drive_lights_system.after(composite_led_layers),
+            lights_primary,
+            lights_secondary,
+        ));
         app.register_type::<PhysicalTubeIndex>();
         app.register_type::<PhysicalLedTube>();
         app.register_type::<PhysicalLedTubeLed>();
@@ -0,0 +1,252 @@
+//! Pixel mapping of 2D content onto the LED tubes. Every LED samples the selected image at its
+//! position, render targets are read back from the GPU, plain images are sampled on the CPU.
+
+pub mod readback;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use bevy::prelude::*;
+use bevy::render::extract_resource::ExtractResource;
+use bevy::render::render_resource::TextureFormat;
+use strum_macros::EnumIter;
+use crate::elements2d::render::Elements2dRendertarget;
+use crate::physics_hexagon::lights::led_layers::{composite_led_layers, LedLayer};
+use crate::physics_hexagon::lights::led_symmetry::apply_led_symmetry;
+use crate::physics_hexagon::lights::led_tube::{LedTube, LedTubeLed};
+use crate::physics_hexagon::render::PhysicsHexagonRenderTarget;
+use crate::pixel_map::readback::PixelMapReadbackPlugin;
+use crate::swirl::render_target::SwirlRenderTarget;
+
+pub struct PixelMapPlugin;
+
+impl Plugin for PixelMapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PixelMapSettings>();
+        app.init_resource::<PixelMapRequest>();
+        app.init_resource::<PixelMapSamples>();
+        app.add_plugins(PixelMapReadbackPlugin);
+        app.add_systems(PostUpdate, (
+            update_pixel_map_request,
+            apply_pixel_map.before(apply_led_symmetry).before(composite_led_layers),
+        ));
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, EnumIter)]
+pub enum PixelMapSource {
+    Elements2d,
+    PhysicsHexagons,
+    Swirl,
+    Image,
+}
+
+#[derive(Resource)]
+pub struct PixelMapSettings {
+    pub enabled: bool,
+    pub source: PixelMapSource,
+    /// Asset path of the image sampled for `PixelMapSource::Image`
+    pub image_path: String,
+    pub image: Option<Handle<Image>>,
+    /// Blur radius along the tube in LEDs
+    pub blur: usize,
+    /// How much the mapped colours cover the tube animations below
+    pub mix: f32,
+    /// Multiplier for the sampled colours
+    pub gain: f32,
+}
+
+impl Default for PixelMapSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            source: PixelMapSource::Elements2d,
+            image_path: String::new(),
+            image: None,
+            blur: 1,
+            mix: 1.,
+            gain: 1.,
+        }
+    }
+}
+
+/// Texels of a render target the LEDs need, extracted to the render world for the read back
+#[derive(Resource, Clone, Default, ExtractResource)]
+pub struct PixelMapRequest {
+    pub texture: Option<Handle<Image>>,
+    pub texels: Vec<UVec2>,
+}
+
+/// Latest texel colours read back from the GPU, written from the render world
+#[derive(Default)]
+pub struct ReadbackSamples {
+    pub texture: Option<AssetId<Image>>,
+    pub colors: HashMap<UVec2, Vec4>,
+}
+
+#[derive(Resource, Clone, Default)]
+pub struct PixelMapSamples(pub Arc<Mutex<ReadbackSamples>>);
+
+/// Texel of an image below a world position. World space matches the 1920x1080 screen space.
+pub fn world_to_texel(position: Vec2, size: UVec2) -> UVec2 {
+    let uv = Vec2::new(
+        (position.x + 1920. / 2.) / 1920.,
+        1. - (position.y + 1080. / 2.) / 1080.,
+    );
+    (uv.clamp(Vec2::ZERO, Vec2::ONE) * size.as_vec2())
+        .as_uvec2()
+        .min(size.max(UVec2::ONE) - UVec2::ONE)
+}
+
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1. } else { 1. };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f32;
+    match exponent {
+        0 => { sign * mantissa * 2f32.powi(-24) }
+        0x1f => { if mantissa == 0. { sign * f32::INFINITY } else { f32::NAN } }
+        _ => { sign * (1. + mantissa / 1024.) * 2f32.powi(exponent - 15) }
+    }
+}
+
+/// Bytes of one texel of the formats the pixel mapping can decode
+pub fn texel_size(format: TextureFormat) -> Option<usize> {
+    match format {
+        TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => { Some(4) }
+        TextureFormat::Rgba16Float => { Some(8) }
+        TextureFormat::Rgba32Float => { Some(16) }
+        _ => { None }
+    }
+}
+
+/// Decode one texel into linear RGBA
+pub fn decode_texel(format: TextureFormat, bytes: &[u8]) -> Option<Vec4> {
+    match format {
+        TextureFormat::Rgba8Unorm => {
+            Some(Vec4::from_array([0, 1, 2, 3].map(|i| bytes[i] as f32 / 255.)))
+        }
+        TextureFormat::Rgba8UnormSrgb => {
+            let [r, g, b, a] = Color::rgba_u8(bytes[0], bytes[1], bytes[2], bytes[3]).as_linear_rgba_f32();
+            Some(Vec4::new(r, g, b, a))
+        }
+        TextureFormat::Rgba16Float => {
+            Some(Vec4::from_array([0, 1, 2, 3].map(|i| f16_to_f32(u16::from_le_bytes([bytes[i * 2], bytes[i * 2 + 1]])))))
+        }
+        TextureFormat::Rgba32Float => {
+            Some(Vec4::from_array([0, 1, 2, 3].map(|i| f32::from_le_bytes([bytes[i * 4], bytes[i * 4 + 1], bytes[i * 4 + 2], bytes[i * 4 + 3]]))))
+        }
+        _ => { None }
+    }
+}
+
+/// Sample an image on the CPU
+fn sample_image(image: &Image, texel: UVec2) -> Option<Vec4> {
+    let format = image.texture_descriptor.format;
+    let size = texel_size(format)?;
+    let offset = (texel.y as usize * image.width() as usize + texel.x as usize) * size;
+    decode_texel(format, image.data.get(offset..offset + size)?)
+}
+
+fn render_target(
+    source: PixelMapSource,
+    elements2d: &Elements2dRendertarget,
+    physics_hexagons: &PhysicsHexagonRenderTarget,
+    swirl: &SwirlRenderTarget,
+) -> Option<Handle<Image>> {
+    match source {
+        PixelMapSource::Elements2d => { Some(elements2d.render_target.clone()) }
+        PixelMapSource::PhysicsHexagons => { Some(physics_hexagons.render_target.clone()) }
+        PixelMapSource::Swirl => { Some(swirl.render_target.clone()) }
+        PixelMapSource::Image => { None }
+    }
+}
+
+/// Request the texels below every LED from the selected render target
+pub fn update_pixel_map_request(
+    settings: Res<PixelMapSettings>,
+    mut request: ResMut<PixelMapRequest>,
+    images: Res<Assets<Image>>,
+    elements2d: Res<Elements2dRendertarget>,
+    physics_hexagons: Res<PhysicsHexagonRenderTarget>,
+    swirl: Res<SwirlRenderTarget>,
+    led_query: Query<&GlobalTransform, With<LedTubeLed>>,
+) {
+    let texture = render_target(settings.source, &elements2d, &physics_hexagons, &swirl)
+        .filter(|_| settings.enabled);
+    let Some(image) = texture.as_ref().and_then(|texture| images.get(texture)) else {
+        request.texture = None;
+        request.texels.clear();
+        return;
+    };
+
+    let size = image.size();
+    request.texels = led_query.iter()
+        .map(|gt| world_to_texel(gt.translation().truncate(), size))
+        .collect();
+    request.texels.sort_by_key(|texel| (texel.y, texel.x));
+    request.texels.dedup();
+    request.texture = texture;
+}
+
+/// Write the mapped colours into the pixel map layer, blurred along each tube
+pub fn apply_pixel_map(
+    mut settings: ResMut<PixelMapSettings>,
+    samples: Res<PixelMapSamples>,
+    request: Res<PixelMapRequest>,
+    images: Res<Assets<Image>>,
+    asset_server: Res<AssetServer>,
+    tube_query: Query<&Children, With<LedTube>>,
+    mut led_query: Query<(&mut LedTubeLed, &GlobalTransform)>,
+) {
+    if !settings.enabled { return; }
+
+    if settings.source == PixelMapSource::Image && !settings.image_path.is_empty() {
+        let loaded_path = settings.image.as_ref()
+            .and_then(|image| asset_server.get_path(image.id()))
+            .map(|path| path.to_string());
+        if loaded_path.as_deref() != Some(settings.image_path.as_str()) {
+            settings.image = Some(asset_server.load(settings.image_path.clone()));
+        }
+    }
+
+    let cpu_image = settings.image.as_ref()
+        .and_then(|image| images.get(image))
+        .filter(|_| settings.source == PixelMapSource::Image);
+    let readback_size = request.texture.as_ref()
+        .and_then(|texture| images.get(texture))
+        .map(|image| image.size());
+    let readback = samples.0.lock().unwrap();
+    let readback_current = readback.texture.is_some() && readback.texture == request.texture.as_ref().map(|texture| texture.id());
+
+    for children in tube_query.iter() {
+        let mut leds: Vec<(Entity, isize, Vec4)> = children.iter()
+            .filter_map(|child| {
+                let (ltl, gt) = led_query.get(*child).ok()?;
+                let position = gt.translation().truncate();
+                let color = if let Some(image) = cpu_image {
+                    sample_image(image, world_to_texel(position, image.size()))
+                } else if let (true, Some(size)) = (readback_current, readback_size) {
+                    readback.colors.get(&world_to_texel(position, size)).cloned()
+                } else {
+                    None
+                };
+                Some((*child, ltl.get_index(), color?))
+            })
+            .collect();
+        leds.sort_by_key(|(_, index, _)| *index);
+
+        let radius = settings.blur as isize;
+        for (i, (entity, _, _)) in leds.iter().enumerate() {
+            let start = (i as isize - radius).max(0) as usize;
+            let end = (i + settings.blur + 1).min(leds.len());
+            let sum: Vec4 = leds[start..end].iter().map(|(_, _, color)| *color).sum();
+            let average = sum / (end - start) as f32;
+            let Ok((mut ltl, _)) = led_query.get_mut(*entity) else { continue; };
+            *ltl.layer_mut(LedLayer::PixelMap) = Color::rgba_linear(
+                average.x * settings.gain,
+                average.y * settings.gain,
+                average.z * settings.gain,
+                settings.mix,
+            );
+        }
+    }
+}
@@ -0,0 +1,190 @@
+//! Read back the texels requested by the pixel mapping from a render target. Only the texels
+//! below the LEDs are copied, into a small buffer that is mapped asynchronously so the main
+//! world never waits for the GPU. The samples arrive a few frames late, which the LEDs don't show.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use bevy::prelude::*;
+use bevy::render::{Render, RenderApp, RenderSet};
+use bevy::render::extract_resource::ExtractResourcePlugin;
+use bevy::render::graph::CameraDriverLabel;
+use bevy::render::render_asset::RenderAssets;
+use bevy::render::render_graph::{Node, NodeRunError, RenderGraph, RenderGraphContext, RenderLabel};
+use bevy::render::render_resource::{Buffer, BufferDescriptor, BufferUsages, Extent3d, ImageCopyBuffer, ImageCopyTexture, ImageDataLayout, Maintain, MapMode, Origin3d, TextureAspect, TextureFormat};
+use bevy::render::renderer::{RenderContext, RenderDevice};
+use crate::pixel_map::{decode_texel, PixelMapRequest, PixelMapSamples, texel_size};
+
+/// Texels are copied to a multiple of this offset, the largest texel we decode is 16 bytes
+const TEXEL_STRIDE: u64 = 16;
+
+pub struct PixelMapReadbackPlugin;
+
+impl Plugin for PixelMapReadbackPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(ExtractResourcePlugin::<PixelMapRequest>::default());
+    }
+
+    fn finish(&self, app: &mut App) {
+        let samples = app.world.resource::<PixelMapSamples>().clone();
+        let Ok(render_app) = app.get_sub_app_mut(RenderApp) else { return; };
+        render_app.insert_resource(samples);
+        render_app.init_resource::<PixelMapReadback>();
+        render_app.add_systems(Render, (
+            prepare_pixel_map_readback.in_set(RenderSet::Prepare),
+            map_pixel_map_readback.in_set(RenderSet::Cleanup),
+        ));
+
+        let mut render_graph = render_app.world.resource_mut::<RenderGraph>();
+        render_graph.add_node(PixelMapReadbackLabel, PixelMapReadbackNode);
+        render_graph.add_node_edge(CameraDriverLabel, PixelMapReadbackLabel);
+    }
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+struct PixelMapReadbackLabel;
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum ReadbackState {
+    Idle,
+    /// The node copies the texels into the buffer this frame
+    Copy,
+    /// Waiting for the buffer to be mapped
+    Mapping,
+}
+
+#[derive(Resource)]
+struct PixelMapReadback {
+    state: ReadbackState,
+    buffer: Option<Buffer>,
+    texture: Option<AssetId<Image>>,
+    format: TextureFormat,
+    texels: Vec<UVec2>,
+    mapped: Arc<AtomicBool>,
+}
+
+impl Default for PixelMapReadback {
+    fn default() -> Self {
+        Self {
+            state: ReadbackState::Idle,
+            buffer: None,
+            texture: None,
+            format: TextureFormat::Rgba16Float,
+            texels: vec![],
+            mapped: Arc::new(AtomicBool::new(false)),
+        }
+    }
+}
+
+impl PixelMapReadback {
+    fn size(&self) -> u64 {
+        self.texels.len() as u64 * TEXEL_STRIDE
+    }
+}
+
+/// Hand finished samples to the main world and start the next copy
+fn prepare_pixel_map_readback(
+    mut readback: ResMut<PixelMapReadback>,
+    request: Res<PixelMapRequest>,
+    samples: Res<PixelMapSamples>,
+    gpu_images: Res<RenderAssets<Image>>,
+    render_device: Res<RenderDevice>,
+) {
+    if readback.state == ReadbackState::Mapping {
+        render_device.poll(Maintain::Poll);
+        if !readback.mapped.load(Ordering::Acquire) {
+            return;
+        }
+        if let Some(buffer) = &readback.buffer {
+            let mut samples = samples.0.lock().unwrap();
+            samples.texture = readback.texture;
+            samples.colors.clear();
+            {
+                let data = buffer.slice(0..readback.size()).get_mapped_range();
+                for (i, texel) in readback.texels.iter().enumerate() {
+                    let offset = i * TEXEL_STRIDE as usize;
+                    if let Some(color) = decode_texel(readback.format, &data[offset..offset + TEXEL_STRIDE as usize]) {
+                        samples.colors.insert(*texel, color);
+                    }
+                }
+            }
+            buffer.unmap();
+        }
+        readback.state = ReadbackState::Idle;
+    }
+
+    let Some(texture) = &request.texture else { return; };
+    let Some(gpu_image) = gpu_images.get(texture) else { return; };
+    if request.texels.is_empty() || texel_size(gpu_image.texture_format).is_none() {
+        return;
+    }
+
+    readback.texture = Some(texture.id());
+    readback.format = gpu_image.texture_format;
+    readback.texels = request.texels.clone();
+    let size = readback.size();
+    if readback.buffer.as_ref().map(|buffer| buffer.size() < size).unwrap_or(true) {
+        readback.buffer = Some(render_device.create_buffer(&BufferDescriptor {
+            label: Some("pixel_map_readback_buffer"),
+            size,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        }));
+    }
+    readback.state = ReadbackState::Copy;
+}
+
+/// Map the buffer after the copy was submitted
+fn map_pixel_map_readback(
+    mut readback: ResMut<PixelMapReadback>,
+) {
+    if readback.state != ReadbackState::Copy { return; }
+    let Some(buffer) = &readback.buffer else { return; };
+
+    let mapped = readback.mapped.clone();
+    mapped.store(false, Ordering::Release);
+    buffer.slice(0..readback.size()).map_async(MapMode::Read, move |result| {
+        match result {
+            Ok(_) => { mapped.store(true, Ordering::Release) }
+            Err(e) => { error!("Couldn't map pixel map readback buffer: {}", e) }
+        }
+    });
+    readback.state = ReadbackState::Mapping;
+}
+
+struct PixelMapReadbackNode;
+
+impl Node for PixelMapReadbackNode {
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let readback = world.resource::<PixelMapReadback>();
+        if readback.state != ReadbackState::Copy { return Ok(()); }
+        let (Some(buffer), Some(texture)) = (&readback.buffer, readback.texture) else { return Ok(()); };
+        let Some(gpu_image) = world.resource::<RenderAssets<Image>>().get(texture) else { return Ok(()); };
+
+        let encoder = render_context.command_encoder();
+        for (i, texel) in readback.texels.iter().enumerate() {
+            encoder.copy_texture_to_buffer(
+                ImageCopyTexture {
+                    texture: &gpu_image.texture,
+                    mip_level: 0,
+                    origin: Origin3d { x: texel.x, y: texel.y, z: 0 },
+                    aspect: TextureAspect::All,
+                },
+                ImageCopyBuffer {
+                    buffer,
+                    layout: ImageDataLayout {
+                        offset: i as u64 * TEXEL_STRIDE,
+                        bytes_per_row: None,
+                        rows_per_image: None,
+                    },
+                },
+                Extent3d { width: 1, height: 1, depth_or_array_layers: 1 },
+            );
+        }
+        Ok(())
+    }
+}
@@ -111,6 +111,8 @@ pub struct SwirlAutomation {
     pub preset: usize,
     pub fix_fb_rot: bool,
     pub fix_pal: bool,
+    /// Axis of the colour rotation taken from the active palette instead of the preset
+    pub palette_col_rot: Option<Color>,
 }
 
 pub fn swirl_beat(
@@ -206,7 +208,10 @@ pub fn swirl_beat(
 
         new_params.offset_strength = preset.offset_strength.clone();
         new_params.uv_scale = preset.uv_scale.clone();
-        new_params.col_rot = preset.col_rot.clone();
+        new_params.col_rot = match automation.palette_col_rot {
+            Some(axis) => { axis.with_a(preset.col_rot.a()) }
+            None => { preset.col_rot.clone() }
+        };
         new_params.fb_strength = preset.fb_strength.clone();
 
         material.params = new_params;